@@ -32,6 +32,30 @@ impl Decoder {
             Ok(decoder) => Ok(decoder),
         }
     }
+
+    /// Reads the tags and the duration of the data without decoding it.
+    ///
+    /// Returns `None` if the format of the data has not been recognized.
+    pub fn read_tags<R: Read + Seek + Send + Sync + 'static>(data: R) -> Option<Tags> {
+        let mss = MediaSourceStream::new(
+            Box::new(ReadSeekSource::new(data)) as Box<dyn MediaSource>,
+            ::symphonia::core::io::MediaSourceStreamOptions::default(),
+        );
+        symphonia::read_tags(mss, Some("mp4"))
+    }
+}
+
+/// Metadata of a file, each field is `None` when the file doesn't have it.
+#[derive(Debug, Clone, Default)]
+pub struct Tags {
+    /// Title of the track.
+    pub title: Option<String>,
+    /// Artist of the track, or of the album when the track has none.
+    pub artist: Option<String>,
+    /// Album of the track.
+    pub album: Option<String>,
+    /// Length of the default track of the file.
+    pub duration: Option<Duration>,
 }
 
 #[derive(Debug)]
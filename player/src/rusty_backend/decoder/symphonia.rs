@@ -6,7 +6,7 @@ use symphonia::{
         errors::Error,
         formats::{FormatReader, SeekMode, SeekTo},
         io::MediaSourceStream,
-        // meta::MetadataOptions,
+        meta::{StandardTagKey, Tag},
         probe::Hint,
         units::{Time, TimeBase},
     },
@@ -15,6 +15,46 @@ use symphonia::{
 
 use super::DecoderError;
 use super::Source;
+use super::Tags;
+
+/// Reads the tags of the container and the duration of its default track.
+pub fn read_tags(mss: MediaSourceStream, extension: Option<&str>) -> Option<Tags> {
+    let mut hint = Hint::new();
+    if let Some(ext) = extension {
+        hint.with_extension(ext);
+    }
+    let format_opts = symphonia::core::formats::FormatOptions::default();
+    let metadata_opts = symphonia::core::meta::MetadataOptions::default();
+    let mut probed = get_probe()
+        .format(&hint, mss, &format_opts, &metadata_opts)
+        .ok()?;
+    // Some formats have their tags before the container, like ID3 in front of an MP3
+    let mut all_tags: Vec<Tag> = probed
+        .metadata
+        .get()
+        .and_then(|x| x.current().map(|x| x.tags().to_vec()))
+        .unwrap_or_default();
+    if let Some(revision) = probed.format.metadata().current() {
+        all_tags.extend_from_slice(revision.tags());
+    }
+    let find = |key: StandardTagKey| {
+        all_tags
+            .iter()
+            .find(|x| x.std_key == Some(key))
+            .map(|x| x.value.to_string())
+    };
+    let duration = probed.format.default_track().and_then(|track| {
+        let params = &track.codec_params;
+        let time = params.time_base?.calc_time(params.n_frames?);
+        Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+    });
+    Some(Tags {
+        title: find(StandardTagKey::TrackTitle),
+        artist: find(StandardTagKey::Artist).or_else(|| find(StandardTagKey::AlbumArtist)),
+        album: find(StandardTagKey::Album),
+        duration,
+    })
+}
 
 #[allow(clippy::module_name_repetitions)]
 pub struct SymphoniaDecoder {
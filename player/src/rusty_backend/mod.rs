@@ -18,21 +18,33 @@ pub use cpal::{
 };
 pub use decoder::Decoder;
 use flume::Sender;
-pub use sink::Sink;
+pub use sink::{Sink, VolumeControl};
 pub use source::Source;
 pub use stream::{OutputStream, OutputStreamHandle, PlayError, StreamError};
 
-use std::path::Path;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 use std::time::Duration;
 use std::{fs::File, io::BufReader};
 
+use tokio_util::sync::CancellationToken;
+
+use self::source::Amplify;
 use self::stream::CpalDeviceExt;
 
 static VOLUME_STEP: u8 = 5;
+static MIN_SPEED: f32 = 0.25;
+static MAX_SPEED: f32 = 3.0;
+/// Interval between two changes of the volume while fading it
+static FADE_STEP: Duration = Duration::from_millis(50);
 
 pub struct Player {
     sink: Sink,
+    /// The sink of the previous track while it fades out
+    fading_sink: Option<Sink>,
+    /// Path and total duration of the track queued after the current one, if any
+    preloaded: Option<(PathBuf, Option<Duration>)>,
     data: PlayerData,
     error_sender: Sender<StreamError>,
     options: PlayerOptions,
@@ -46,15 +58,64 @@ pub struct Guard {
 #[derive(Clone)]
 pub struct PlayerData {
     total_duration: Option<Duration>,
-    volume: u8,
+    current_path: Option<PathBuf>,
+    /// Volume of the player, shared with the task fading it
+    volume: Arc<Mutex<Volume>>,
+    /// Cancels the volume fade in progress, if any
+    fade: Option<CancellationToken>,
+    speed: f32,
     safe_guard: bool,
 }
 
+impl PlayerData {
+    /// Cancels the volume fade in progress, if any, and locks the volume.
+    ///
+    /// The fade is cancelled while the lock is held so none of its steps can override the
+    /// volume set afterwards.
+    fn stop_fade(&mut self) -> MutexGuard<'_, Volume> {
+        let volume = self.volume.lock().unwrap();
+        if let Some(fade) = self.fade.take() {
+            fade.cancel();
+        }
+        volume
+    }
+}
+
+/// Volume of the player and the sink of the current track it is applied to
+struct Volume {
+    percent: u8,
+    sink: VolumeControl,
+}
+
+impl Volume {
+    /// Changes the volume, heard right away on the current track
+    fn set(&mut self, percent: u8) {
+        self.percent = percent;
+        self.sink.set(f32::from(percent) / 100.0);
+    }
+    /// Applies the volume to `sink`, which becomes the one of the current track
+    fn attach(&mut self, sink: &Sink) {
+        self.sink = sink.volume_control();
+        self.set(self.percent);
+    }
+}
+
 /// Options to configure the player behavior
 #[derive(Debug, Clone)]
 pub struct PlayerOptions {
     /// Initial volume of the player, in percent.
     pub initial_volume: u8,
+    /// Duration of the crossfade between two tracks, in seconds.
+    /// Clamped at 10, zero disables the crossfade.
+    pub crossfade_secs: u8,
+    /// Number of seconds skipped by `seek_fw` and `seek_bw`.
+    pub seek_step_secs: u8,
+    /// Gains of the bands of the equalizer (see `source::EQ_FREQUENCIES`), in dB.
+    /// Clamped between -12 and 12, all zero disables the equalizer.
+    pub eq_bands: [f32; 10],
+    /// Gains leveling the loudness of the tracks, in dB, by file name without its extension.
+    /// The tracks without a gain are played unchanged, `None` disables the normalization.
+    pub track_gains: Option<Arc<RwLock<HashMap<String, f32>>>>,
 }
 
 impl Player {
@@ -108,14 +169,24 @@ impl Player {
         let sink = Sink::try_new(&handle)?;
         let volume = options.initial_volume.min(100);
         sink.set_volume(f32::from(volume) / 100.0);
+        sink.set_eq_bands(&options.eq_bands);
+        let volume = Arc::new(Mutex::new(Volume {
+            percent: volume,
+            sink: sink.volume_control(),
+        }));
 
         Ok((
             Self {
                 sink,
+                fading_sink: None,
+                preloaded: None,
                 error_sender,
                 data: PlayerData {
                     total_duration: None,
+                    current_path: None,
                     volume,
+                    fade: None,
+                    speed: 1.0,
                     safe_guard: false,
                 },
                 options,
@@ -126,15 +197,85 @@ impl Player {
             },
         ))
     }
+    /// Returns the names of the available output devices and the name of the default one
+    pub fn output_device_names() -> Result<(Vec<String>, Option<String>), cpal::DevicesError> {
+        let host = cpal::default_host();
+        let names = host
+            .output_devices()?
+            .filter_map(|device| device.name().ok())
+            .collect();
+        let default = host
+            .default_output_device()
+            .and_then(|device| device.name().ok());
+        Ok((names, default))
+    }
+    /// Plays silence on the default output device for `duration`, to check that it works
+    pub fn test_output(duration: Duration) -> Result<(), PlayError> {
+        let (error_sender, error_receiver) = flume::unbounded();
+        let (_stream, handle) = Self::try_default(error_sender).map_err(PlayError::StreamError)?;
+        let mut sink = Sink::try_new(&handle)?;
+        sink.append(source::Zero::<f32>::new(2, 44100).take_duration(duration));
+        std::thread::sleep(duration);
+        match error_receiver.try_recv() {
+            Ok(e) => Err(PlayError::StreamError(e)),
+            Err(_) => Ok(()),
+        }
+    }
+    /// Moves the playback to the first output device whose name contains `name` (case-insensitive).
+    ///
+    /// The current track is reopened on the new device at the same position.
+    pub fn set_output_device(&mut self, name: &str, guard: &mut Guard) -> Result<(), PlayError> {
+        let lowercase_name = name.to_lowercase();
+        let device = cpal::default_host()
+            .output_devices()
+            .ok()
+            .and_then(|mut devices| {
+                devices.find(|device| {
+                    device
+                        .name()
+                        .is_ok_and(|x| x.to_lowercase().contains(&lowercase_name))
+                })
+            })
+            .ok_or_else(|| PlayError::DeviceNotFound(name.to_owned()))?;
+        let (stream, handle) = Self::try_from_device(&device, self.error_sender.clone())
+            .map_err(PlayError::StreamError)?;
+        let mut sink = Sink::try_new(&handle)?;
+        sink.set_speed(self.data.speed);
+        sink.set_eq_bands(&self.options.eq_bands);
+        if let Some(path) = &self.data.current_path {
+            if !self.sink.is_empty() {
+                sink.append(self.open(path)?);
+                sink.seek(Duration::from_secs(u64::from(self.elapsed())));
+                if self.sink.is_paused() {
+                    sink.pause();
+                }
+            }
+        }
+
+        if let Some(fading_sink) = self.fading_sink.take() {
+            fading_sink.destroy();
+        }
+        self.sink.destroy();
+        self.replace_sink(sink);
+        self.preloaded = None;
+        *guard = Guard {
+            _stream: stream,
+            handle,
+        };
+        Ok(())
+    }
     pub fn update(&self) -> Result<(Self, Guard), PlayError> {
         let (stream, handle) =
             Self::try_default(self.error_sender.clone()).map_err(PlayError::StreamError)?;
         let sink = Sink::try_new(&handle)?;
-        let volume = self.data.volume;
-        sink.set_volume(f32::from(volume) / 100.0);
+        self.data.volume.lock().unwrap().attach(&sink);
+        sink.set_speed(self.data.speed);
+        sink.set_eq_bands(&self.options.eq_bands);
         Ok((
             Self {
                 sink,
+                fading_sink: None,
+                preloaded: None,
                 error_sender: self.error_sender.clone(),
                 data: self.data.clone(),
                 options: self.options.clone(),
@@ -150,33 +291,137 @@ impl Player {
 #[allow(unused)]
 impl Player {
     pub fn change_volume(&mut self, positive: bool) {
-        if positive {
-            self.data.volume += VOLUME_STEP;
-        } else if self.data.volume >= VOLUME_STEP {
-            self.data.volume -= VOLUME_STEP;
+        let mut volume = self.data.stop_fade();
+        let percent = if positive {
+            volume.percent + VOLUME_STEP
         } else {
-            self.data.volume = 0;
-        }
-        self.data.volume = self.data.volume.min(100);
-        self.sink.set_volume(f32::from(self.data.volume) / 100.0);
+            volume.percent.saturating_sub(VOLUME_STEP)
+        };
+        volume.set(percent.min(100));
+    }
+    /// Makes `sink` the one of the current track, playing at the volume of the player.
+    ///
+    /// Returns the previous sink.
+    fn replace_sink(&mut self, sink: Sink) -> Sink {
+        self.data.volume.lock().unwrap().attach(&sink);
+        std::mem::replace(&mut self.sink, sink)
     }
     pub fn is_finished(&self) -> bool {
         self.sink.is_empty()
     }
-    pub fn play(&mut self, path: &Path, guard: &Guard) -> Result<(), PlayError> {
-        self.stop(guard);
+    /// Decodes the given file, amplified by its gain in `PlayerOptions::track_gains`.
+    fn open(&self, path: &Path) -> Result<Amplify<impl Source<Item = i16>>, PlayError> {
         let file = File::open(path).map_err(PlayError::Io)?;
-        //println!("{:?}", path);
         let decoder =
             Decoder::new_decoder(BufReader::new(file)).map_err(PlayError::DecoderError)?;
+        let gain = self
+            .options
+            .track_gains
+            .as_ref()
+            .and_then(|gains| {
+                let name = path.file_stem()?.to_str()?;
+                gains.read().unwrap().get(name).copied()
+            })
+            .unwrap_or(0.0);
+        Ok(decoder.amplify(10f32.powf(gain / 20.0)))
+    }
+    pub fn play(&mut self, path: &Path, guard: &Guard) -> Result<(), PlayError> {
+        self.stop(guard);
+        let decoder = self.open(path)?;
         self.data.total_duration = decoder.total_duration();
+        self.data.current_path = Some(path.to_path_buf());
         self.sink.append(decoder);
         Ok(())
     }
+    /// Starts playing the given file while the current track fades out.
+    ///
+    /// Both tracks are played simultaneously for `PlayerOptions::crossfade_secs` seconds.
+    pub fn crossfade_to(&mut self, path: &Path, guard: &Guard) -> Result<(), PlayError> {
+        let decoder = self.open(path)?;
+        let fade_duration = Duration::from_secs(u64::from(self.crossfade_secs()));
+        let mut sink = Sink::try_new(&guard.handle)?;
+        sink.set_volume(0.0);
+        sink.set_speed(self.data.speed);
+        sink.set_eq_bands(&self.options.eq_bands);
+        if self.sink.is_paused() {
+            sink.pause();
+        }
+        self.data.total_duration = decoder.total_duration();
+        self.data.current_path = Some(path.to_path_buf());
+        sink.append(decoder);
+        {
+            let mut volume = self.data.volume.lock().unwrap();
+            volume.sink = sink.volume_control();
+            sink.fade_to(f32::from(volume.percent) / 100.0, fade_duration);
+        }
+
+        if let Some(fading_sink) = self.fading_sink.take() {
+            fading_sink.destroy();
+        }
+        let previous_sink = std::mem::replace(&mut self.sink, sink);
+        previous_sink.clear_queued();
+        previous_sink.fade_to(0.0, fade_duration);
+        self.preloaded = None;
+        self.fading_sink = Some(previous_sink);
+        Ok(())
+    }
+    /// Duration of the crossfade in seconds, zero if disabled
+    pub fn crossfade_secs(&self) -> u8 {
+        self.options.crossfade_secs.min(10)
+    }
+    /// Fraction of the current track played during the crossfade with the next one
+    pub fn crossfade_fraction(&self) -> f64 {
+        match self.duration() {
+            // The duration is in track time so the crossfade covers more of it when sped up
+            Some(duration) if duration > 0.0 && self.crossfade_secs() > 0 => {
+                (f64::from(self.crossfade_secs()) * f64::from(self.data.speed) / duration).min(1.0)
+            }
+            _ => 0.0,
+        }
+    }
+    /// Queues the given file after the current track so it plays without any gap.
+    ///
+    /// Replaces the track previously preloaded, if any.
+    pub fn preload_next(&mut self, path: &Path) -> Result<(), PlayError> {
+        let decoder = self.open(path)?;
+        self.preloaded = Some((path.to_path_buf(), decoder.total_duration()));
+        self.sink.append(decoder);
+        Ok(())
+    }
+    /// Removes the preloaded track so it won't be played after the current one
+    pub fn cancel_preload(&mut self) {
+        if self.preloaded.take().is_some() {
+            self.sink.clear_queued();
+        }
+    }
+    /// Returns true if a track is queued after the current one
+    pub fn has_preloaded(&self) -> bool {
+        self.preloaded.is_some()
+    }
+    /// Checks if the preloaded track started playing and, if so, makes it the current track.
+    ///
+    /// Returns true when the player moved to the preloaded track.
+    pub fn advance_to_preloaded(&mut self) -> bool {
+        if self.preloaded.is_some() && self.sink.len() <= 1 {
+            if let Some((path, total_duration)) = self.preloaded.take() {
+                self.data.current_path = Some(path);
+                self.data.total_duration = total_duration;
+            }
+            !self.sink.is_empty()
+        } else {
+            false
+        }
+    }
     pub fn stop(&mut self, guard: &Guard) -> Result<(), PlayError> {
+        self.preloaded = None;
+        if let Some(fading_sink) = self.fading_sink.take() {
+            fading_sink.destroy();
+        }
         self.sink.destroy();
-        self.sink = Sink::try_new(&guard.handle)?;
-        self.sink.set_volume(f32::from(self.data.volume) / 100.0);
+        let sink = Sink::try_new(&guard.handle)?;
+        sink.set_speed(self.data.speed);
+        sink.set_eq_bands(&self.options.eq_bands);
+        self.replace_sink(sink);
         Ok(())
     }
     pub fn elapsed(&self) -> u32 {
@@ -191,7 +436,7 @@ impl Player {
         self.sink.toggle_playback();
     }
     pub fn seek_fw(&mut self) {
-        let new_pos = self.elapsed() as f64 + 5.0;
+        let new_pos = self.elapsed() as f64 + f64::from(self.options.seek_step_secs);
         if let Some(duration) = self.duration() {
             if new_pos > duration {
                 self.data.safe_guard = true;
@@ -201,7 +446,7 @@ impl Player {
         }
     }
     pub fn seek_bw(&self) {
-        let mut new_pos = self.elapsed()  as f64 - 5.0;
+        let mut new_pos = self.elapsed() as f64 - f64::from(self.options.seek_step_secs);
         if new_pos < 0.0 {
             new_pos = 0.0;
         }
@@ -218,7 +463,42 @@ impl Player {
         })
     }
     pub fn volume_percent(&self) -> u8 {
-        self.data.volume
+        self.data.volume.lock().unwrap().percent
+    }
+    /// Number of seconds skipped when seeking forward or backward
+    pub fn seek_step_secs(&self) -> u8 {
+        self.options.seek_step_secs
+    }
+    /// Changes the number of seconds skipped when seeking forward or backward
+    pub fn set_seek_step_secs(&mut self, secs: u8) {
+        self.options.seek_step_secs = secs;
+    }
+    /// Gains of the bands of the equalizer, in dB
+    pub fn eq_bands(&self) -> [f32; 10] {
+        self.options.eq_bands
+    }
+    /// Changes the gains of the bands of the equalizer, heard right away
+    pub fn set_eq_bands(&mut self, gains: &[f32; 10]) {
+        self.options.eq_bands = *gains;
+        self.sink.set_eq_bands(gains);
+        if let Some(fading_sink) = &self.fading_sink {
+            fading_sink.set_eq_bands(gains);
+        }
+    }
+    /// Playback rate of the player, `1.0` being the normal speed
+    pub fn speed(&self) -> f32 {
+        self.data.speed
+    }
+    /// Changes the playback rate, clamped between 0.25 and 3.0.
+    ///
+    /// Elapsed time, duration and seeking stay in track time, so `percentage` is unaffected.
+    pub fn set_speed(&mut self, rate: f32) {
+        self.data.speed = if rate.is_finite() {
+            rate.clamp(MIN_SPEED, MAX_SPEED)
+        } else {
+            1.0
+        };
+        self.sink.set_speed(self.data.speed);
     }
 }
 
@@ -228,7 +508,7 @@ impl Player {
     }
 
     pub fn volume(&self) -> i32 {
-        self.data.volume.into()
+        self.volume_percent().into()
     }
 
     pub fn volume_up(&mut self) {
@@ -248,8 +528,47 @@ impl Player {
         } else if volume < 0 {
             volume = 0;
         }
-        self.data.volume = volume as u8;
-        self.sink.set_volume((volume as f32) / 100.0);
+        self.data.stop_fade().set(volume as u8);
+    }
+
+    /// Smoothly changes the volume to `target` percent over `duration` without blocking.
+    ///
+    /// The volume is stepped every 50 ms by a task of the tokio runtime, so the fade progresses
+    /// whether a track is playing or not and `volume_percent` follows it. It is cancelled by any
+    /// following call to `fade_volume`, `set_volume` or `change_volume`. Outside of a runtime,
+    /// the volume is set right away.
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss
+    )]
+    pub fn fade_volume(&mut self, target: u8, duration: Duration) {
+        let target = target.min(100);
+        let from = self.data.stop_fade().percent;
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            self.set_volume(target.into());
+            return;
+        };
+        let steps = (duration.as_millis() / FADE_STEP.as_millis()).max(1) as u32;
+        let token = CancellationToken::new();
+        let cancelled = token.clone();
+        let volume = self.data.volume.clone();
+        runtime.spawn(async move {
+            for step in 1..=steps {
+                tokio::select! {
+                    () = cancelled.cancelled() => return,
+                    () = tokio::time::sleep(FADE_STEP) => {}
+                }
+                let mut volume = volume.lock().unwrap();
+                if cancelled.is_cancelled() {
+                    return;
+                }
+                let progress = step as f32 / steps as f32;
+                let percent = f32::from(from) + (f32::from(target) - f32::from(from)) * progress;
+                volume.set(percent.round() as u8);
+            }
+        });
+        self.data.fade = Some(token);
     }
 
     pub fn pause(&self) {
@@ -67,6 +67,12 @@ where
             = Some(Box::new(source) as Box<_>);
     }
 
+    /// Removes the sound waiting to be played, if any.
+    #[inline]
+    pub fn clear(&self) {
+        *self.next_sounds.lock().unwrap() = None;
+    }
+
     /// Sets whether the queue stays alive if there's no more sound to play.
     ///
     /// See also the constructor.
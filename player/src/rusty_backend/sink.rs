@@ -1,6 +1,6 @@
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use atomic_float::AtomicF32;
 
@@ -15,7 +15,7 @@ pub struct Sink {
     queue_tx: Arc<queue::SourcesQueueInput<f32>>,
 
     controls: Arc<Controls>,
-    sound_playing: Arc<AtomicBool>,
+    sound_count: Arc<AtomicUsize>,
 
     detached: bool,
 
@@ -25,8 +25,57 @@ pub struct Sink {
 struct Controls {
     pause: AtomicBool,
     volume: AtomicF32,
+    speed: AtomicF32,
+    /// Gains of the bands of the equalizer, in dB
+    eq_bands: Mutex<[f32; 10]>,
     seek: Mutex<Option<Duration>>,
     stopped: AtomicBool,
+    fade: Mutex<Option<VolumeFade>>,
+}
+
+/// Handle changing the volume of a `Sink` from another thread
+#[derive(Clone)]
+pub struct VolumeControl(Arc<Controls>);
+
+impl VolumeControl {
+    /// Changes the volume of the sink, cancelling the fade in progress, if any.
+    pub fn set(&self, value: f32) {
+        *self.0.fade.lock().unwrap() = None;
+        self.0.volume.store(value, Ordering::Relaxed);
+    }
+}
+
+/// A linear volume transition applied by the audio thread
+struct VolumeFade {
+    from: f32,
+    to: f32,
+    started: Instant,
+    duration: Duration,
+}
+
+impl Controls {
+    /// Updates the volume according to the fade in progress, if any
+    fn apply_fade(&self) {
+        let mut fade = self.fade.lock().unwrap();
+        if let Some(VolumeFade {
+            from,
+            to,
+            started,
+            duration,
+        }) = fade.as_ref()
+        {
+            let progress = if duration.is_zero() {
+                1.0
+            } else {
+                (started.elapsed().as_secs_f32() / duration.as_secs_f32()).min(1.0)
+            };
+            self.volume
+                .store(from + (to - from) * progress, Ordering::Relaxed);
+            if progress >= 1.0 {
+                *fade = None;
+            }
+        }
+    }
 }
 
 #[allow(unused, clippy::missing_const_for_fn)]
@@ -49,10 +98,13 @@ impl Sink {
             controls: Arc::new(Controls {
                 pause: AtomicBool::new(false),
                 volume: AtomicF32::new(1.0),
+                speed: AtomicF32::new(1.0),
+                eq_bands: Mutex::new([0.0; 10]),
                 stopped: AtomicBool::new(false),
                 seek: Mutex::new(None),
+                fade: Mutex::new(None),
             }),
-            sound_playing: Arc::new(AtomicBool::new(false)),
+            sound_count: Arc::new(AtomicUsize::new(0)),
             detached: false,
             elapsed: Arc::new(AtomicU32::new(0)),
         };
@@ -71,6 +123,8 @@ impl Sink {
 
         let elapsed = self.elapsed.clone();
         let source = source
+            .speed(1.0)
+            .equalizer(*self.controls.eq_bands.lock().unwrap())
             .pausable(false)
             .amplify(1.0)
             .stoppable()
@@ -87,15 +141,25 @@ impl Sink {
                         }
                     }
                     elapsed.store(src.elapsed().as_secs() as u32, Ordering::Relaxed);
+                    controls.apply_fade();
                     src.inner_mut().set_factor(controls.volume.load(Ordering::Relaxed));
                     src.inner_mut()
                         .inner_mut()
                         .set_paused(controls.pause.load(Ordering::Relaxed));
+                    src.inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .set_gains(*controls.eq_bands.lock().unwrap());
+                    src.inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .inner_mut()
+                        .set_factor(controls.speed.load(Ordering::Relaxed));
                 }
             })
             .convert_samples::<f32>();
-        self.sound_playing.store(true, Ordering::Relaxed);
-        self.queue_tx.append(Done::new(source, self.sound_playing.clone()));
+        self.sound_count.fetch_add(1, Ordering::Relaxed);
+        self.queue_tx.append(Done::new(source, self.sound_count.clone()));
     }
 
     /// Gets the volume of the sound.
@@ -111,11 +175,61 @@ impl Sink {
     ///
     /// The value `1.0` is the "normal" volume (unfiltered input). Any value other than `1.0` will
     /// multiply each sample by this value.
+    ///
+    /// Cancels the fade in progress, if any.
     #[inline]
     pub fn set_volume(&self, value: f32) {
+        *self.controls.fade.lock().unwrap() = None;
         self.controls.volume.store(value, Ordering::Relaxed)
     }
 
+    /// Returns a handle changing the volume of this sink from another thread.
+    #[inline]
+    pub fn volume_control(&self) -> VolumeControl {
+        VolumeControl(self.controls.clone())
+    }
+
+    /// Gets the playback rate of the sound, `1.0` being the normal speed.
+    #[inline]
+    pub fn speed(&self) -> f32 {
+        self.controls.speed.load(Ordering::Relaxed)
+    }
+
+    /// Changes the playback rate of the sound.
+    ///
+    /// The pitch changes along with the speed.
+    #[inline]
+    pub fn set_speed(&self, value: f32) {
+        self.controls.speed.store(value, Ordering::Relaxed)
+    }
+
+    /// Gets the gains of the bands of the equalizer, in dB.
+    #[inline]
+    pub fn eq_bands(&self) -> [f32; 10] {
+        *self.controls.eq_bands.lock().unwrap()
+    }
+
+    /// Changes the gains of the bands of the equalizer, in dB.
+    ///
+    /// The sound playing is affected within 50 ms.
+    #[inline]
+    pub fn set_eq_bands(&self, gains: &[f32; 10]) {
+        *self.controls.eq_bands.lock().unwrap() = *gains;
+    }
+
+    /// Linearly changes the volume from its current value to `target` over `duration`.
+    ///
+    /// The volume is updated by the audio thread after each 50 ms of output, so the fade only
+    /// progresses while the sink is playing a sound.
+    pub fn fade_to(&self, target: f32, duration: Duration) {
+        *self.controls.fade.lock().unwrap() = Some(VolumeFade {
+            from: self.volume(),
+            to: target,
+            started: Instant::now(),
+            duration,
+        });
+    }
+
     /// Resumes playback of a paused sink.
     ///
     /// No effect if not paused.
@@ -163,7 +277,19 @@ impl Sink {
     /// Returns true if this sink has no more sounds to play.
     #[inline]
     pub fn is_empty(&self) -> bool {
-        !self.sound_playing.load(Ordering::Relaxed)
+        self.len() == 0
+    }
+
+    /// Returns the number of sounds that are playing or waiting to be played.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sound_count.load(Ordering::Relaxed)
+    }
+
+    /// Removes the sound waiting to be played after the current one, if any.
+    #[inline]
+    pub fn clear_queued(&self) {
+        self.queue_tx.clear();
     }
 
     #[inline]
@@ -172,6 +298,7 @@ impl Sink {
     }
     pub fn destroy(&self) {
         self.queue_tx.set_keep_alive_if_empty(false);
+        self.queue_tx.clear();
 
         if !self.detached {
             self.controls.stopped.store(true, Ordering::Relaxed);
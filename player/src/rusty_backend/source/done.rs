@@ -1,21 +1,23 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use super::{Sample, Source};
 
 /// When the inner source is empty this decrements an `AtomicUsize`.
-#[derive(Debug, Clone)]
+///
+/// The counter is also decremented if the source is dropped before the end.
+#[derive(Debug)]
 pub struct Done<I> {
     input: I,
-    signal: Arc<AtomicBool>,
+    signal: Arc<AtomicUsize>,
     signal_sent: bool,
 }
 
 #[allow(clippy::use_self, clippy::missing_const_for_fn, unused)]
 impl<I> Done<I> {
     #[inline]
-    pub fn new(input: I, signal: Arc<AtomicBool>) -> Done<I> {
+    pub fn new(input: I, signal: Arc<AtomicUsize>) -> Done<I> {
         Done {
             input,
             signal,
@@ -34,12 +36,6 @@ impl<I> Done<I> {
     pub fn inner_mut(&mut self) -> &mut I {
         &mut self.input
     }
-
-    /// Returns the inner source.
-    #[inline]
-    pub fn into_inner(self) -> I {
-        self.input
-    }
 }
 
 impl<I> Iterator for Done<I>
@@ -53,7 +49,7 @@ where
     fn next(&mut self) -> Option<I::Item> {
         let next = self.input.next();
         if !self.signal_sent && next.is_none() {
-            self.signal.store(false, Ordering::Relaxed);
+            self.signal.fetch_sub(1, Ordering::Relaxed);
             self.signal_sent = true;
         }
         next
@@ -64,6 +60,14 @@ where
     }
 }
 
+impl<I> Drop for Done<I> {
+    fn drop(&mut self) {
+        if !self.signal_sent {
+            self.signal.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
 impl<I> Source for Done<I>
 where
     I: Source,
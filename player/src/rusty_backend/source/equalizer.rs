@@ -0,0 +1,234 @@
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use cpal::Sample as CpalSample;
+
+use super::{Sample, Source};
+
+/// Center frequencies of the bands of the equalizer, in Hz.
+pub const EQ_FREQUENCIES: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Maximum boost or cut of a band, in dB.
+pub const EQ_MAX_GAIN: f32 = 12.0;
+
+/// Quality factor giving each band a width of about one octave.
+const BAND_Q: f32 = 1.41;
+
+/// Internal function that builds an `Equalizer` object.
+pub fn equalizer<I>(input: I, gains: [f32; 10]) -> Equalizer<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    let mut equalizer = Equalizer {
+        input,
+        gains: [0.0; 10],
+        filters: Vec::new(),
+        state: Vec::new(),
+        channel: 0,
+        channels: 0,
+        sample_rate: 0,
+    };
+    equalizer.set_gains(gains);
+    equalizer
+}
+
+/// Coefficients of a peaking filter, normalized by `a0`.
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    /// Peaking filter from the Audio EQ Cookbook, `None` when it has no effect.
+    fn peaking(frequency: f32, gain_db: f32, sample_rate: u32) -> Option<Self> {
+        // A band too close to the Nyquist frequency can't be represented
+        if gain_db.abs() < 0.01 || frequency >= sample_rate as f32 * 0.45 {
+            return None;
+        }
+        let a = 10f32.powf(gain_db / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate as f32;
+        let alpha = w0.sin() / (2.0 * BAND_Q);
+        let a0 = 1.0 + alpha / a;
+        Some(Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * w0.cos() / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * w0.cos() / a0,
+            a2: (1.0 - alpha / a) / a0,
+        })
+    }
+
+    /// Filters a sample, `state` holds the two delayed values of the transposed direct form II.
+    #[inline]
+    fn process(&self, x: f32, state: &mut [f32; 2]) -> f32 {
+        let y = self.b0 * x + state[0];
+        state[0] = self.b1 * x - self.a1 * y + state[1];
+        state[1] = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// Filter that boosts or cuts the frequencies of `EQ_FREQUENCIES` with a chain of biquads.
+///
+/// The sound is left untouched when all the gains are zero.
+#[derive(Clone, Debug)]
+pub struct Equalizer<I> {
+    input: I,
+    gains: [f32; 10],
+    filters: Vec<Biquad>,
+    /// State of each filter for each channel
+    state: Vec<[f32; 2]>,
+    /// Channel of the next sample
+    channel: u16,
+    /// Channels and sample rate the filters were computed for
+    channels: u16,
+    sample_rate: u32,
+}
+
+#[allow(clippy::missing_const_for_fn, unused)]
+impl<I> Equalizer<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    /// Changes the gains of the bands, in dB, clamped at `EQ_MAX_GAIN`.
+    #[inline]
+    pub fn set_gains(&mut self, gains: [f32; 10]) {
+        let gains = gains.map(|x| {
+            if x.is_finite() {
+                x.clamp(-EQ_MAX_GAIN, EQ_MAX_GAIN)
+            } else {
+                0.0
+            }
+        });
+        if gains != self.gains || self.channels == 0 {
+            self.gains = gains;
+            self.update_filters();
+        }
+    }
+
+    /// Returns the gains of the bands, in dB.
+    #[inline]
+    pub fn gains(&self) -> [f32; 10] {
+        self.gains
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+
+    /// Computes the filters for the current format of the input, the filter states are reset.
+    fn update_filters(&mut self) {
+        self.channels = self.input.channels().max(1);
+        self.sample_rate = self.input.sample_rate();
+        self.filters = EQ_FREQUENCIES
+            .iter()
+            .zip(self.gains)
+            .filter_map(|(frequency, gain)| Biquad::peaking(*frequency, gain, self.sample_rate))
+            .collect();
+        self.state = vec![[0.0; 2]; self.filters.len() * usize::from(self.channels)];
+        self.channel = 0;
+    }
+}
+
+impl<I> Iterator for Equalizer<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        // The format can only change between two frames
+        if self.channel == 0
+            && (self.input.sample_rate() != self.sample_rate
+                || self.input.channels() != self.channels)
+        {
+            self.update_filters();
+        }
+        let sample = self.input.next()?;
+        let channel = usize::from(self.channel);
+        self.channel = (self.channel + 1) % self.channels;
+        if self.filters.is_empty() {
+            return Some(sample);
+        }
+        let states = &mut self.state[channel * self.filters.len()..];
+        let value = self
+            .filters
+            .iter()
+            .zip(states)
+            .fold(sample.to_f32(), |x, (filter, state)| {
+                filter.process(x, state)
+            });
+        Some(CpalSample::from(&value.clamp(-1.0, 1.0)))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> ExactSizeIterator for Equalizer<I>
+where
+    I: Source + ExactSizeIterator,
+    I::Item: Sample,
+{
+}
+
+impl<I> Source for Equalizer<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn elapsed(&mut self) -> Duration {
+        self.input.elapsed()
+    }
+
+    fn seek(&mut self, time: Duration) -> Result<Duration, ()> {
+        self.input.seek(time)
+    }
+}
@@ -7,10 +7,12 @@ use super::Sample;
 pub use self::amplify::Amplify;
 pub use self::done::Done;
 pub use self::empty::Empty;
+pub use self::equalizer::{Equalizer, EQ_FREQUENCIES, EQ_MAX_GAIN};
 pub use self::fadein::FadeIn;
 pub use self::pausable::Pausable;
 pub use self::periodic::PeriodicAccess;
 pub use self::samples_converter::SamplesConverter;
+pub use self::speed::Speed;
 pub use self::stoppable::Stoppable;
 pub use self::take::TakeDuration;
 pub use self::uniform::UniformSourceIterator;
@@ -19,10 +21,12 @@ pub use self::zero::Zero;
 mod amplify;
 mod done;
 mod empty;
+mod equalizer;
 mod fadein;
 mod pausable;
 mod periodic;
 mod samples_converter;
+mod speed;
 mod stoppable;
 mod take;
 mod uniform;
@@ -200,4 +204,22 @@ where
     {
         stoppable::stoppable(self)
     }
+
+    /// Boosts or cuts the bands of `EQ_FREQUENCIES` by the given gains, in dB.
+    #[inline]
+    fn equalizer(self, gains: [f32; 10]) -> Equalizer<Self>
+    where
+        Self: Sized,
+    {
+        equalizer::equalizer(self, gains)
+    }
+
+    /// Changes the playback rate of the sound by the given factor.
+    #[inline]
+    fn speed(self, factor: f32) -> Speed<Self>
+    where
+        Self: Sized,
+    {
+        speed::speed(self, factor)
+    }
 }
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use super::{Sample, Source};
+
+/// Internal function that builds a `Speed` object.
+pub fn speed<I>(input: I, factor: f32) -> Speed<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    Speed { input, factor }
+}
+
+/// Filter that modifies the playback rate by changing the reported sample rate.
+///
+/// The durations returned by `total_duration` and `elapsed`, and the time given to `seek`,
+/// are positions in the inner source and don't depend on the speed.
+#[derive(Clone, Debug)]
+pub struct Speed<I> {
+    input: I,
+    factor: f32,
+}
+
+#[allow(clippy::missing_const_for_fn, unused)]
+impl<I> Speed<I> {
+    /// Modifies the speed factor.
+    #[inline]
+    pub fn set_factor(&mut self, factor: f32) {
+        self.factor = factor;
+    }
+
+    /// Returns a reference to the inner source.
+    #[inline]
+    pub fn inner(&self) -> &I {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the inner source.
+    #[inline]
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    /// Returns the inner source.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.input
+    }
+}
+
+impl<I> Iterator for Speed<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        self.input.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<I> ExactSizeIterator for Speed<I>
+where
+    I: Source + ExactSizeIterator,
+    I::Item: Sample,
+{
+}
+
+impl<I> Source for Speed<I>
+where
+    I: Source,
+    I::Item: Sample,
+{
+    #[inline]
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    #[inline]
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    #[inline]
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn sample_rate(&self) -> u32 {
+        ((self.input.sample_rate() as f32 * self.factor) as u32).max(1)
+    }
+
+    #[inline]
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    #[inline]
+    fn elapsed(&mut self) -> Duration {
+        self.input.elapsed()
+    }
+
+    fn seek(&mut self, time: Duration) -> Result<Duration, ()> {
+        self.input.seek(time)
+    }
+}
@@ -104,8 +104,28 @@ pub enum PlayError {
     Io(std::io::Error),
     /// The output device was lost.
     NoDevice,
+    /// No output device matches the given name.
+    DeviceNotFound(String),
 }
 
+impl PlayError {
+    /// A plain explanation of the error for the user, `Display` gives the details
+    pub fn user_message(&self) -> &str {
+        match self {
+            Self::DecoderError(_) => "The audio file is corrupted or in an unsupported format",
+            Self::StreamError(e) => e.user_message(),
+            Self::Io(_) => "The audio file can't be read, it may have been removed",
+            Self::NoDevice => NO_DEVICE_MESSAGE,
+            Self::DeviceNotFound(_) => {
+                "The configured audio output device wasn't found, see `--list-audio-devices`"
+            }
+        }
+    }
+}
+
+const NO_DEVICE_MESSAGE: &str =
+    "No audio output device found \u{2014} please connect speakers or headphones";
+
 impl From<decoder::DecoderError> for PlayError {
     fn from(err: decoder::DecoderError) -> Self {
         Self::DecoderError(err)
@@ -117,6 +137,7 @@ impl fmt::Display for PlayError {
         match self {
             Self::DecoderError(e) => e.fmt(f),
             Self::NoDevice => write!(f, "NoDevice"),
+            Self::DeviceNotFound(name) => write!(f, "No audio output device matches `{name}`"),
             Self::StreamError(e) => e.fmt(f),
             Self::Io(e) => e.fmt(f),
         }
@@ -128,6 +149,7 @@ impl error::Error for PlayError {
         match self {
             Self::DecoderError(e) => Some(e),
             Self::NoDevice => None,
+            Self::DeviceNotFound(_) => None,
             Self::StreamError(e) => Some(e),
             Self::Io(e) => Some(e),
         }
@@ -145,6 +167,22 @@ pub enum StreamError {
     NoDevice,
 }
 
+impl StreamError {
+    /// A plain explanation of the error for the user, `Display` gives the details
+    pub fn user_message(&self) -> &str {
+        match self {
+            Self::StreamError(_) => "The audio output device was disconnected",
+            Self::NoDevice => NO_DEVICE_MESSAGE,
+            Self::PlayStreamError(_)
+            | Self::DefaultStreamConfigError(_)
+            | Self::BuildStreamError(_)
+            | Self::SupportedStreamConfigsError(_) => {
+                "The audio output device can't be opened, it may be used by another application"
+            }
+        }
+    }
+}
+
 impl From<cpal::DefaultStreamConfigError> for StreamError {
     fn from(err: cpal::DefaultStreamConfigError) -> Self {
         Self::DefaultStreamConfigError(err)
@@ -0,0 +1,232 @@
+use std::path::PathBuf;
+
+use clap::{builder::PossibleValue, ArgGroup, CommandFactory, Parser, ValueEnum};
+use clap_complete::Shell;
+use serde_json::{json, Value};
+
+use crate::{connect_api, database};
+
+/// How the commands print their results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+impl OutputFormat {
+    /// Prints a line of result, the JSON value is only built when needed
+    pub fn print(self, text: impl FnOnce() -> String, json: impl FnOnce() -> Value) {
+        match self {
+            Self::Text => println!("{}", text()),
+            Self::Json => println!("{}", json()),
+        }
+    }
+
+    /// Prints an error, as `{"error": ...}` in JSON
+    pub fn error(self, message: &str) {
+        self.print(
+            || format!("[ERROR] {message}"),
+            || json!({ "error": message }),
+        );
+    }
+}
+
+/// A terminal YouTube Music player. Without a command the player is started.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+#[command(group(ArgGroup::new("command").multiple(false)))]
+pub struct Cli {
+    /// Show the location of the ytermusic files
+    #[arg(long, group = "command")]
+    pub files: bool,
+    /// List the available audio output devices
+    #[arg(long, group = "command")]
+    pub list_audio_devices: bool,
+    /// Rename the log file to `log.txt.1` and start a new one
+    #[arg(long, group = "command")]
+    pub rotate_logs: bool,
+    /// Fix the database
+    #[arg(long, group = "command")]
+    pub fix_db: bool,
+    /// Rewrite the database with the latest format
+    #[arg(long, group = "command")]
+    pub upgrade_db: bool,
+    /// Export a playlist to M3U
+    #[arg(long, group = "command", num_args = 2, value_names = ["NAME_OR_ID", "OUTPUT"])]
+    pub export_playlist: Option<Vec<String>>,
+    /// Import an M3U or XSPF playlist as a local playlist
+    #[arg(long, group = "command", value_name = "FILE")]
+    pub import_playlist: Option<PathBuf>,
+    /// Remove the cached songs, thumbnails and playlists
+    #[arg(long, group = "command")]
+    pub clear_cache: bool,
+    /// Sign in with a device code instead of cookies
+    #[arg(long, group = "command")]
+    pub auth_oauth: bool,
+    /// Send a JSON command to a ytermusic started with `--daemon-socket`, the socket is the
+    /// default one when omitted
+    #[arg(long, group = "command", num_args = 1..=2, value_names = ["JSON_COMMAND", "SOCKET"])]
+    pub send: Option<Vec<String>>,
+    /// List the local playlists and the playlists of the home page and the library
+    #[arg(long, group = "command")]
+    pub list_playlists: bool,
+    /// Search YouTube Music
+    #[arg(long, group = "command", value_name = "QUERY")]
+    pub search: Option<String>,
+    /// Check the connection, the authentication and the audio output
+    #[arg(long, group = "command")]
+    pub diagnose: bool,
+    /// Print the results of `--list-playlists`, `--search` and `--export-playlist` as text or
+    /// JSON
+    #[arg(long, value_enum, default_value_t, value_name = "FORMAT")]
+    pub output: OutputFormat,
+    /// Print the completion script of a shell
+    #[arg(long, group = "command", value_name = "SHELL")]
+    pub generate_completions: Option<Shell>,
+    /// Play a video first, from its id or URL
+    #[arg(long, conflicts_with = "command", value_name = "VIDEO_ID_OR_URL")]
+    pub play: Option<String>,
+    /// Receive JSON commands on a Unix socket, in the cache directory by default
+    #[arg(long, conflicts_with = "command", num_args = 0..=1, value_name = "SOCKET")]
+    pub daemon_socket: Option<Option<PathBuf>>,
+    /// Read the cookies from a browser instead of `headers.txt`, from all of them by default
+    #[arg(
+        long,
+        conflicts_with = "command",
+        num_args = 0..=1,
+        value_name = "BROWSER",
+        value_parser = browsers(),
+    )]
+    pub with_auto_cookies: Option<Option<String>>,
+}
+
+/// The browsers the cookies can be read from on this platform
+fn browsers() -> Vec<PossibleValue> {
+    let mut browsers = vec![
+        PossibleValue::new("all"),
+        PossibleValue::new("firefox"),
+        PossibleValue::new("chrome"),
+        PossibleValue::new("edge"),
+        PossibleValue::new("opera"),
+        PossibleValue::new("brave"),
+        PossibleValue::new("vivaldi"),
+        PossibleValue::new("chromium"),
+        PossibleValue::new("arc"),
+        PossibleValue::new("librewolf"),
+        PossibleValue::new("opera-gx").alias("opera_gx"),
+    ];
+    if cfg!(target_os = "macos") {
+        browsers.push(PossibleValue::new("safari"));
+    }
+    if cfg!(target_os = "windows") {
+        browsers.push(PossibleValue::new("internet-explorer").aliases(["internet_explorer", "ie"]));
+        browsers.push(PossibleValue::new("octo-browser").alias("octo_browser"));
+    }
+    browsers
+}
+
+pub fn list_playlists(output: OutputFormat) {
+    if let Some(videos) = database::read() {
+        *database::DATABASE.write().unwrap() = videos;
+    }
+    for playlist in database::list_local_playlists() {
+        let tracks = playlist.video_ids.len();
+        output.print(
+            || {
+                format!(
+                    " - {} (local playlist {}, {tracks} tracks)",
+                    playlist.name, playlist.id.0
+                )
+            },
+            || json!({ "name": playlist.name, "local_id": playlist.id.0, "tracks": tracks }),
+        );
+    }
+    for fetched in database::read_fetched_playlists() {
+        let playlist = fetched.playlist;
+        output.print(
+            || {
+                format!(
+                    " - {} ({}) [{}]",
+                    playlist.name, playlist.subtitle, playlist.browse_id
+                )
+            },
+            || json!(playlist),
+        );
+    }
+}
+
+/// Prints the songs then the albums, artists and playlists found
+pub async fn search(query: &str, output: OutputFormat) -> Result<(), String> {
+    let api = connect_api()
+        .await
+        .map_err(|e| format!("Can't connect: {e:?}"))?;
+    let results = api
+        .search(query, 0)
+        .await
+        .map_err(|e| format!("Can't search: {e:?}"))?;
+    for video in results.videos {
+        output.print(
+            || {
+                format!(
+                    " - {} | {} ({}) [{}]",
+                    video.author, video.title, video.duration, video.video_id
+                )
+            },
+            || json!(video),
+        );
+    }
+    for album in results.albums {
+        output.print(
+            || {
+                let year = album
+                    .year
+                    .map(|year| format!(" ({year})"))
+                    .unwrap_or_default();
+                format!(
+                    " - [A] {} | {}{year} [{}]",
+                    album.artist, album.title, album.browse_id
+                )
+            },
+            || json!(album),
+        );
+    }
+    for artist in results.artists {
+        output.print(
+            || format!(" - [@] {} [{}]", artist.name, artist.channel_id),
+            || json!(artist),
+        );
+    }
+    for playlist in results.playlists {
+        output.print(
+            || {
+                format!(
+                    " - {} ({}) [{}]",
+                    playlist.name, playlist.subtitle, playlist.browse_id
+                )
+            },
+            || json!(playlist),
+        );
+    }
+    Ok(())
+}
+
+pub fn print_completions(shell: Shell) {
+    clap_complete::generate(
+        shell,
+        &mut Cli::command(),
+        "ytermusic",
+        &mut std::io::stdout(),
+    );
+}
+
+#[test]
+fn cli_test() {
+    Cli::command().debug_assert();
+    let cli = Cli::parse_from(["ytermusic", "--play", "dQw4w9WgXcQ", "--daemon-socket"]);
+    assert_eq!(cli.play.as_deref(), Some("dQw4w9WgXcQ"));
+    assert_eq!(cli.daemon_socket, Some(None));
+    assert!(Cli::try_parse_from(["ytermusic", "--fix-db", "--play", "dQw4w9WgXcQ"]).is_err());
+    assert!(Cli::try_parse_from(["ytermusic", "--with-auto-cookies", "netscape"]).is_err());
+}
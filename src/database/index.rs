@@ -0,0 +1,196 @@
+use std::{collections::HashMap, sync::RwLock};
+
+use once_cell::sync::Lazy;
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::utils::trigrams;
+
+use super::DATABASE;
+
+/// Positions in `DATABASE` of the tracks of each artist and album, keyed by lowercase name
+#[derive(Default)]
+struct DatabaseIndex {
+    artists: HashMap<String, Vec<usize>>,
+    albums: HashMap<String, Vec<usize>>,
+    /// Positions of the tracks whose `title author album` has each trigram
+    trigrams: HashMap<[char; 3], Vec<usize>>,
+    /// Number of distinct trigrams of each track
+    trigram_counts: Vec<usize>,
+    /// Number of indexed tracks, used to detect an index out of sync with the database
+    len: usize,
+}
+
+impl DatabaseIndex {
+    fn build(videos: &[YoutubeMusicVideoRef]) -> Self {
+        let mut index = Self::default();
+        for (position, video) in videos.iter().enumerate() {
+            index.insert(position, video);
+        }
+        index
+    }
+
+    fn insert(&mut self, position: usize, video: &YoutubeMusicVideoRef) {
+        self.artists
+            .entry(video.author.to_lowercase())
+            .or_default()
+            .push(position);
+        self.albums
+            .entry(video.album.to_lowercase())
+            .or_default()
+            .push(position);
+        let grams = trigrams(&format!("{} {} {}", video.title, video.author, video.album));
+        for gram in &grams {
+            self.trigrams.entry(*gram).or_default().push(position);
+        }
+        if self.trigram_counts.len() <= position {
+            self.trigram_counts.resize(position + 1, 0);
+        }
+        self.trigram_counts[position] = grams.len();
+        self.len = self.len.max(position + 1);
+    }
+
+    /// Removes the track at `position`, the following ones move back by one
+    fn remove(&mut self, position: usize) {
+        for positions in self
+            .artists
+            .values_mut()
+            .chain(self.albums.values_mut())
+            .chain(self.trigrams.values_mut())
+        {
+            positions.retain(|x| *x != position);
+            for x in positions.iter_mut().filter(|x| **x > position) {
+                *x -= 1;
+            }
+        }
+        self.artists.retain(|_, x| !x.is_empty());
+        self.albums.retain(|_, x| !x.is_empty());
+        self.trigrams.retain(|_, x| !x.is_empty());
+        if position < self.trigram_counts.len() {
+            self.trigram_counts.remove(position);
+        }
+        self.len = self.len.saturating_sub(1);
+    }
+
+    /// The tracks sharing trigrams with the query, scored like `trigram_similarity`
+    fn search(&self, query: &str, limit: usize) -> Vec<(usize, f32)> {
+        let query = trigrams(query);
+        let mut shared = HashMap::<usize, usize>::new();
+        for gram in &query {
+            for position in self.trigrams.get(gram).into_iter().flatten() {
+                *shared.entry(*position).or_default() += 1;
+            }
+        }
+        let mut results = shared
+            .into_iter()
+            .map(|(position, shared)| {
+                let union = query.len() + self.trigram_counts[position] - shared;
+                (position, shared as f32 / union as f32)
+            })
+            .collect::<Vec<_>>();
+        results.sort_by(|(a_position, a), (b_position, b)| {
+            b.total_cmp(a).then(a_position.cmp(b_position))
+        });
+        results.truncate(limit);
+        results
+    }
+}
+
+static INDEX: Lazy<RwLock<DatabaseIndex>> = Lazy::new(|| RwLock::new(DatabaseIndex::default()));
+
+/// Rebuilds the artist and album indices, must be called when `DATABASE` is replaced
+pub fn rebuild_index() {
+    let db = DATABASE.read().unwrap();
+    *INDEX.write().unwrap() = DatabaseIndex::build(&db);
+}
+
+/// Indexes a video appended to `DATABASE` at `position`
+pub(super) fn index_video(position: usize, video: &YoutubeMusicVideoRef) {
+    INDEX.write().unwrap().insert(position, video);
+}
+
+/// Removes the video at `position` from the index, after it was removed from `DATABASE`
+pub(super) fn unindex_video(position: usize) {
+    INDEX.write().unwrap().remove(position);
+}
+
+/// Rebuilds the index if videos were added to or removed from `DATABASE` without it
+fn sync_index() {
+    let db = DATABASE.read().unwrap();
+    if INDEX.read().unwrap().len != db.len() {
+        *INDEX.write().unwrap() = DatabaseIndex::build(&db);
+    }
+}
+
+/// Positions in `DATABASE` of the tracks matching the query from best to worst, with their
+/// trigram similarity between 0 and 1
+pub fn full_text_search(query: &str, limit: usize) -> Vec<(usize, f32)> {
+    sync_index();
+    INDEX.read().unwrap().search(query, limit)
+}
+
+fn query(
+    name: &str,
+    get: impl Fn(&DatabaseIndex) -> &HashMap<String, Vec<usize>>,
+    sort_key: impl Fn(&YoutubeMusicVideoRef, usize) -> (String, usize),
+) -> Vec<YoutubeMusicVideoRef> {
+    sync_index();
+    let db = DATABASE.read().unwrap();
+    let index = INDEX.read().unwrap();
+    let mut videos = get(&index)
+        .get(&name.to_lowercase())
+        .into_iter()
+        .flatten()
+        .filter_map(|position| Some((*position, db.get(*position)?)))
+        .collect::<Vec<_>>();
+    videos.sort_by_cached_key(|(position, video)| sort_key(video, *position));
+    videos.into_iter().map(|(_, video)| video.clone()).collect()
+}
+
+/// The downloaded tracks of an artist sorted by album, then in the order they were added
+pub fn query_by_artist(name: &str) -> Vec<YoutubeMusicVideoRef> {
+    query(
+        name,
+        |index| &index.artists,
+        |video, position| (video.album.to_lowercase(), position),
+    )
+}
+
+/// The downloaded tracks of an album in the order they were added
+pub fn query_by_album(name: &str) -> Vec<YoutubeMusicVideoRef> {
+    query(
+        name,
+        |index| &index.albums,
+        |_, position| (String::new(), position),
+    )
+}
+
+#[test]
+fn full_text_search_test() {
+    let video = |title: &str, author: &str| YoutubeMusicVideoRef {
+        title: title.to_owned(),
+        author: author.to_owned(),
+        album: String::new(),
+        video_id: String::new(),
+        duration: String::new(),
+        duration_seconds: None,
+        thumbnail_url: None,
+        like_status: None,
+        explicit: false,
+        year: None,
+    };
+    let mut index = DatabaseIndex::build(&[
+        video("One More Time", "Daft Punk"),
+        video("Bohemian Rhapsody", "Queen"),
+        video("Around the World", "Daft Punk"),
+    ]);
+    let results = index.search("bohemian rapsody", 10);
+    assert_eq!(results[0].0, 1);
+    assert!(results[0].1 > 0.5);
+    assert_eq!(index.search("daft punk", 1).len(), 1);
+    index.remove(0);
+    assert_eq!(index.search("around world", 10)[0].0, 1);
+    assert!(index
+        .search("one more time", 10)
+        .iter()
+        .all(|(_, score)| *score < 0.3));
+}
@@ -0,0 +1,175 @@
+use std::{path::Path, sync::RwLock};
+
+use log::warn;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{
+    consts::{CACHE_DIR, CONFIG},
+    utils::{is_xspf, parse_m3u, parse_xspf, similarity},
+};
+
+use super::DATABASE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LocalPlaylistId(pub u32);
+
+/// A playlist created by the user from downloaded tracks
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalPlaylist {
+    pub id: LocalPlaylistId,
+    pub name: String,
+    pub video_ids: Vec<String>,
+}
+
+impl LocalPlaylist {
+    /// The downloaded videos of the playlist, in the playlist order
+    pub fn videos(&self) -> Vec<YoutubeMusicVideoRef> {
+        let db = DATABASE.read().unwrap();
+        self.video_ids
+            .iter()
+            .filter_map(|id| db.iter().find(|x| &x.video_id == id).cloned())
+            .collect()
+    }
+}
+
+/// The local playlists, loaded from `local_playlists.json`
+static LOCAL_PLAYLISTS: Lazy<RwLock<Vec<LocalPlaylist>>> = Lazy::new(|| {
+    RwLock::new(
+        std::fs::read_to_string(CACHE_DIR.join("local_playlists.json"))
+            .ok()
+            .and_then(|x| serde_json::from_str(&x).ok())
+            .unwrap_or_default(),
+    )
+});
+
+fn save(playlists: &[LocalPlaylist]) {
+    let result = serde_json::to_string(playlists)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            std::fs::write(CACHE_DIR.join("local_playlists.json"), json).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        warn!("Can't save the local playlists: {e}");
+    }
+}
+
+/// Creates an empty playlist
+pub fn create_local_playlist(name: &str) -> LocalPlaylistId {
+    let mut playlists = LOCAL_PLAYLISTS.write().unwrap();
+    let id = LocalPlaylistId(playlists.iter().map(|x| x.id.0 + 1).max().unwrap_or(0));
+    playlists.push(LocalPlaylist {
+        id,
+        name: name.to_owned(),
+        video_ids: Vec::new(),
+    });
+    save(&playlists);
+    id
+}
+
+/// Appends a video to a playlist, videos already in the playlist are ignored
+pub fn add_to_local_playlist(id: LocalPlaylistId, video_id: &str) {
+    let mut playlists = LOCAL_PLAYLISTS.write().unwrap();
+    if let Some(playlist) = playlists.iter_mut().find(|x| x.id == id) {
+        if !playlist.video_ids.iter().any(|x| x == video_id) {
+            playlist.video_ids.push(video_id.to_owned());
+            save(&playlists);
+        }
+    }
+}
+
+pub fn remove_from_local_playlist(id: LocalPlaylistId, video_id: &str) {
+    let mut playlists = LOCAL_PLAYLISTS.write().unwrap();
+    if let Some(playlist) = playlists.iter_mut().find(|x| x.id == id) {
+        playlist.video_ids.retain(|x| x != video_id);
+        save(&playlists);
+    }
+}
+
+pub fn delete_local_playlist(id: LocalPlaylistId) {
+    let mut playlists = LOCAL_PLAYLISTS.write().unwrap();
+    playlists.retain(|x| x.id != id);
+    save(&playlists);
+}
+
+pub fn list_local_playlists() -> Vec<LocalPlaylist> {
+    LOCAL_PLAYLISTS.read().unwrap().clone()
+}
+
+/// The tracks of an imported playlist that were found or not in the downloads
+pub struct ImportSummary {
+    pub matched: usize,
+    pub unmatched: Vec<String>,
+}
+
+/// Imports an M3U or XSPF playlist as a local playlist named after the file, replacing the
+/// tracks of the playlist if it was already imported. Each entry is matched with the most
+/// similar downloaded track.
+pub fn import_playlist(path: &Path) -> std::io::Result<ImportSummary> {
+    let content = std::fs::read_to_string(path)?;
+    let extension = path
+        .extension()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let entries = if is_xspf(&content) {
+        parse_xspf(&content)
+    } else if matches!(extension.as_str(), "m3u" | "m3u8") {
+        parse_m3u(&content)
+    } else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Unknown format, only M3U and XSPF playlists can be imported",
+        ));
+    };
+    let name = path
+        .file_stem()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut matched = Vec::new();
+    let mut unmatched = Vec::new();
+    {
+        let db = DATABASE.read().unwrap();
+        for entry in entries {
+            // Extended M3U entries are usually `artist - title`
+            let entry_key = match entry.split_once(" - ") {
+                Some((author, title)) => format!("{author} | {title}"),
+                None => entry.clone(),
+            };
+            let best = db
+                .iter()
+                .map(|x| {
+                    (
+                        similarity(&entry_key, &format!("{} | {}", x.author, x.title)),
+                        x,
+                    )
+                })
+                .max_by(|(a, _), (b, _)| a.total_cmp(b))
+                .filter(|(score, _)| *score >= CONFIG.import.fuzzy_threshold);
+            match best {
+                Some((_, video)) => matched.push(video.video_id.clone()),
+                None => {
+                    warn!("No downloaded track matches `{entry}`");
+                    unmatched.push(entry);
+                }
+            }
+        }
+    }
+    let existing = list_local_playlists().into_iter().find(|x| x.name == name);
+    let id = match existing {
+        Some(playlist) => {
+            for video_id in &playlist.video_ids {
+                remove_from_local_playlist(playlist.id, video_id);
+            }
+            playlist.id
+        }
+        None => create_local_playlist(&name),
+    };
+    for video_id in &matched {
+        add_to_local_playlist(id, video_id);
+    }
+    Ok(ImportSummary {
+        matched: matched.len(),
+        unmatched,
+    })
+}
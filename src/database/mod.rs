@@ -1,36 +1,200 @@
-use std::{fs::OpenOptions, sync::RwLock};
+use std::{
+    fs::OpenOptions,
+    sync::{Mutex, RwLock},
+};
 
-use log::info;
+use log::{info, warn};
 use once_cell::sync::Lazy;
 
+mod index;
+mod local_playlists;
 mod reader;
+mod stats;
 mod writer;
 
+pub use index::{full_text_search, query_by_album, query_by_artist, rebuild_index};
+pub use local_playlists::{
+    add_to_local_playlist, create_local_playlist, delete_local_playlist, import_playlist,
+    list_local_playlists, remove_from_local_playlist, LocalPlaylist, LocalPlaylistId,
+};
 pub use reader::read;
+use serde::{Deserialize, Serialize};
+pub use stats::{append_play_event, get_most_played, get_recently_played};
 pub use writer::{fix_db, write, write_video};
-use ytpapi2::YoutubeMusicVideoRef;
+use ytpapi2::{YoutubeMusicPlaylistRef, YoutubeMusicVideoRef};
 
-use crate::consts::CACHE_DIR;
+use crate::{consts::CACHE_DIR, utils::trigram_similarity};
+
+/// Magic bytes starting the database file, followed by the version as a little-endian `u16`
+const DB_MAGIC: &[u8; 4] = b"YTDB";
+/// First byte of the files written by versions 1 and 2, followed by the version as a `u8`.
+/// It can't start an unversioned file because a varint encoded `u32` never starts with `0xFF`.
+const LEGACY_MARKER: u8 = 0xFF;
+/// Version of the database format written by this build
+const DB_VERSION: u16 = 4;
 
 /// A global variable to store the current musical Database
 pub static DATABASE: Lazy<RwLock<Vec<YoutubeMusicVideoRef>>> =
     Lazy::new(|| RwLock::new(Vec::new()));
 
+/// A playlist name, or description, with its tracks
+pub type NamedPlaylist = (String, Vec<YoutubeMusicVideoRef>);
+
+/// A playlist of the home page or the library with its tracks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchedPlaylist {
+    #[serde(flatten)]
+    pub playlist: YoutubeMusicPlaylistRef,
+    pub videos: Vec<YoutubeMusicVideoRef>,
+}
+
+/// The playlists of the home page and the library, saved in `playlists.json` as they are
+/// fetched so they can be exported without connecting
+static FETCHED_PLAYLISTS: Lazy<Mutex<Vec<FetchedPlaylist>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+pub fn read_fetched_playlists() -> Vec<FetchedPlaylist> {
+    std::fs::read_to_string(CACHE_DIR.join("playlists.json"))
+        .ok()
+        .and_then(|x| serde_json::from_str(&x).ok())
+        .unwrap_or_default()
+}
+
+/// Adds a playlist fetched from YouTube Music to `playlists.json`, replacing the previous run's
+pub fn save_fetched_playlist(playlist: YoutubeMusicPlaylistRef, videos: Vec<YoutubeMusicVideoRef>) {
+    let mut playlists = FETCHED_PLAYLISTS.lock().unwrap();
+    playlists.retain(|x| x.playlist.browse_id != playlist.browse_id);
+    playlists.push(FetchedPlaylist { playlist, videos });
+    let result = serde_json::to_string(&*playlists)
+        .map_err(|e| e.to_string())
+        .and_then(|x| {
+            std::fs::write(CACHE_DIR.join("playlists.json"), x).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        warn!("Can't save the fetched playlists: {e}");
+    }
+}
+
+/// Finds the stored playlists named so, case-insensitively, or the local playlist with the id.
+/// Looks in the local playlists, the last played playlist and the playlists fetched from
+/// YouTube Music, then in `Local musics` and the albums and artists of the downloaded tracks.
+/// Returns a description of each playlist with its tracks.
+pub fn find_playlists(name_or_id: &str) -> Vec<NamedPlaylist> {
+    let id = name_or_id.parse().ok().map(LocalPlaylistId);
+    let mut found = list_local_playlists()
+        .into_iter()
+        .filter(|x| Some(x.id) == id || x.name.eq_ignore_ascii_case(name_or_id))
+        .map(|x| {
+            (
+                format!("Local playlist `{}` ({})", x.name, x.id.0),
+                x.videos(),
+            )
+        })
+        .collect::<Vec<_>>();
+    if let Some((name, videos)) = std::fs::read_to_string(CACHE_DIR.join("last-playlist.json"))
+        .ok()
+        .and_then(|x| serde_json::from_str::<(String, Vec<YoutubeMusicVideoRef>)>(&x).ok())
+    {
+        if name.eq_ignore_ascii_case(name_or_id) {
+            found.push((format!("Last played playlist `{name}`"), videos));
+        }
+    }
+    found.extend(
+        read_fetched_playlists()
+            .into_iter()
+            .filter(|x| x.playlist.name.eq_ignore_ascii_case(name_or_id))
+            .map(|x| {
+                (
+                    format!(
+                        "YouTube Music playlist `{}` ({})",
+                        x.playlist.name, x.playlist.browse_id
+                    ),
+                    x.videos,
+                )
+            }),
+    );
+    if !found.is_empty() {
+        return found;
+    }
+    if name_or_id.eq_ignore_ascii_case("Local musics") {
+        return vec![("Local musics".to_owned(), DATABASE.read().unwrap().clone())];
+    }
+    Some(query_by_album(name_or_id))
+        .filter(|x| !x.is_empty())
+        .or_else(|| Some(query_by_artist(name_or_id)).filter(|x| !x.is_empty()))
+        .map(|videos| vec![(name_or_id.to_owned(), videos)])
+        .unwrap_or_default()
+}
+
+/// Finds the downloaded songs similar to the query by title, author or both.
+/// Returns at most `limit` songs with their score, sorted by descending score.
+pub fn fuzzy_search(query: &str, limit: usize) -> Vec<(YoutubeMusicVideoRef, f32)> {
+    // Only the tracks sharing trigrams with the query can have a score
+    let candidates = full_text_search(query, usize::MAX);
+    let db = DATABASE.read().unwrap();
+    let mut results = candidates
+        .into_iter()
+        .filter_map(|(position, _)| db.get(position))
+        .map(|video| {
+            let score = [
+                trigram_similarity(query, &video.title),
+                trigram_similarity(query, &video.author),
+                trigram_similarity(query, &format!("{} {}", video.author, video.title)),
+            ]
+            .into_iter()
+            .fold(0., f32::max);
+            (video.clone(), score)
+        })
+        .collect::<Vec<_>>();
+    results.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    results.truncate(limit);
+    results
+}
+
+/// Rewrites the database file with the current format.
+/// Returns the number of videos or `None` if the database can't be read.
+pub fn upgrade() -> Option<usize> {
+    let videos = read()?;
+    let count = videos.len();
+    *DATABASE.write().unwrap() = videos;
+    rebuild_index();
+    write();
+    Some(count)
+}
+
 /// Remove a video from the database
 pub fn remove_video(video: &YoutubeMusicVideoRef) {
     let mut database = DATABASE.write().unwrap();
-    database.retain(|v| v.video_id != video.video_id);
+    while let Some(position) = database.iter().position(|v| v.video_id == video.video_id) {
+        database.remove(position);
+        index::unindex_video(position);
+    }
+    drop(database);
     write();
 }
 
-/// Append a video to the database
-pub fn append(video: YoutubeMusicVideoRef) {
+/// Append a video to the database.
+/// Returns `false` without writing anything if the video is already present.
+pub fn append(video: YoutubeMusicVideoRef) -> bool {
+    let mut database = DATABASE.write().unwrap();
+    if database.iter().any(|v| v.video_id == video.video_id) {
+        info!("{} is already in the database", video.title);
+        return false;
+    }
+    info!("Appended {} to database", video.title);
+    // Legacy databases are rewritten entirely to avoid mixing formats
+    index::index_video(database.len(), &video);
+    if !writer::is_current_version() {
+        database.push(video);
+        drop(database);
+        write();
+        return true;
+    }
     let mut file = OpenOptions::new()
         .append(true)
         .create(true)
         .open(CACHE_DIR.join("db.bin"))
         .unwrap();
     write_video(&mut file, &video);
-    info!("Appended {} to database", video.title);
-    DATABASE.write().unwrap().push(video);
+    database.push(video);
+    true
 }
@@ -1,13 +1,30 @@
 use std::io::{Cursor, Read};
 
+use log::warn;
 use varuint::ReadVarint;
 use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::consts::CACHE_DIR;
 
+use super::{DB_MAGIC, DB_VERSION, LEGACY_MARKER};
+
 /// Reads the database
 pub fn read() -> Option<Vec<YoutubeMusicVideoRef>> {
+    super::writer::restore_backup(&CACHE_DIR.join("db.bin"));
     let mut buffer = Cursor::new(std::fs::read(CACHE_DIR.join("db.bin")).ok()?);
+    let version = read_header(&mut buffer)?;
+    // Older formats are migrated by leaving the missing fields empty, newer ones are read
+    // as the current format on a best-effort basis
+    let read_video = match version {
+        0 => read_video_v0,
+        1 => read_video_v1,
+        2 | 3 => read_video_v2,
+        4 => read_video_v4,
+        _ => {
+            warn!("Database version {version} is newer than {DB_VERSION}, trying to read it");
+            read_video_v4
+        }
+    };
     let mut videos = Vec::new();
     while buffer.get_mut().len() > buffer.position() as usize {
         videos.push(read_video(&mut buffer)?);
@@ -15,14 +32,63 @@ pub fn read() -> Option<Vec<YoutubeMusicVideoRef>> {
     Some(videos)
 }
 
-/// Reads a video from the cursor
-fn read_video(buffer: &mut Cursor<Vec<u8>>) -> Option<YoutubeMusicVideoRef> {
+/// Reads the format version and moves the cursor after the header
+fn read_header(buffer: &mut Cursor<Vec<u8>>) -> Option<u16> {
+    let data = buffer.get_ref();
+    let (version, header_len) = if data.starts_with(DB_MAGIC) {
+        (u16::from_le_bytes([*data.get(4)?, *data.get(5)?]), 6)
+    } else if data.first() == Some(&LEGACY_MARKER) {
+        (*data.get(1)? as u16, 2)
+    } else {
+        // Files without any header were written before the format was versioned
+        (0, 0)
+    };
+    buffer.set_position(header_len);
+    Some(version)
+}
+
+/// Reads a video written before the format was versioned
+fn read_video_v0(buffer: &mut Cursor<Vec<u8>>) -> Option<YoutubeMusicVideoRef> {
     Some(YoutubeMusicVideoRef {
         title: read_str(buffer)?,
         author: read_str(buffer)?,
         album: read_str(buffer)?,
         video_id: read_str(buffer)?,
         duration: read_str(buffer)?,
+        duration_seconds: None,
+        thumbnail_url: None,
+        like_status: None,
+        explicit: false,
+        year: None,
+    })
+}
+
+/// Reads a video with its duration in seconds
+fn read_video_v1(buffer: &mut Cursor<Vec<u8>>) -> Option<YoutubeMusicVideoRef> {
+    let video = read_video_v0(buffer)?;
+    Some(YoutubeMusicVideoRef {
+        duration_seconds: read_optional_u32(buffer)?,
+        ..video
+    })
+}
+
+/// Reads a video with its duration in seconds and its thumbnail
+fn read_video_v2(buffer: &mut Cursor<Vec<u8>>) -> Option<YoutubeMusicVideoRef> {
+    let video = read_video_v1(buffer)?;
+    Some(YoutubeMusicVideoRef {
+        thumbnail_url: read_optional_str(buffer)?,
+        ..video
+    })
+}
+
+/// Reads a video with its duration in seconds, its thumbnail, whether it is explicit and its
+/// release year
+fn read_video_v4(buffer: &mut Cursor<Vec<u8>>) -> Option<YoutubeMusicVideoRef> {
+    let video = read_video_v2(buffer)?;
+    Some(YoutubeMusicVideoRef {
+        explicit: read_u32(buffer)? != 0,
+        year: read_optional_u32(buffer)?.and_then(|x| u16::try_from(x).ok()),
+        ..video
     })
 }
 
@@ -33,7 +99,62 @@ fn read_str(cursor: &mut Cursor<Vec<u8>>) -> Option<String> {
     String::from_utf8(buf).ok()
 }
 
+/// Reads an optional string from the cursor, prefixed by `1` if present and `0` otherwise
+fn read_optional_str(cursor: &mut Cursor<Vec<u8>>) -> Option<Option<String>> {
+    match read_u32(cursor)? {
+        0 => Some(None),
+        _ => Some(Some(read_str(cursor)?)),
+    }
+}
+
+/// Reads an optional u32 from the cursor, stored as `value + 1` with `0` for `None`
+fn read_optional_u32(cursor: &mut Cursor<Vec<u8>>) -> Option<Option<u32>> {
+    Some(read_u32(cursor)?.checked_sub(1))
+}
+
 /// Reads a u32 from the cursor
 fn read_u32(cursor: &mut Cursor<Vec<u8>>) -> Option<u32> {
     ReadVarint::<u32>::read_varint(cursor).ok()
 }
+
+#[test]
+fn read_video_migration_test() {
+    let video = YoutubeMusicVideoRef {
+        title: "Title".to_owned(),
+        author: "Author".to_owned(),
+        album: "Album".to_owned(),
+        video_id: "abcdefghijk".to_owned(),
+        duration: "3:25".to_owned(),
+        duration_seconds: Some(205),
+        thumbnail_url: Some("https://example.com/thumbnail.jpg".to_owned()),
+        like_status: None,
+        explicit: true,
+        year: Some(2013),
+    };
+    let mut buffer = Vec::new();
+    super::write_video(&mut buffer, &video);
+    assert_eq!(read_video_v4(&mut Cursor::new(buffer)), Some(video.clone()));
+
+    // A video written before the format was versioned only has the text fields
+    let mut buffer = Vec::new();
+    for field in [
+        &video.title,
+        &video.author,
+        &video.album,
+        &video.video_id,
+        &video.duration,
+    ] {
+        varuint::WriteVarint::write_varint(&mut buffer, field.len() as u32).unwrap();
+        buffer.extend_from_slice(field.as_bytes());
+    }
+    assert_eq!(
+        read_video_v0(&mut Cursor::new(buffer)),
+        Some(YoutubeMusicVideoRef {
+            duration_seconds: None,
+            thumbnail_url: None,
+            explicit: false,
+            year: None,
+            ..video
+        })
+    );
+}
@@ -0,0 +1,72 @@
+use std::{collections::HashMap, sync::RwLock, time::SystemTime};
+
+use log::warn;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{consts::CACHE_DIR, utils::write_atomic};
+
+use super::DATABASE;
+
+/// How much a track has been listened to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackStats {
+    pub play_count: u32,
+    pub last_played: Option<SystemTime>,
+}
+
+/// The listening statistics by video id, loaded from `stats.bin`
+pub static STATS: Lazy<RwLock<HashMap<String, TrackStats>>> =
+    Lazy::new(|| RwLock::new(read_stats().unwrap_or_default()));
+
+fn read_stats() -> Option<HashMap<String, TrackStats>> {
+    bincode::deserialize(&std::fs::read(CACHE_DIR.join("stats.bin")).ok()?).ok()
+}
+
+fn write_stats(stats: &HashMap<String, TrackStats>) {
+    let result = bincode::serialize(stats)
+        .map_err(|e| e.to_string())
+        .and_then(|data| {
+            write_atomic(&CACHE_DIR.join("stats.bin"), &data).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        warn!("Can't save the listening statistics: {e}");
+    }
+}
+
+/// Records that a track started playing
+pub fn append_play_event(video_id: &str) {
+    let mut stats = STATS.write().unwrap();
+    let entry = stats.entry(video_id.to_owned()).or_default();
+    entry.play_count += 1;
+    entry.last_played = Some(SystemTime::now());
+    write_stats(&stats);
+}
+
+/// The downloaded tracks sorted by the given key, tracks never played are ignored
+fn sorted_by<K: Ord>(limit: usize, key: impl Fn(&TrackStats) -> K) -> Vec<YoutubeMusicVideoRef> {
+    let stats = STATS.read().unwrap();
+    let mut videos = DATABASE
+        .read()
+        .unwrap()
+        .iter()
+        .filter_map(|video| Some((key(stats.get(&video.video_id)?), video.clone())))
+        .collect::<Vec<_>>();
+    videos.sort_by(|(a, _), (b, _)| b.cmp(a));
+    videos
+        .into_iter()
+        .take(limit)
+        .map(|(_, video)| video)
+        .collect()
+}
+
+/// The most played downloaded tracks, most played first
+pub fn get_most_played(limit: usize) -> Vec<YoutubeMusicVideoRef> {
+    sorted_by(limit, |x| x.play_count)
+}
+
+/// The last played downloaded tracks, most recent first
+pub fn get_recently_played(limit: usize) -> Vec<YoutubeMusicVideoRef> {
+    sorted_by(limit, |x| x.last_played)
+}
@@ -1,27 +1,64 @@
-use std::{fs::OpenOptions, io::Write};
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    path::Path,
+};
+
+use log::{error, warn};
 
 use varuint::WriteVarint;
 use ytpapi2::YoutubeMusicVideoRef;
 
-use crate::consts::CACHE_DIR;
+use crate::{consts::CACHE_DIR, utils::write_atomic};
 
-use super::DATABASE;
+use super::{DATABASE, DB_MAGIC, DB_VERSION};
 
 /// Writes the database to the disk
 pub fn write() {
     let db = super::DATABASE.read().unwrap();
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(false)
-        .create(true)
-        .truncate(true)
-        .open(CACHE_DIR.join("db.bin"))
-        .unwrap();
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(DB_MAGIC);
+    buffer.extend_from_slice(&DB_VERSION.to_le_bytes());
     for video in db.iter() {
-        write_video(&mut file, video)
+        write_video(&mut buffer, video)
+    }
+    if let Err(e) = write_database(&CACHE_DIR.join("db.bin"), &buffer) {
+        error!("Can't write the database: {e}");
+    }
+}
+
+/// Writes the database with [`write_atomic`]. As the rename may not be atomic on Windows, the
+/// previous database is also kept as a `.bak` backup restored by [`restore_backup`].
+fn write_database(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    #[cfg(windows)]
+    if path.exists() {
+        std::fs::copy(path, path.with_extension("bin.bak"))?;
+    }
+    write_atomic(path, data)
+}
+
+/// Restores the `.bak` backup if the database at `path` is missing or empty
+pub(super) fn restore_backup(path: &Path) {
+    let backup = path.with_extension("bin.bak");
+    let is_empty = std::fs::metadata(path).map_or(true, |x| x.len() == 0);
+    if is_empty && backup.exists() {
+        warn!("The database is empty, restoring the backup");
+        if let Err(e) = std::fs::rename(&backup, path) {
+            error!("Can't restore the database backup: {e}");
+        }
     }
 }
 
+/// Checks if the database on disk exists and uses the current format
+pub(super) fn is_current_version() -> bool {
+    let mut header = [0u8; 6];
+    std::fs::File::open(CACHE_DIR.join("db.bin"))
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok()
+        && header[..4] == *DB_MAGIC
+        && header[4..] == DB_VERSION.to_le_bytes()
+}
+
 pub fn fix_db() {
     let mut db = DATABASE.write().unwrap();
     db.clear();
@@ -88,6 +125,22 @@ pub fn fix_db() {
 
         db.push(video);
     }
+    let count = db.len();
+    dedup(&mut db);
+    if db.len() != count {
+        println!("[INFO] Removed {} duplicated videos", count - db.len());
+    }
+    drop(db);
+    super::rebuild_index();
+    write();
+}
+
+/// Removes the videos with the same id, keeping the last occurrence
+fn dedup(videos: &mut Vec<YoutubeMusicVideoRef>) {
+    let mut seen = HashSet::new();
+    videos.reverse();
+    videos.retain(|v| seen.insert(v.video_id.clone()));
+    videos.reverse();
 }
 
 /// Writes a video to a file
@@ -97,6 +150,10 @@ pub fn write_video(buffer: &mut impl Write, video: &YoutubeMusicVideoRef) {
     write_str(buffer, &video.album);
     write_str(buffer, &video.video_id);
     write_str(buffer, &video.duration);
+    write_optional_u32(buffer, video.duration_seconds);
+    write_optional_str(buffer, video.thumbnail_url.as_deref());
+    write_u32(buffer, u32::from(video.explicit));
+    write_optional_u32(buffer, video.year.map(u32::from));
 }
 
 /// Writes a string from the cursor
@@ -105,7 +162,65 @@ fn write_str(cursor: &mut impl Write, value: &str) {
     cursor.write_all(value.as_bytes()).unwrap();
 }
 
+/// Writes an optional string prefixed by `1` if present and `0` otherwise
+fn write_optional_str(cursor: &mut impl Write, value: Option<&str>) {
+    match value {
+        Some(value) => {
+            write_u32(cursor, 1);
+            write_str(cursor, value);
+        }
+        None => write_u32(cursor, 0),
+    }
+}
+
+/// Writes an optional u32 as `value + 1`, `0` meaning `None`
+fn write_optional_u32(cursor: &mut impl Write, value: Option<u32>) {
+    write_u32(cursor, value.map_or(0, |x| x.saturating_add(1)));
+}
+
 /// Writes a u32 from the cursor
 fn write_u32(cursor: &mut impl Write, value: u32) {
     cursor.write_varint(value).unwrap();
 }
+
+#[test]
+fn dedup_test() {
+    let video = |video_id: &str, title: &str| YoutubeMusicVideoRef {
+        title: title.to_owned(),
+        author: "Author".to_owned(),
+        album: "Album".to_owned(),
+        video_id: video_id.to_owned(),
+        duration: "3:00".to_owned(),
+        duration_seconds: Some(180),
+        thumbnail_url: None,
+        like_status: None,
+        explicit: false,
+        year: None,
+    };
+    let mut videos = vec![video("a", "First"), video("b", "B"), video("a", "Last")];
+    dedup(&mut videos);
+    assert_eq!(videos, vec![video("b", "B"), video("a", "Last")]);
+}
+
+#[test]
+fn write_database_test() {
+    let dir = std::env::temp_dir().join(format!("ytermusic-db-test-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("db.bin");
+    write_database(&path, b"YTDB complete").unwrap();
+
+    // A crash while writing leaves a partial temporary file but never touches the database
+    std::fs::write(path.with_extension("bin.tmp"), b"YT").unwrap();
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 13);
+    write_database(&path, b"YTDB new").unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"YTDB new");
+    assert!(!path.with_extension("bin.tmp").exists());
+
+    // A database emptied by a crash is restored from the backup
+    std::fs::write(path.with_extension("bin.bak"), b"YTDB backup").unwrap();
+    std::fs::write(&path, b"").unwrap();
+    restore_backup(&path);
+    assert_eq!(std::fs::read(&path).unwrap(), b"YTDB backup");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
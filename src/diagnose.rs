@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use player::{cpal, Player};
+use rusty_ytdl::reqwest;
+
+use crate::{connect_api, get_header_file, try_get_oauth_token};
+
+const DOMAIN: &str = "music.youtube.com";
+
+/// Resolves the address of YouTube Music
+async fn check_dns() -> Result<String, String> {
+    let addresses = tokio::net::lookup_host((DOMAIN, 443))
+        .await
+        .map_err(|e| e.to_string())?
+        .map(|x| x.ip().to_string())
+        .collect::<Vec<_>>();
+    if addresses.is_empty() {
+        return Err("No address found".to_owned());
+    }
+    Ok(addresses.join(", "))
+}
+
+async fn check_https() -> Result<String, String> {
+    let response = reqwest::Client::new()
+        .get(format!("https://{DOMAIN}"))
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| format!("{e:?}"))?;
+    Ok(format!("HTTP {}", response.status()))
+}
+
+/// Checks that `headers.txt` has a cookie with a SAPISID, unless OAuth is used
+fn check_headers() -> Result<String, String> {
+    if try_get_oauth_token().is_some() {
+        return Ok("Not used, signed in with OAuth".to_owned());
+    }
+    let (content, path) =
+        get_header_file().map_err(|(e, path)| format!("Can't read {}: {e}", path.display()))?;
+    let cookies = content
+        .lines()
+        .find_map(|line| {
+            line.split_once(": ")
+                .filter(|(key, _)| key.eq_ignore_ascii_case("cookie"))
+                .map(|(_, value)| value)
+        })
+        .ok_or_else(|| format!("No `Cookie` header in {}", path.display()))?;
+    let names = cookies
+        .split(';')
+        .map(|x| x.trim().split_once('=').map(|(name, _)| name))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| "The cookie isn't a list of `name=value`".to_owned())?;
+    if !names.contains(&"SAPISID") {
+        return Err("No SAPISID in the cookie, copy it again while signed in".to_owned());
+    }
+    Ok(format!("{}, {} cookies", path.display(), names.len()))
+}
+
+async fn check_api() -> Result<String, String> {
+    let api = connect_api().await.map_err(|e| format!("{e:?}"))?;
+    let home = api.get_home(0).await.map_err(|e| format!("{e:?}"))?;
+    Ok(format!(
+        "{} playlists on the home page",
+        home.playlists.len()
+    ))
+}
+
+fn check_audio_devices() -> Result<String, String> {
+    let (names, default) = Player::output_device_names().map_err(|e| e.to_string())?;
+    if names.is_empty() {
+        return Err(format!(
+            "No output device found with {:?}",
+            cpal::default_host().id()
+        ));
+    }
+    let default = default.unwrap_or_else(|| "none".to_owned());
+    Ok(format!("{} (default: {default})", names.join(", ")))
+}
+
+async fn check_playback() -> Result<String, String> {
+    tokio::task::spawn_blocking(|| Player::test_output(Duration::from_secs(1)))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{} ({e})", e.user_message()))?;
+    Ok("1s of silence played".to_owned())
+}
+
+fn print_check(name: &str, result: Result<String, String>) -> bool {
+    match &result {
+        Ok(details) => println!("[PASS] {name}: {details}"),
+        Err(e) => println!("[FAIL] {name}: {e}"),
+    }
+    result.is_ok()
+}
+
+/// Runs the checks of `--diagnose` one after the other, returns whether they all passed
+pub async fn diagnose() -> bool {
+    let wsl = std::fs::read_to_string("/proc/version")
+        .is_ok_and(|x| x.to_lowercase().contains("microsoft"));
+    println!("# ytermusic diagnostics");
+    println!(
+        "ytermusic {} on {} {}{}",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        if wsl { " (WSL)" } else { "" }
+    );
+    println!();
+    let checks = [
+        print_check(&format!("DNS resolution of {DOMAIN}"), check_dns().await),
+        print_check(
+            &format!("HTTPS connection to {DOMAIN}"),
+            check_https().await,
+        ),
+        print_check("headers.txt", check_headers()),
+        print_check("Authenticated API call", check_api().await),
+        print_check("Audio output devices", check_audio_devices()),
+        print_check("Playback", check_playback().await),
+    ];
+    if wsl && !checks[4..].iter().all(|x| *x) {
+        println!();
+        println!(
+            "On WSL the audio goes through the PulseAudio server of WSLg, \
+            check that `PULSE_SERVER` is set"
+        );
+    }
+    checks.iter().all(|x| *x)
+}
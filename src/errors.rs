@@ -1,4 +1,6 @@
 use flume::Sender;
+use log::error;
+use player::PlayError;
 
 use crate::term::{ManagerMessage, Screens};
 
@@ -35,3 +37,25 @@ where
 {
     let _ = handle_error_option(updater, error_type, a);
 }
+
+/// Reports a playback error with its explanation for the user, the details are logged
+pub fn handle_play_error_option<E>(
+    updater: &Sender<ManagerMessage>,
+    error_type: &'static str,
+    a: Result<E, PlayError>,
+) -> Option<E> {
+    let a = a.map_err(|e| {
+        error!("{error_type}: {e}");
+        e.user_message().to_owned()
+    });
+    handle_error_option(updater, error_type, a)
+}
+
+/// Reports a playback error with its explanation for the user, the details are logged
+pub fn handle_play_error(
+    updater: &Sender<ManagerMessage>,
+    error_type: &'static str,
+    a: Result<(), PlayError>,
+) {
+    let _ = handle_play_error_option(updater, error_type, a);
+}
@@ -1,10 +1,17 @@
-use consts::CACHE_DIR;
+use clap::Parser;
+use cli::{Cli, OutputFormat};
+use consts::{CACHE_DIR, CONFIG};
 use flume::{Receiver, Sender};
-use log::{error, info};
-use once_cell::sync::Lazy;
+use log::{error, info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use rusty_ytdl::get_video_id;
+use serde_json::json;
 use structures::performance::STARTUP_TIME;
 use term::{Manager, ManagerMessage};
 use tokio::select;
+use ytpapi2::{
+    ClientOptions, HeaderMap, HeaderValue, OAuthClient, OAuthToken, YoutubeMusicInstance,
+};
 
 use std::{
     future::Future,
@@ -13,14 +20,17 @@ use std::{
     process::exit,
     str::FromStr,
     sync::RwLock,
+    time::Duration,
 };
 use systems::{logger::init, player::player_system};
 
 use crate::{consts::HEADER_TUTORIAL, systems::logger::get_log_file_path, utils::get_project_dirs};
 
+mod cli;
 mod config;
 mod consts;
 mod database;
+mod diagnose;
 mod errors;
 mod structures;
 mod systems;
@@ -52,6 +62,9 @@ where
 }
 
 fn shutdown() {
+    tasks::last_playlist::save_last_state();
+    structures::persistence::flush_player_state();
+    systems::ipc::remove_socket();
     for _ in 0..1000 {
         SIGNALING_STOP.0.send(()).unwrap();
     }
@@ -65,61 +78,330 @@ pub fn try_get_cookies() -> Option<String> {
     cookies.clone()
 }
 
+/// The browser given to `--with-auto-cookies`, `None` for all of them
+static AUTO_COOKIES_BROWSER: OnceCell<Option<String>> = OnceCell::new();
+
+/// Reads the cookies from the browser again with `--with-auto-cookies`, returns whether new
+/// cookies were found
+pub fn reload_auto_cookies() -> bool {
+    let Some(browser) = AUTO_COOKIES_BROWSER.get() else {
+        return false;
+    };
+    let Some(cookies) = cookies(browser.clone()) else {
+        return false;
+    };
+    *COOKIES.write().unwrap() = Some(cookies);
+    true
+}
+
+/// Path of the token saved by `--auth-oauth`
+fn oauth_token_path() -> PathBuf {
+    CACHE_DIR.join("oauth_token.json")
+}
+
+pub fn try_get_oauth_token() -> Option<OAuthToken> {
+    let token = std::fs::read_to_string(oauth_token_path()).ok()?;
+    serde_json::from_str(&token)
+        .map_err(|e| error!("Can't read the OAuth token: {e}"))
+        .ok()
+}
+
+/// Connects to YouTube Music with the OAuth token, the browser cookies or the header file
+pub async fn connect_api() -> ytpapi2::Result<YoutubeMusicInstance> {
+    let api = if let Some(token) = try_get_oauth_token() {
+        YoutubeMusicInstance::from_oauth_token(&token, client_options()).await
+    } else if let Some(cookies) = try_get_cookies() {
+        let mut headermap = HeaderMap::new();
+        headermap.insert("cookie", HeaderValue::from_str(&cookies).unwrap());
+        headermap.insert(
+            "user-agent",
+            HeaderValue::from_static(
+                "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0",
+            ),
+        );
+        YoutubeMusicInstance::new(headermap, client_options()).await
+    } else {
+        match get_header_file() {
+            Ok((_, path)) => YoutubeMusicInstance::from_header_file(&path, client_options()).await,
+            Err((e, _)) => Err(ytpapi2::YoutubeMusicError::IoError(e)),
+        }
+    };
+    api.map(configure_api)
+}
+
+/// The options of the HTTP client from the network settings of the config
+pub fn client_options() -> ClientOptions {
+    ClientOptions {
+        proxy: CONFIG.network.proxy_url.clone(),
+        timeout: Some(Duration::from_secs(CONFIG.network.request_timeout_secs)),
+    }
+}
+
+/// Applies the network settings of the config to a new instance
+pub fn configure_api(api: YoutubeMusicInstance) -> YoutubeMusicInstance {
+    api.with_max_parallel_continuations(CONFIG.network.max_parallel_continuations)
+}
+
+/// Authorizes ytermusic with the OAuth device flow and saves the token
+async fn auth_oauth(client: OAuthClient) -> Result<(), String> {
+    let code = client
+        .request_device_code(&client_options())
+        .await
+        .map_err(|e| format!("{e:?}"))?;
+    println!(
+        "Open {} and enter the code {}",
+        code.verification_url, code.user_code
+    );
+    let token = client
+        .wait_for_token(&code, &client_options())
+        .await
+        .map_err(|e| format!("{e:?}"))?;
+    std::fs::create_dir_all(&*CACHE_DIR).map_err(|e| e.to_string())?;
+    std::fs::write(
+        oauth_token_path(),
+        serde_json::to_string(&token).map_err(|e| e.to_string())?,
+    )
+    .map_err(|e| e.to_string())
+}
+
 #[tokio::main]
 async fn main() {
-    // Check if the first param is --files
-    if let Some(arg) = std::env::args().nth(1) {
-        match arg.as_str() {
-            "--files" => {
-                println!("# Location of ytermusic files");
-                println!(" - Logs: {}", get_log_file_path().display());
-                println!(" - Headers: {}", get_header_file().unwrap().1.display());
-                println!(" - Cache: {}", CACHE_DIR.display());
-                return;
+    let cli = Cli::parse();
+    if let Some(shell) = cli.generate_completions {
+        cli::print_completions(shell);
+        return;
+    }
+    if cli.files {
+        println!("# Location of ytermusic files");
+        println!(" - Logs: {}", get_log_file_path().display());
+        println!(" - Headers: {}", get_header_file().unwrap().1.display());
+        println!(" - Cache: {}", CACHE_DIR.display());
+        return;
+    }
+    if cli.list_audio_devices {
+        let (names, default) = match player::Player::output_device_names() {
+            Ok((names, _)) if names.is_empty() => {
+                println!("[ERROR] No audio output device found");
+                print_audio_help();
+                exit(1);
             }
-            "--fix-db" => {
-                database::fix_db();
-                println!("[INFO] Database fixed");
-                return;
+            Ok(devices) => devices,
+            Err(e) => {
+                println!("[ERROR] Can't list the audio output devices: {e}");
+                print_audio_help();
+                exit(1);
             }
-            "--clear-cache" => {
-                match std::fs::remove_dir_all(&*CACHE_DIR) {
-                    Ok(_) => {
-                        println!("[INFO] Cache cleared");
-                    }
-                    Err(e) => {
-                        println!("[ERROR] Can't clear cache: {e}");
-                    }
-                }
-                return;
+        };
+        println!("# Audio output devices, `*` marks the default one");
+        println!("# Copy a line to the `[player]` section of the config to use the device");
+        for name in names {
+            let marker = if Some(&name) == default.as_ref() {
+                '*'
+            } else {
+                ' '
+            };
+            println!("{marker} output_device = {}", toml::Value::String(name));
+        }
+        return;
+    }
+    if cli.diagnose {
+        if !diagnose::diagnose().await {
+            exit(1);
+        }
+        return;
+    }
+    if cli.rotate_logs {
+        systems::logger::rotate_logs(CONFIG.logging.max_rotations);
+        println!("[INFO] Rotated {}", get_log_file_path().display());
+        return;
+    }
+    if cli.fix_db {
+        database::fix_db();
+        println!("[INFO] Database fixed");
+        return;
+    }
+    if cli.upgrade_db {
+        match database::upgrade() {
+            Some(count) => println!("[INFO] Database upgraded ({count} videos)"),
+            None => println!("[ERROR] Can't read the database, try --fix-db"),
+        }
+        return;
+    }
+    if let Some([name, out]) = cli.export_playlist.as_deref() {
+        let output = cli.output;
+        if let Some(videos) = database::read() {
+            *DATABASE.write().unwrap() = videos;
+            database::rebuild_index();
+        }
+        let tracks = match database::find_playlists(name).as_slice() {
+            [] => {
+                output.error(&format!("No playlist named `{name}`"));
+                exit(1);
             }
-            "--with-auto-cookies" => {
-                std::fs::write(get_log_file_path(), "# YTerMusic log file\n\n").unwrap();
-                init().expect("Failed to initialize logger");
-                let param = std::env::args().nth(2);
-                if let Some(cookies) = cookies(param) {
-                    let mut cookies_guard = COOKIES.write().unwrap();
-                    info!("Cookies: {cookies}");
-                    *cookies_guard = Some(cookies);
-                    info!("Cookies loaded");
-                } else {
-                    error!("Can't load cookies");
-                    error!("Maybe rookie didn't find any cookies or any browser");
-                    error!("Please make sure you have cookies in your browser");
-                    return;
+            [(_, tracks)] => tracks.clone(),
+            found => {
+                output.print(
+                    || {
+                        format!(
+                            "[ERROR] Several playlists are named `{name}`, use the id of a local playlist or rename it:\n{}",
+                            found
+                                .iter()
+                                .map(|(description, tracks)| {
+                                    format!(" - {description}, {} tracks", tracks.len())
+                                })
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        )
+                    },
+                    || {
+                        json!({
+                            "error": format!("Several playlists are named `{name}`"),
+                            "matches": found.iter().map(|(x, _)| x).collect::<Vec<_>>(),
+                        })
+                    },
+                );
+                exit(1);
+            }
+        };
+        let downloaded = {
+            let db = DATABASE.read().unwrap();
+            tracks
+                .iter()
+                .filter(|x| db.iter().any(|y| y.video_id == x.video_id))
+                .count()
+        };
+        match utils::export_m3u(&tracks, Path::new(out)) {
+            Ok(()) => output.print(
+                || {
+                    format!(
+                        "[INFO] Exported {downloaded}/{} tracks to {out}, the others are commented out as they aren't downloaded",
+                        tracks.len()
+                    )
+                },
+                || json!({ "path": out, "downloaded": downloaded, "tracks": tracks.len() }),
+            ),
+            Err(e) => {
+                output.error(&format!("Can't export the playlist: {e}"));
+                exit(1);
+            }
+        }
+        return;
+    }
+    if cli.list_playlists {
+        cli::list_playlists(cli.output);
+        return;
+    }
+    if let Some(query) = &cli.search {
+        if let Err(e) = cli::search(query, cli.output).await {
+            cli.output.error(&e);
+            exit(1);
+        }
+        return;
+    }
+    if let Some(file) = &cli.import_playlist {
+        if let Some(videos) = database::read() {
+            *DATABASE.write().unwrap() = videos;
+            database::rebuild_index();
+        }
+        match database::import_playlist(file) {
+            Ok(summary) => {
+                for entry in &summary.unmatched {
+                    println!("[WARN] No downloaded track matches `{entry}`");
                 }
+                println!(
+                    "[INFO] Playlist imported from {}: {} matched, {} unmatched",
+                    file.display(),
+                    summary.matched,
+                    summary.unmatched.len()
+                );
+            }
+            Err(e) => println!("[ERROR] Can't import the playlist: {e}"),
+        }
+        return;
+    }
+    if cli.clear_cache {
+        let reclaimed = tasks::clean::compact();
+        println!("[INFO] Removed {reclaimed} bytes of unreferenced files");
+        match std::fs::remove_dir_all(&*CACHE_DIR) {
+            Ok(_) => {
+                println!("[INFO] Cache cleared");
             }
-            e => {
-                println!("Unknown argument `{e}`");
-                println!("Here are the available arguments:");
-                println!(" - --files: Show the location of the ytermusic files");
-                println!(" - --fix-db: Fix the database");
-                return;
+            Err(e) => {
+                println!("[ERROR] Can't clear cache: {e}");
             }
         }
-    } else {
-        std::fs::write(get_log_file_path(), "# YTerMusic log file\n\n").unwrap();
-        init().expect("Failed to initialize logger");
+        return;
+    }
+    if cli.auth_oauth {
+        let config = &CONFIG.oauth;
+        let (Some(client_id), Some(client_secret)) =
+            (config.client_id.clone(), config.client_secret.clone())
+        else {
+            println!(
+                "[ERROR] Set `client_id` and `client_secret` in the `[oauth]` section of the config"
+            );
+            return;
+        };
+        match auth_oauth(OAuthClient {
+            client_id,
+            client_secret,
+        })
+        .await
+        {
+            Ok(()) => println!(
+                "[INFO] Authorized, the token is saved in {}",
+                oauth_token_path().display()
+            ),
+            Err(e) => println!("[ERROR] Can't authorize: {e}"),
+        }
+        return;
+    }
+    if let Some(send) = &cli.send {
+        let path = send
+            .get(1)
+            .map_or_else(systems::ipc::default_socket_path, PathBuf::from);
+        match systems::ipc::send_command(&path, &send[0]) {
+            Ok(reply) => println!("{reply}"),
+            Err(e) => {
+                println!("[ERROR] {e}");
+                exit(1);
+            }
+        }
+        return;
+    }
+    if cli.output == OutputFormat::Json {
+        println!("[ERROR] `--output json` is only for `--list-playlists`, `--search` and `--export-playlist`");
+        exit(1);
+    }
+    // The video played first with `--play`
+    let preload = match cli.play {
+        Some(play) => {
+            let Some(id) = get_video_id(&play) else {
+                println!("[ERROR] `{play}` isn't a video id or URL");
+                exit(1);
+            };
+            Some(id)
+        }
+        None => None,
+    };
+    // Where commands are received with `--daemon-socket`
+    let socket = cli
+        .daemon_socket
+        .map(|x| x.unwrap_or_else(systems::ipc::default_socket_path));
+    init(&CONFIG.logging).expect("Failed to initialize logger");
+    if let Some(browser) = cli.with_auto_cookies {
+        let _ = AUTO_COOKIES_BROWSER.set(browser.clone());
+        if let Some(cookies) = cookies(browser) {
+            let mut cookies_guard = COOKIES.write().unwrap();
+            info!("Cookies: {cookies}");
+            *cookies_guard = Some(cookies);
+            info!("Cookies loaded");
+        } else {
+            error!("Can't load cookies");
+            error!("Maybe rookie didn't find any cookies or any browser");
+            error!("Please make sure you have cookies in your browser");
+            return;
+        }
     }
     panic::set_hook(Box::new(|e| {
         println!("{e}");
@@ -128,7 +410,7 @@ async fn main() {
     }));
     select! {
         _ = async {
-            app_start().await
+            app_start(preload, socket).await
         } => {},
         _ = SIGNALING_STOP.1.recv_async() => {},
         _ = tokio::signal::ctrl_c() => {
@@ -137,6 +419,15 @@ async fn main() {
     };
 }
 
+/// Explains why the audio devices may be missing
+fn print_audio_help() {
+    println!("Audio support is limited on this system, ytermusic needs a working sound server.");
+    println!(
+        "On WSL or minimal Linux installs, install PulseAudio (or PipeWire with its PulseAudio \
+        support) and check that `PULSE_SERVER` is set, then run `ytermusic --diagnose`."
+    );
+}
+
 fn cookies(specific_browser: Option<String>) -> Option<String> {
     let loaded = match specific_browser {
         Some(browser) => match browser.as_str() {
@@ -218,12 +509,28 @@ fn get_header_file() -> Result<(String, PathBuf), (std::io::Error, PathBuf)> {
     let fp = fp.join("headers.txt");
     std::fs::read_to_string(&fp).map_or_else(|e| Err((e, fp.clone())), |e| Ok((e, fp.clone())))
 }
-async fn app_start() {
+async fn app_start(preload: Option<String>, socket: Option<PathBuf>) {
     STARTUP_TIME.log("Init");
 
+    match config::Config::check() {
+        Ok(warnings) => {
+            for warning in warnings {
+                eprintln!("[WARN] {warning}");
+                warn!("{warning}");
+            }
+        }
+        Err(errors) => {
+            eprintln!("Invalid config file:");
+            for e in errors {
+                eprintln!("  {e}");
+            }
+            exit(1);
+        }
+    }
+
     std::fs::create_dir_all(CACHE_DIR.join("downloads")).unwrap();
 
-    if try_get_cookies().is_none() {
+    if try_get_cookies().is_none() && try_get_oauth_token().is_none() {
         if let Err((error, filepath)) = get_header_file() {
             println!("Can't read or find `{}`", filepath.display());
             println!("Error: {error}");
@@ -236,25 +543,40 @@ async fn app_start() {
 
     STARTUP_TIME.log("Startup");
 
-    // Spawn the clean task
-    let (updater_s, updater_r) = flume::unbounded::<ManagerMessage>();
-    tasks::clean::spawn_clean_task();
+    structures::keybindings::BINDINGS.validate();
 
-    STARTUP_TIME.log("Spawned clean task");
+    let (updater_s, updater_r) = flume::unbounded::<ManagerMessage>();
     // Spawn the player task
-    let (sa, player) = player_system(updater_s.clone());
+    let scrobbler = systems::scrobbler::spawn_scrobbler();
+    let discord = systems::discord_rpc::spawn_discord_rpc();
+    let (sa, player) = player_system(updater_s.clone(), scrobbler, discord);
     // Spawn the downloader system
     systems::download::spawn_system(&sa);
+    // Spawn the clean task once the saved download queue is restored
+    tasks::clean::spawn_clean_task();
+    STARTUP_TIME.log("Spawned clean task");
+    if let Some(path) = socket {
+        systems::ipc::spawn_ipc_server(path, sa.clone());
+    }
+    if let Some(id) = preload {
+        tasks::download::spawn_play_task(id, sa.clone(), updater_s.clone());
+    }
     STARTUP_TIME.log("Spawned system task");
     tasks::last_playlist::spawn_last_playlist_task(updater_s.clone());
     STARTUP_TIME.log("Spawned last playlist task");
     // Spawn the API task
     tasks::api::spawn_api_task(updater_s.clone());
+    // Only the header file can be reloaded, the other sources don't expire while running
+    if try_get_cookies().is_none() && try_get_oauth_token().is_none() {
+        tasks::header_watcher::spawn_header_watcher_task(updater_s.clone());
+    }
     STARTUP_TIME.log("Spawned api task");
     // Spawn the database getter task
-    tasks::local_musics::spawn_local_musics_task(updater_s);
+    tasks::local_musics::spawn_local_musics_task(updater_s.clone());
+    tasks::local_musics::spawn_downloads_watcher_task(updater_s);
 
     STARTUP_TIME.log("Running manager");
     let mut manager = Manager::new(sa, player).await;
     manager.run(&updater_r).unwrap();
+    shutdown();
 }
@@ -14,6 +14,8 @@ pub enum MusicDownloadStatus {
     NotDownloaded,
     Downloaded,
     Downloading(usize),
+    /// A partial file from a previous session is being completed
+    Resuming,
     DownloadFailed,
 }
 
@@ -33,6 +35,7 @@ impl MusicDownloadStatus {
             }
             Self::Downloaded => ' ',
             Self::Downloading(progress) => return format!("⭳ [{:02}%]", progress),
+            Self::Resuming => return "⭳ [↻]".to_owned(),
             Self::DownloadFailed => '⚠',
         }
         .into()
@@ -51,7 +54,7 @@ impl MusicDownloadStatus {
                     CONFIG.player.text_next_style
                 }
             }
-            Self::Downloading(_) => CONFIG.player.text_downloading_style,
+            Self::Downloading(_) | Self::Resuming => CONFIG.player.text_downloading_style,
             Self::DownloadFailed => CONFIG.player.text_error_style,
         };
         if playing.is_some() {
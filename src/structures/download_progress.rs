@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+
+/// Bytes received for a running download, sampled to estimate the remaining time
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadProgress {
+    pub downloaded_bytes: u64,
+    pub total_bytes: Option<u64>,
+    /// Bytes per second between the last two progress events, zero until they are received
+    pub speed_bps: f64,
+    last_update: Instant,
+}
+
+impl DownloadProgress {
+    pub fn new(downloaded_bytes: u64, total_bytes: Option<u64>) -> Self {
+        Self {
+            downloaded_bytes,
+            total_bytes,
+            speed_bps: 0.,
+            last_update: Instant::now(),
+        }
+    }
+
+    pub fn update(&mut self, downloaded_bytes: u64, total_bytes: Option<u64>) {
+        let elapsed = self.last_update.elapsed().as_secs_f64();
+        if elapsed > 0. && downloaded_bytes >= self.downloaded_bytes {
+            self.speed_bps = (downloaded_bytes - self.downloaded_bytes) as f64 / elapsed;
+        }
+        self.downloaded_bytes = downloaded_bytes;
+        self.total_bytes = total_bytes;
+        self.last_update = Instant::now();
+    }
+
+    /// Estimated time until the download ends, unknown until two progress events are received
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.total_bytes?.saturating_sub(self.downloaded_bytes);
+        if self.speed_bps <= 0. {
+            return None;
+        }
+        Some(Duration::from_secs_f64(remaining as f64 / self.speed_bps))
+    }
+}
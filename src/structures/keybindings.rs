@@ -0,0 +1,219 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use log::warn;
+use once_cell::sync::Lazy;
+
+use crate::{config::KeybindingsConfig, consts::CONFIG};
+
+/// The player actions that can be bound in the `[keybindings]` section of the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    PlayPause,
+    Next,
+    Previous,
+    SeekForward,
+    SeekBackward,
+    VolumeUp,
+    VolumeDown,
+    Search,
+    Shuffle,
+    Settings,
+    Help,
+    ContextMenu,
+}
+
+impl Action {
+    /// What the action does, shown in the help
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::PlayPause => "Play or pause",
+            Self::Next => "Next song",
+            Self::Previous => "Previous song",
+            Self::SeekForward => "Seek forward",
+            Self::SeekBackward => "Seek backward",
+            Self::VolumeUp => "Volume up",
+            Self::VolumeDown => "Volume down",
+            Self::Search => "Search",
+            Self::Shuffle => "Shuffle the queue",
+            Self::Settings => "Open the settings",
+            Self::Help => "Show or hide this help",
+            Self::ContextMenu => "Open the menu of the selected song",
+        }
+    }
+}
+
+/// The keys bound to each action
+pub struct Bindings {
+    bindings: Vec<(Action, KeyCode, KeyModifiers)>,
+}
+
+pub static BINDINGS: Lazy<Bindings> = Lazy::new(|| Bindings::new(&CONFIG.keybindings));
+
+impl Bindings {
+    /// Parses the bindings of the config, invalid ones are ignored with a warning
+    pub fn new(config: &KeybindingsConfig) -> Self {
+        let mut bindings = Vec::new();
+        for (action, keys) in [
+            (Action::PlayPause, &config.play_pause),
+            (Action::Next, &config.next),
+            (Action::Previous, &config.prev),
+            (Action::SeekForward, &config.seek_fw),
+            (Action::SeekBackward, &config.seek_bw),
+            (Action::VolumeUp, &config.volume_up),
+            (Action::VolumeDown, &config.volume_down),
+            (Action::Search, &config.search),
+            (Action::Shuffle, &config.shuffle),
+            (Action::Settings, &config.settings),
+            (Action::Help, &config.help),
+            (Action::ContextMenu, &config.context_menu),
+        ] {
+            for key in keys.split_whitespace() {
+                match parse_keybinding(key) {
+                    Ok((code, modifiers)) => bindings.push((action, code, modifiers)),
+                    Err(e) => warn!("Invalid keybinding `{key}` for {action:?}: {e}"),
+                }
+            }
+        }
+        Self { bindings }
+    }
+
+    /// The action bound to a key press
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        let modifiers = normalize(key.code, key.modifiers);
+        self.bindings
+            .iter()
+            .find(|(_, code, m)| *code == key.code && *m == modifiers)
+            .map(|(action, _, _)| *action)
+    }
+
+    /// The keys bound to an action, formatted like in the config
+    pub fn keys(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(a, _, _)| *a == action)
+            .map(|(_, code, modifiers)| format_keybinding(*code, *modifiers))
+            .collect()
+    }
+
+    /// Warns about the keys bound to several actions
+    pub fn validate(&self) {
+        for (i, (action, code, modifiers)) in self.bindings.iter().enumerate() {
+            if let Some((other, _, _)) = self.bindings[..i]
+                .iter()
+                .find(|(other, c, m)| other != action && c == code && m == modifiers)
+            {
+                warn!("{modifiers:?} {code:?} is bound to both {other:?} and {action:?}, only {other:?} will be used");
+            }
+        }
+    }
+}
+
+/// The shift modifier is ignored for characters as it is already part of the character
+fn normalize(code: KeyCode, modifiers: KeyModifiers) -> KeyModifiers {
+    match code {
+        KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+        _ => modifiers,
+    }
+}
+
+/// Parses a key like `ctrl+right`, `space` or `T`
+pub fn parse_keybinding(s: &str) -> Result<(KeyCode, KeyModifiers), String> {
+    // `+` is both a key and the separator
+    let (modifiers, key) = match s.strip_suffix('+') {
+        Some(rest) if rest.is_empty() || rest.ends_with('+') => (rest, "+"),
+        _ => s.rsplit_once('+').map_or(("", s), |(m, k)| (m, k)),
+    };
+    let mut parsed_modifiers = KeyModifiers::NONE;
+    for modifier in modifiers.split('+').filter(|x| !x.is_empty()) {
+        parsed_modifiers |= match modifier.to_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return Err(format!("unknown modifier `{modifier}`")),
+        };
+    }
+    let mut chars = key.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(c), None) => KeyCode::Char(c),
+        _ => match key.to_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backspace" => KeyCode::Backspace,
+            "del" | "delete" => KeyCode::Delete,
+            "insert" => KeyCode::Insert,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            name => match name.strip_prefix('f').and_then(|x| x.parse().ok()) {
+                Some(n) => KeyCode::F(n),
+                None => return Err(format!("unknown key `{key}`")),
+            },
+        },
+    };
+    Ok((code, normalize(code, parsed_modifiers)))
+}
+
+/// Formats a key the way [`parse_keybinding`] reads it
+pub fn format_keybinding(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let mut text = String::new();
+    for (modifier, name) in [
+        (KeyModifiers::CONTROL, "ctrl+"),
+        (KeyModifiers::ALT, "alt+"),
+        (KeyModifiers::SHIFT, "shift+"),
+    ] {
+        if modifiers.contains(modifier) {
+            text.push_str(name);
+        }
+    }
+    match code {
+        KeyCode::Char(' ') => text.push_str("space"),
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::F(n) => text.push_str(&format!("F{n}")),
+        code => text.push_str(&format!("{code:?}").to_lowercase()),
+    }
+    text
+}
+
+#[test]
+fn parse_keybinding_test() {
+    assert_eq!(
+        parse_keybinding("ctrl+right"),
+        Ok((KeyCode::Right, KeyModifiers::CONTROL))
+    );
+    assert_eq!(
+        parse_keybinding("space"),
+        Ok((KeyCode::Char(' '), KeyModifiers::NONE))
+    );
+    assert_eq!(
+        parse_keybinding("+"),
+        Ok((KeyCode::Char('+'), KeyModifiers::NONE))
+    );
+    assert_eq!(
+        parse_keybinding("alt++"),
+        Ok((KeyCode::Char('+'), KeyModifiers::ALT))
+    );
+    assert_eq!(
+        parse_keybinding("shift+T"),
+        Ok((KeyCode::Char('T'), KeyModifiers::NONE))
+    );
+    assert_eq!(
+        parse_keybinding("F5"),
+        Ok((KeyCode::F(5), KeyModifiers::NONE))
+    );
+    assert_eq!(
+        format_keybinding(KeyCode::Right, KeyModifiers::CONTROL),
+        "ctrl+right"
+    );
+    assert_eq!(
+        format_keybinding(KeyCode::PageUp, KeyModifiers::NONE),
+        "pageup"
+    );
+    assert!(parse_keybinding("hyper+x").is_err());
+    assert!(parse_keybinding("nothing").is_err());
+}
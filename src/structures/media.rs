@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use flume::Sender;
-use log::{error, info};
+use log::{error, info, warn};
 use player::Player;
 use souvlaki::{
     Error, MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition,
@@ -18,6 +18,7 @@ pub struct Media {
 
     current_meta: Option<(String, String, String)>,
     current_playback: Option<MediaPlayback>,
+    current_volume: Option<u8>,
 }
 
 impl Media {
@@ -28,6 +29,7 @@ impl Media {
                 controls: None,
                 current_meta: None,
                 current_playback: None,
+                current_volume: None,
             };
         }
         let mut handle = get_handle(&updater);
@@ -42,6 +44,7 @@ impl Media {
             controls: handle,
             current_meta: None,
             current_playback: None,
+            current_volume: None,
         }
     }
 
@@ -56,7 +59,10 @@ impl Media {
                 album: current.as_ref().map(|video| video.album.as_str()),
                 artist: current.as_ref().map(|video| video.author.as_str()),
                 cover_url: None,
-                duration: None,
+                duration: current
+                    .as_ref()
+                    .and_then(|video| video.duration_seconds)
+                    .map(|secs| Duration::from_secs(secs.into())),
             };
             if self.current_meta
                 != Some((
@@ -87,6 +93,12 @@ impl Media {
                 self.current_playback = Some(playback.clone());
                 e.set_playback(playback)?;
             }
+            // Only MPRIS has a volume property
+            #[cfg(all(unix, not(target_os = "macos")))]
+            if self.current_volume != Some(sink.volume_percent()) {
+                self.current_volume = Some(sink.volume_percent());
+                e.set_volume(f64::from(sink.volume_percent()) / 100.0)?;
+            }
         }
         Ok(())
     }
@@ -94,9 +106,15 @@ impl Media {
 
 fn connect(mpris: &mut MediaControls, sender: Sender<SoundAction>) -> Result<(), Error> {
     mpris.attach(move |e| match e {
-        MediaControlEvent::Toggle | MediaControlEvent::Play | MediaControlEvent::Pause => {
+        MediaControlEvent::Toggle => {
             sender.send(SoundAction::PlayPause).unwrap();
         }
+        MediaControlEvent::Play => {
+            sender.send(SoundAction::Play).unwrap();
+        }
+        MediaControlEvent::Pause => {
+            sender.send(SoundAction::Pause).unwrap();
+        }
         MediaControlEvent::Next => {
             sender.send(SoundAction::Next(1)).unwrap();
         }
@@ -114,29 +132,37 @@ fn connect(mpris: &mut MediaControls, sender: Sender<SoundAction>) -> Result<(),
                 sender.send(SoundAction::Backward).unwrap();
             }
         },
-        // TODO(functionnality): implement seek amount
-        MediaControlEvent::SeekBy(a, _b) => {
-            if a == SeekDirection::Forward {
-                sender.send(SoundAction::Forward).unwrap();
-            } else {
-                sender.send(SoundAction::Backward).unwrap();
-            }
+        MediaControlEvent::SeekBy(direction, amount) => {
+            let secs = amount.as_secs_f64();
+            sender
+                .send(SoundAction::SeekBy(
+                    if direction == SeekDirection::Forward {
+                        secs
+                    } else {
+                        -secs
+                    },
+                ))
+                .unwrap();
         }
-
-        MediaControlEvent::SetPosition(a) => {
-            todo!("Can't set position to {a:?}")
+        MediaControlEvent::SetPosition(MediaPosition(position)) => {
+            sender.send(SoundAction::SeekTo(position)).unwrap();
         }
-        MediaControlEvent::OpenUri(a) => {
-            todo!("Implement URI opening {a:?}")
+        MediaControlEvent::OpenUri(uri) => {
+            warn!("Opening {uri} from the media controls isn't supported");
         }
         MediaControlEvent::Raise => {
-            todo!("Implement raise")
+            info!("The terminal can't be raised from the media controls");
         }
         MediaControlEvent::Quit => {
             shutdown();
         }
-        MediaControlEvent::SetVolume(e) => {
-            todo!("Implement volume setting {e:?}");
+        MediaControlEvent::SetVolume(volume) => {
+            sender
+                .send(SoundAction::FadeVolumeTo(
+                    (volume.clamp(0.0, 1.0) * 100.0).round() as u8,
+                    Duration::from_millis(200),
+                ))
+                .unwrap();
         }
     })
 }
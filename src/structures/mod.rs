@@ -1,5 +1,10 @@
 pub mod app_status;
+pub mod download_progress;
+pub mod keybindings;
 pub mod media;
 pub mod music_status;
 pub mod performance;
+pub mod persistence;
+pub mod repeat_mode;
+pub mod session_stats;
 pub mod sound_action;
@@ -0,0 +1,86 @@
+use std::sync::Mutex;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{CACHE_DIR, CONFIG},
+    utils::write_atomic,
+};
+
+use super::repeat_mode::RepeatMode;
+
+const PLAYER_STATE_FILE: &str = "player_state.json";
+/// Where the repeat mode was saved before it was part of the player state
+const LEGACY_REPEAT_MODE_FILE: &str = "repeat_mode";
+
+/// The state set since the last save, written by `flush_player_state` on shutdown
+static UNSAVED: Mutex<Option<SavedPlayerState>> = Mutex::new(None);
+
+/// The settings of the player changed at runtime, restored on the next startup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SavedPlayerState {
+    /// Volume of the player, in percent
+    pub volume: u8,
+    #[serde(default = "read_legacy_repeat_mode")]
+    pub repeat_mode: RepeatMode,
+}
+
+impl Default for SavedPlayerState {
+    /// The state before the first save, the volume of the config
+    fn default() -> Self {
+        Self {
+            volume: CONFIG.player.initial_volume,
+            repeat_mode: read_legacy_repeat_mode(),
+        }
+    }
+}
+
+fn read_legacy_repeat_mode() -> RepeatMode {
+    RepeatMode::from_name(
+        std::fs::read_to_string(CACHE_DIR.join(LEGACY_REPEAT_MODE_FILE))
+            .unwrap_or_default()
+            .trim(),
+    )
+    .unwrap_or_default()
+}
+
+/// Reads the state written by `save_player_state`, the default one when it was never saved
+pub fn load_player_state() -> SavedPlayerState {
+    let Ok(content) = std::fs::read_to_string(CACHE_DIR.join(PLAYER_STATE_FILE)) else {
+        return SavedPlayerState::default();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        warn!("Can't read {PLAYER_STATE_FILE}: {e}");
+        SavedPlayerState::default()
+    })
+}
+
+/// Saves the state in the cache directory so it is restored on the next startup
+pub fn save_player_state(state: &SavedPlayerState) {
+    *UNSAVED.lock().unwrap() = None;
+    let result = serde_json::to_string(state)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            write_atomic(&CACHE_DIR.join(PLAYER_STATE_FILE), json.as_bytes())
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        warn!("Can't save the player state: {e}");
+        return;
+    }
+    let _ = std::fs::remove_file(CACHE_DIR.join(LEGACY_REPEAT_MODE_FILE));
+}
+
+/// Keeps a state to save later, it is written on shutdown if it wasn't saved meanwhile
+pub fn set_unsaved_player_state(state: SavedPlayerState) {
+    *UNSAVED.lock().unwrap() = Some(state);
+}
+
+/// Saves the state set by `set_unsaved_player_state` since the last save
+pub fn flush_player_state() {
+    let unsaved = UNSAVED.lock().unwrap().take();
+    if let Some(state) = unsaved {
+        save_player_state(&state);
+    }
+}
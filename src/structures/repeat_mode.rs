@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// What happens when the current track ends
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    /// Stops after the last track of the queue
+    #[default]
+    None,
+    /// Replays the current track
+    One,
+    /// Goes back to the first track after the last one
+    All,
+}
+
+impl RepeatMode {
+    /// Returns the next mode in the `None` -> `All` -> `One` cycle
+    pub fn cycle(self) -> Self {
+        match self {
+            Self::None => Self::All,
+            Self::All => Self::One,
+            Self::One => Self::None,
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::One => "↺",
+            Self::All => "⟳",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::One => "one",
+            Self::All => "all",
+        }
+    }
+
+    /// Parses the name returned by [`RepeatMode::name`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "none" => Some(Self::None),
+            "one" => Some(Self::One),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+/// A track is counted once played for this long
+const MIN_PLAYED_SECS: u32 = 30;
+/// Longest jump of the position counted as played between two updates, longer ones are seeks
+const MAX_TICK_SECS: u32 = 2;
+
+/// What was listened to since ytermusic was started, shown with `i` in the player
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    pub tracks_played: u32,
+    pub total_seconds_played: u64,
+    pub artist_play_counts: HashMap<String, u32>,
+    /// The artist of the track being played
+    playing: Option<String>,
+    /// The seconds the track being played was listened to, the seeks aren't counted
+    played_secs: u32,
+    /// The position in the track being played at the last update, in seconds
+    last_position: u32,
+}
+
+impl SessionStats {
+    /// Counts the seconds played since the last update
+    pub fn update_position(&mut self, position: u32) {
+        let played = position.saturating_sub(self.last_position);
+        if (1..=MAX_TICK_SECS).contains(&played) {
+            self.played_secs += played;
+            self.total_seconds_played += u64::from(played);
+        }
+        self.last_position = position;
+    }
+
+    /// Counts the previous track if it was played long enough and starts counting a new one
+    pub fn track_started(&mut self, artist: &str) {
+        self.track_left();
+        self.playing = Some(artist.to_owned());
+    }
+
+    /// Counts the track being played if it was played long enough, does nothing when it
+    /// was already counted
+    pub fn track_left(&mut self) {
+        if let Some(artist) = self.playing.take() {
+            if self.played_secs >= MIN_PLAYED_SECS {
+                self.tracks_played += 1;
+                *self.artist_play_counts.entry(artist).or_default() += 1;
+            }
+        }
+        self.played_secs = 0;
+        self.last_position = 0;
+    }
+
+    /// The `n` most played artists, the most played first
+    pub fn top_artists(&self, n: usize) -> Vec<(&str, u32)> {
+        let mut artists = self
+            .artist_play_counts
+            .iter()
+            .map(|(artist, count)| (artist.as_str(), *count))
+            .collect::<Vec<_>>();
+        artists.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        artists.truncate(n);
+        artists
+    }
+}
+
+#[test]
+fn session_stats_test() {
+    let mut stats = SessionStats::default();
+    stats.track_started("A");
+    for position in 1..=40 {
+        stats.update_position(position);
+    }
+    stats.track_started("B");
+    // A seek isn't counted as played
+    stats.update_position(1);
+    stats.update_position(60);
+    stats.track_started("A");
+    for position in 1..=30 {
+        stats.update_position(position);
+    }
+    stats.track_left();
+    stats.track_left();
+    assert_eq!(stats.tracks_played, 2);
+    assert_eq!(stats.total_seconds_played, 71);
+    assert_eq!(stats.top_artists(5), vec![("A", 2)]);
+}
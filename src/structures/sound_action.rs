@@ -1,29 +1,82 @@
+use std::time::{Duration, Instant};
+
+use flume::Sender;
+use log::info;
 use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::{
-    errors::{handle_error, handle_error_option},
-    systems::{download, player::PlayerState},
-    tasks::download::IN_DOWNLOAD,
+    database,
+    errors::{handle_play_error, handle_play_error_option},
+    systems::{
+        download,
+        ipc::PlayerStatus,
+        player::{remove_from_queue, PlayerState},
+    },
+    tasks::{api, download::IN_DOWNLOAD, last_playlist},
+    term::{ManagerMessage, NOTIFICATION_DURATION},
+    utils::copy_to_clipboard,
     DATABASE,
 };
 
-use super::app_status::MusicDownloadStatus;
+use super::{app_status::MusicDownloadStatus, repeat_mode::RepeatMode};
 /// Actions that can be sent to the player from other services
 #[derive(Debug, Clone)]
 pub enum SoundAction {
     Cleanup,
     PlayPause,
+    Play,
+    Pause,
+    /// Moves the playback to a position of the song
+    SeekTo(Duration),
+    /// Moves the playback by a number of seconds, backward when negative
+    SeekBy(f64),
     RestartPlayer,
     Plus,
     Minus,
+    SetSpeed(f32),
+    FadeVolumeTo(u8, Duration),
+    SetSleepTimer(Duration),
+    ClearSleepTimer,
+    CycleRepeat,
+    SetRepeat(RepeatMode),
+    SetSeekStep(u8),
+    /// Changes the gains of the bands of the equalizer, in dB
+    SetEqBands([f32; 10]),
     Previous(usize),
+    /// Returns to the track and the position left by the last skip
+    UndoSkip,
     Forward,
     Backward,
     Next(usize),
     AddVideosToQueue(Vec<YoutubeMusicVideoRef>),
+    /// Adds the video at the end of the queue
     AddVideoUnary(YoutubeMusicVideoRef),
+    /// Adds the video right after the current one
+    QueueNext(YoutubeMusicVideoRef),
+    /// Removes the video from the database and from the queue, the next song is played when
+    /// it is the current one
+    DeleteVideoUnary(YoutubeMusicVideoRef),
+    /// Plays the video right away, the rest of the queue is kept
+    PlayVideoUnary(YoutubeMusicVideoRef),
     ReplaceQueue(Vec<YoutubeMusicVideoRef>),
+    /// Replaces the songs after the current one and plays the video at the position
+    ReplaceQueueFrom(Vec<YoutubeMusicVideoRef>, usize),
+    /// Moves a song of the queue to another position, the playback isn't interrupted
+    MoveTrack {
+        from: usize,
+        to: usize,
+    },
     VideoStatusUpdate(String, MusicDownloadStatus),
+    CancelDownload(String),
+    ToggleDownloadPause,
+    /// Replies with the state of the player, for the IPC socket
+    Status(Sender<PlayerStatus>),
+    /// Adds a video to a playlist of the YouTube Music library, from their ids
+    SaveToYouTubePlaylist(String, String),
+    /// Likes the video or removes its like on YouTube Music
+    SetLike(String, bool),
+    /// Copies the URL to the clipboard, it is shown instead when there is no clipboard
+    CopyVideoUrl(String),
 }
 
 impl SoundAction {
@@ -36,23 +89,118 @@ impl SoundAction {
         }
         if matches!(
             player.music_status.get(&video),
-            Some(&MusicDownloadStatus::Downloading(_) | &MusicDownloadStatus::Downloaded)
+            Some(
+                &MusicDownloadStatus::Downloading(_)
+                    | &MusicDownloadStatus::Resuming
+                    | &MusicDownloadStatus::Downloaded
+            )
         ) && status == MusicDownloadStatus::NotDownloaded
         {
             return;
         }
         player.music_status.insert(video, status);
     }
+    /// Adds the download status of a video added to the queue
+    fn insert_video(player: &mut PlayerState, video: &YoutubeMusicVideoRef) {
+        let downloaded = DATABASE
+            .read()
+            .unwrap()
+            .iter()
+            .any(|e| e.video_id == video.video_id);
+        Self::insert(
+            player,
+            video.video_id.clone(),
+            if downloaded {
+                MusicDownloadStatus::Downloaded
+            } else {
+                MusicDownloadStatus::NotDownloaded
+            },
+        );
+    }
     pub fn apply_sound_action(self, player: &mut PlayerState) {
+        let changes_queue = self.changes_queue();
+        let current = player.current;
+        self.apply(player);
+        if changes_queue {
+            last_playlist::remember_queue(&player.list, player.current);
+        } else if player.current != current {
+            last_playlist::remember_current(player.current);
+        }
+    }
+
+    /// Whether the action changes the tracks of the queue, so it is remembered for the next start
+    fn changes_queue(&self) -> bool {
+        matches!(
+            self,
+            Self::Cleanup
+                | Self::MoveTrack { .. }
+                | Self::AddVideosToQueue(_)
+                | Self::AddVideoUnary(_)
+                | Self::QueueNext(_)
+                | Self::DeleteVideoUnary(_)
+                | Self::PlayVideoUnary(_)
+                | Self::ReplaceQueue(_)
+                | Self::ReplaceQueueFrom(_, _)
+        )
+    }
+
+    fn apply(self, player: &mut PlayerState) {
         match self {
             Self::Backward => player.sink.seek_bw(),
             Self::Forward => player.sink.seek_fw(),
             Self::PlayPause => player.sink.toggle_playback(),
+            Self::Play => {
+                if player.sink.is_paused() {
+                    player.sink.resume();
+                }
+            }
+            Self::Pause => {
+                if !player.sink.is_paused() {
+                    player.sink.pause();
+                }
+            }
+            Self::SeekTo(position) => {
+                if let Some(duration) = player.sink.duration() {
+                    player.sink.seek_to(Duration::from_secs_f64(
+                        position.as_secs_f64().min(duration),
+                    ));
+                }
+            }
+            Self::SeekBy(secs) => {
+                let position = (f64::from(player.sink.elapsed()) + secs).max(0.0);
+                Self::SeekTo(Duration::from_secs_f64(position)).apply_sound_action(player);
+            }
+            Self::Status(sender) => {
+                let _ = sender.send(PlayerStatus {
+                    track: player.current().cloned(),
+                    position_secs: player.sink.elapsed(),
+                    duration_secs: player.sink.duration(),
+                    volume: player.sink.volume_percent(),
+                    paused: player.sink.is_paused(),
+                });
+            }
+            Self::SaveToYouTubePlaylist(playlist_id, video_id) => {
+                api::spawn_save_to_playlist_task(playlist_id, video_id, player.updater.clone());
+            }
+            Self::SetLike(video_id, like) => {
+                api::spawn_like_task(video_id, like, player.updater.clone());
+            }
+            Self::CopyVideoUrl(url) => {
+                let message = if copy_to_clipboard(&url) {
+                    "Copied the video URL".to_owned()
+                } else {
+                    info!("Video URL: {url}");
+                    format!("No clipboard, the URL is {url}")
+                };
+                let _ = player
+                    .updater
+                    .send(ManagerMessage::Notify(message, NOTIFICATION_DURATION));
+            }
             Self::Cleanup => {
                 player.list.clear();
                 player.current = 0;
                 player.music_status.clear();
-                handle_error(
+                handle_play_error(
                     &player.updater,
                     "sink stop",
                     player.sink.stop(&player.guard),
@@ -60,8 +208,35 @@ impl SoundAction {
             }
             Self::Plus => player.sink.volume_up(),
             Self::Minus => player.sink.volume_down(),
+            Self::SetSpeed(rate) => player.sink.set_speed(rate),
+            Self::FadeVolumeTo(target, duration) => player.sink.fade_volume(target, duration),
+            Self::SetSleepTimer(duration) => player.sleep_until = Some(Instant::now() + duration),
+            Self::ClearSleepTimer => player.sleep_until = None,
+            Self::MoveTrack { from, to } => {
+                if from >= player.list.len() || to >= player.list.len() {
+                    return;
+                }
+                let video = player.list.remove(from);
+                player.list.insert(to, video);
+                // The index of the playing song follows it, or shifts when a song moves across it
+                if player.current == from {
+                    player.current = to;
+                } else if from < player.current && player.current <= to {
+                    player.current -= 1;
+                } else if to <= player.current && player.current < from {
+                    player.current += 1;
+                }
+            }
+            Self::CycleRepeat => {
+                player.repeat_mode = player.repeat_mode.cycle();
+            }
+            Self::SetRepeat(mode) => player.repeat_mode = mode,
+            Self::SetSeekStep(secs) => player.sink.set_seek_step_secs(secs),
+            Self::SetEqBands(bands) => player.sink.set_eq_bands(&bands),
             Self::Next(a) => {
-                handle_error(
+                player.remember_skip();
+                player.session_stats.track_left();
+                handle_play_error(
                     &player.updater,
                     "sink stop",
                     player.sink.stop(&player.guard),
@@ -70,7 +245,14 @@ impl SoundAction {
                 player.set_relative_current(a as _);
             }
             Self::VideoStatusUpdate(video, status) => {
+                if status == MusicDownloadStatus::Downloaded {
+                    player.prebuffer_completed(&video);
+                }
                 player.music_status.insert(video, status);
+                // Progress updates are too frequent and don't change the queue
+                if !matches!(status, MusicDownloadStatus::Downloading(x) if x > 1) {
+                    download::save_queue();
+                }
             }
             Self::AddVideosToQueue(video) => {
                 let db = DATABASE.read().unwrap();
@@ -88,47 +270,102 @@ impl SoundAction {
                 }
             }
             Self::Previous(a) => {
+                player.remember_skip();
+                player.session_stats.track_left();
                 player.set_relative_current(-(a as isize));
-                handle_error(
+                handle_play_error(
+                    &player.updater,
+                    "sink stop",
+                    player.sink.stop(&player.guard),
+                );
+            }
+            Self::UndoSkip => {
+                let Some((index, position)) = player.history.pop_back() else {
+                    return;
+                };
+                if index >= player.list.len() {
+                    return;
+                }
+                player.current = index;
+                // Prevents the stopped track from being counted as finished and skipped
+                player.rtcurrent = None;
+                player.resume_at = Some(position);
+                handle_play_error(
                     &player.updater,
                     "sink stop",
                     player.sink.stop(&player.guard),
                 );
             }
             Self::RestartPlayer => {
-                (player.sink, player.guard) =
-                    handle_error_option(&player.updater, "update player", player.sink.update())
-                        .unwrap();
+                (player.sink, player.guard) = handle_play_error_option(
+                    &player.updater,
+                    "update player",
+                    player.sink.update(),
+                )
+                .unwrap();
                 if let Some(e) = player.current().cloned() {
-                    Self::AddVideoUnary(e).apply_sound_action(player);
+                    Self::QueueNext(e).apply_sound_action(player);
                 }
             }
             Self::AddVideoUnary(video) => {
-                Self::insert(
-                    player,
-                    video.video_id.clone(),
-                    if DATABASE
-                        .read()
-                        .unwrap()
-                        .iter()
-                        .any(|e| e.video_id == video.video_id)
-                    {
-                        MusicDownloadStatus::Downloaded
-                    } else {
-                        MusicDownloadStatus::NotDownloaded
-                    },
-                );
-                if player.list.is_empty() {
-                    player.list.push(video);
-                } else {
-                    player.list.insert(player.current + 1, video);
+                Self::insert_video(player, &video);
+                player.list.push(video);
+            }
+            Self::QueueNext(video) => {
+                Self::insert_video(player, &video);
+                // Appended when the current song is the last one or the queue ended
+                let position = (player.current + 1).min(player.list.len());
+                player.list.insert(position, video);
+            }
+            Self::DeleteVideoUnary(video) => {
+                database::remove_video(&video);
+                let (current, removed) = remove_from_queue(&mut player.list, player.current, |x| {
+                    x.video_id == video.video_id
+                });
+                player.current = current;
+                player.music_status.remove(&video.video_id);
+                if removed {
+                    handle_play_error(
+                        &player.updater,
+                        "sink stop",
+                        player.sink.stop(&player.guard),
+                    );
+                }
+            }
+            Self::PlayVideoUnary(video) => {
+                let was_empty = player.list.is_empty();
+                Self::QueueNext(video).apply_sound_action(player);
+                if !was_empty {
+                    Self::Next(1).apply_sound_action(player);
                 }
             }
+            Self::CancelDownload(video) => {
+                download::cancel_download(&video, &player.soundaction_sender);
+            }
+            Self::ToggleDownloadPause => {
+                let message = if download::is_download_paused() {
+                    download::resume_download();
+                    "Downloads resumed"
+                } else {
+                    download::pause_download();
+                    "Downloads paused"
+                };
+                player
+                    .updater
+                    .send(ManagerMessage::Notify(
+                        message.to_owned(),
+                        NOTIFICATION_DURATION,
+                    ))
+                    .unwrap();
+            }
             Self::ReplaceQueue(videos) => {
+                Self::ReplaceQueueFrom(videos, 0).apply_sound_action(player);
+            }
+            Self::ReplaceQueueFrom(videos, position) => {
                 player.list.truncate(player.current + 1);
                 download::clean(&player.soundaction_sender);
                 Self::AddVideosToQueue(videos).apply_sound_action(player);
-                Self::Next(1).apply_sound_action(player);
+                Self::Next(position + 1).apply_sound_action(player);
             }
         }
     }
@@ -0,0 +1,77 @@
+use std::{
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use discord_presence::Client;
+use flume::{Selector, Sender};
+use log::{info, warn};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{consts::CONFIG, SIGNALING_STOP};
+
+/// How often the activity is sent again while Discord isn't connected
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+enum Event {
+    Track(YoutubeMusicVideoRef),
+    Stop,
+}
+
+/// Starts the Discord Rich Presence when enabled, the player sends the started tracks to the
+/// returned channel. Discord is connected to in the background and may not be running.
+pub fn spawn_discord_rpc() -> Option<Sender<YoutubeMusicVideoRef>> {
+    let config = &CONFIG.integrations;
+    if !config.discord_rich_presence {
+        return None;
+    }
+    let Some(application_id) = config.discord_application_id else {
+        warn!("Discord Rich Presence is enabled but `discord_application_id` isn't set");
+        return None;
+    };
+    let (sender, receiver) = flume::unbounded();
+    thread::spawn(move || {
+        let mut client = Client::new(application_id);
+        client.start();
+        info!("Discord Rich Presence on");
+        // The track to show and when it started, kept until Discord is connected
+        let mut pending: Option<(YoutubeMusicVideoRef, u64)> = None;
+        loop {
+            let event = Selector::new()
+                .recv(&receiver, |x| x.map_or(Event::Stop, Event::Track))
+                .recv(&SIGNALING_STOP.1, |_| Event::Stop)
+                .wait_timeout(RETRY_DELAY);
+            match event {
+                Ok(Event::Track(video)) => {
+                    let start = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs();
+                    pending = Some((video, start));
+                }
+                Ok(Event::Stop) => break,
+                Err(_) => {}
+            }
+            if !Client::is_ready() {
+                continue;
+            }
+            if let Some((video, start)) = pending.take() {
+                let result = client.set_activity(|activity| {
+                    activity
+                        .state(video.author.as_str())
+                        .details(video.title.as_str())
+                        .timestamps(|timestamps| timestamps.start(start))
+                });
+                if let Err(e) = result {
+                    warn!("Can't set the Discord activity: {e}");
+                    pending = Some((video, start));
+                }
+            }
+        }
+        if Client::is_ready() {
+            let _ = client.clear_activity();
+        }
+        let _ = client.shutdown();
+    });
+    Some(sender)
+}
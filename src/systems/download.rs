@@ -1,30 +1,228 @@
-use std::{collections::VecDeque, sync::Mutex, time::Duration};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
 
 use flume::Sender;
+use log::{error, info};
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use tokio::{task::JoinHandle, time::sleep};
 use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::{
+    consts::CACHE_DIR,
     run_service,
-    structures::sound_action::SoundAction,
+    structures::{
+        app_status::MusicDownloadStatus, download_progress::DownloadProgress,
+        sound_action::SoundAction,
+    },
     tasks::download::{start_download, IN_DOWNLOAD},
+    utils::write_atomic,
+    DATABASE,
 };
 
-pub static HANDLES: Lazy<Mutex<Vec<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(Vec::new()));
-pub static DOWNLOAD_LIST: Lazy<Mutex<VecDeque<YoutubeMusicVideoRef>>> =
+/// The running downloads by video id
+pub static HANDLES: Lazy<Mutex<HashMap<String, JoinHandle<()>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+static WORKERS: Lazy<Mutex<Vec<JoinHandle<()>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+/// When set, the workers don't start new downloads, the running ones are completed
+static PAUSED: AtomicBool = AtomicBool::new(false);
+/// Videos the user cancelled, they aren't downloaded again unless explicitly requested
+pub static CANCELLED: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+static DOWNLOAD_LIST: Lazy<Mutex<VecDeque<YoutubeMusicVideoRef>>> =
+    Lazy::new(|| Mutex::new(VecDeque::new()));
+/// Downloads restored from the previous session, processed after `DOWNLOAD_LIST`
+static RESTORED_LIST: Lazy<Mutex<VecDeque<YoutubeMusicVideoRef>>> =
     Lazy::new(|| Mutex::new(VecDeque::new()));
+/// The downloads of the session, read by the downloads screen on each render
+pub static DOWNLOAD_STATE: Lazy<Arc<RwLock<DownloadState>>> = Lazy::new(Arc::default);
+
+/// The pending, running and finished downloads of the session
+#[derive(Debug, Default)]
+pub struct DownloadState {
+    /// The songs waiting for a worker, in the order they will be downloaded
+    pub queued: VecDeque<YoutubeMusicVideoRef>,
+    /// The progress of the running downloads by video id
+    pub active: HashMap<String, DownloadProgress>,
+    /// The songs downloaded during this session, most recent first
+    pub completed: VecDeque<YoutubeMusicVideoRef>,
+    /// The songs whose download failed, most recent first
+    pub failed: VecDeque<YoutubeMusicVideoRef>,
+}
+
+impl DownloadState {
+    /// Registers a running download, it is no longer listed as failed
+    pub fn start(&mut self, video_id: &str) {
+        self.failed.retain(|x| x.video_id != video_id);
+        self.active
+            .insert(video_id.to_owned(), DownloadProgress::new(0, None));
+    }
+
+    pub fn update(&mut self, video_id: &str, downloaded_bytes: u64, total_bytes: Option<u64>) {
+        if let Some(progress) = self.active.get_mut(video_id) {
+            progress.update(downloaded_bytes, total_bytes);
+        }
+    }
+
+    /// Moves a running download to the completed or the failed ones
+    pub fn finish(&mut self, video: &YoutubeMusicVideoRef, success: bool) {
+        self.active.remove(&video.video_id);
+        let list = if success {
+            &mut self.completed
+        } else {
+            &mut self.failed
+        };
+        list.retain(|x| x.video_id != video.video_id);
+        list.push_front(video.clone());
+    }
+}
+
+/// The pending downloads saved to disk
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct DownloadQueue {
+    in_download: Vec<YoutubeMusicVideoRef>,
+    download_list: Vec<YoutubeMusicVideoRef>,
+}
 
 fn take() -> Option<YoutubeMusicVideoRef> {
-    DOWNLOAD_LIST.lock().unwrap().pop_front()
+    let video = take_next();
+    refresh_queued();
+    video
+}
+
+fn take_next() -> Option<YoutubeMusicVideoRef> {
+    let cancelled = CANCELLED.lock().unwrap();
+    {
+        let mut list = DOWNLOAD_LIST.lock().unwrap();
+        while let Some(video) = list.pop_front() {
+            if !cancelled.contains(&video.video_id) {
+                return Some(video);
+            }
+        }
+    }
+    // Locked in the same order as `restore_queue` to avoid a deadlock
+    let database = DATABASE.read().unwrap();
+    let mut restored = RESTORED_LIST.lock().unwrap();
+    while let Some(video) = restored.pop_front() {
+        if !database.iter().any(|x| x.video_id == video.video_id)
+            && !cancelled.contains(&video.video_id)
+        {
+            return Some(video);
+        }
+    }
+    None
+}
+
+/// The songs waiting for a worker, in the order they will be downloaded
+pub fn queued() -> Vec<YoutubeMusicVideoRef> {
+    let cancelled = CANCELLED.lock().unwrap();
+    DOWNLOAD_LIST
+        .lock()
+        .unwrap()
+        .iter()
+        .chain(RESTORED_LIST.lock().unwrap().iter())
+        .filter(|x| !cancelled.contains(&x.video_id))
+        .cloned()
+        .collect()
+}
+
+/// Copies the songs waiting for a worker to `DOWNLOAD_STATE`
+fn refresh_queued() {
+    let queued = queued().into();
+    DOWNLOAD_STATE.write().unwrap().queued = queued;
+}
+
+/// Replaces the songs to download first, the ones restored from the previous session are kept
+pub fn set_download_list(videos: VecDeque<YoutubeMusicVideoRef>) {
+    *DOWNLOAD_LIST.lock().unwrap() = videos;
+    refresh_queued();
+}
+
+pub fn add_to_download_list(videos: impl IntoIterator<Item = YoutubeMusicVideoRef>) {
+    DOWNLOAD_LIST.lock().unwrap().extend(videos);
+    refresh_queued();
+}
+
+/// Saves the pending and in progress downloads to `download_queue.json` in the cache
+pub fn save_queue() {
+    let queue = DownloadQueue {
+        in_download: IN_DOWNLOAD.lock().unwrap().values().cloned().collect(),
+        download_list: queued(),
+    };
+    let result = serde_json::to_string(&queue)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            write_atomic(&CACHE_DIR.join("download_queue.json"), json.as_bytes())
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        error!("Can't save the download queue: {e}");
+    }
+}
+
+/// Restores the downloads saved by `save_queue` in the previous session
+fn restore_queue() {
+    let Ok(content) = std::fs::read_to_string(CACHE_DIR.join("download_queue.json")) else {
+        return;
+    };
+    let queue = match serde_json::from_str::<DownloadQueue>(&content) {
+        Ok(queue) => queue,
+        Err(e) => {
+            error!("Can't read the download queue: {e}");
+            return;
+        }
+    };
+    let database = DATABASE.read().unwrap();
+    let mut restored = RESTORED_LIST.lock().unwrap();
+    for video in queue.in_download.into_iter().chain(queue.download_list) {
+        if !database.iter().any(|x| x.video_id == video.video_id)
+            && !restored.iter().any(|x| x.video_id == video.video_id)
+        {
+            restored.push_back(video);
+        }
+    }
+    info!(
+        "Restored {} downloads from the previous session",
+        restored.len()
+    );
+    drop(restored);
+    drop(database);
+    refresh_queued();
+}
+
+/// Spawns the download of a song unless it is already running
+pub fn spawn_download(song: YoutubeMusicVideoRef, s: Sender<SoundAction>) {
+    // The lock is held while spawning so the task can't remove its handle before it is inserted
+    let mut handles = HANDLES.lock().unwrap();
+    if handles.contains_key(&song.video_id) {
+        return;
+    }
+    let id = song.video_id.clone();
+    let handle = run_service(async move {
+        let id = song.video_id.clone();
+        start_download(song, &s).await;
+        HANDLES.lock().unwrap().remove(&id);
+    });
+    handles.insert(id, handle);
 }
 
 /// A worker of this system that downloads pending songs
 fn spawn_system_worker_instance(s: Sender<SoundAction>) {
-    HANDLES.lock().unwrap().push(run_service(async move {
+    WORKERS.lock().unwrap().push(run_service(async move {
         loop {
-            if let Some(id) = take() {
-                start_download(id, &s).await;
+            if PAUSED.load(Ordering::Relaxed) {
+                sleep(Duration::from_secs(1)).await;
+            } else if let Some(video) = take() {
+                let id = video.video_id.clone();
+                spawn_download(video, s.clone());
+                while HANDLES.lock().unwrap().contains_key(&id) {
+                    sleep(Duration::from_millis(200)).await;
+                }
             } else {
                 sleep(Duration::from_millis(200)).await;
             }
@@ -32,24 +230,77 @@ fn spawn_system_worker_instance(s: Sender<SoundAction>) {
     }));
 }
 
-/// Destroy all the worker and task getting processed and starts back the system
+/// Stops starting new downloads until `resume_download` is called
+pub fn pause_download() {
+    PAUSED.store(true, Ordering::Relaxed);
+}
+
+pub fn resume_download() {
+    PAUSED.store(false, Ordering::Relaxed);
+}
+
+pub fn is_download_paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+/// Removes a song from the pending downloads or aborts its download if it is running.
+/// The partial file of an aborted download is deleted.
+pub fn cancel_download(video_id: &str, sender: &Sender<SoundAction>) {
+    CANCELLED.lock().unwrap().insert(video_id.to_owned());
+    DOWNLOAD_LIST
+        .lock()
+        .unwrap()
+        .retain(|x| x.video_id != video_id);
+    RESTORED_LIST
+        .lock()
+        .unwrap()
+        .retain(|x| x.video_id != video_id);
+    refresh_queued();
+    if let Some(handle) = HANDLES.lock().unwrap().remove(video_id) {
+        handle.abort();
+        IN_DOWNLOAD.lock().unwrap().remove(video_id);
+        DOWNLOAD_STATE.write().unwrap().active.remove(video_id);
+        let partial = CACHE_DIR.join(format!("downloads/{video_id}.mp4"));
+        if partial.exists() {
+            if let Err(e) = std::fs::remove_file(partial) {
+                error!("Can't remove the partial download of {video_id}: {e}");
+            }
+        }
+        sender
+            .send(SoundAction::VideoStatusUpdate(
+                video_id.to_owned(),
+                MusicDownloadStatus::NotDownloaded,
+            ))
+            .unwrap();
+    }
+    save_queue();
+}
+
+/// Destroy all the worker and task getting processed and starts back the system, the downloads
+/// restored from the previous session are kept
 pub fn clean(sender: &Sender<SoundAction>) {
     DOWNLOAD_LIST.lock().unwrap().clear();
+    CANCELLED.lock().unwrap().clear();
 
     IN_DOWNLOAD.lock().unwrap().clear();
-    {
-        let mut handle = HANDLES.lock().unwrap();
-        for i in handle.iter() {
-            i.abort()
-        }
-        handle.clear();
+    DOWNLOAD_STATE.write().unwrap().active.clear();
+    refresh_queued();
+    for (_, handle) in HANDLES.lock().unwrap().drain() {
+        handle.abort();
+    }
+    for handle in WORKERS.lock().unwrap().drain(..) {
+        handle.abort();
     }
+    save_queue();
     spawn_system(sender);
 }
 
 const DOWNLOADER_COUNT: usize = 4;
 
 pub fn spawn_system(s: &Sender<SoundAction>) {
+    if RESTORED_LIST.lock().unwrap().is_empty() {
+        restore_queue();
+    }
     for _ in 0..DOWNLOADER_COUNT {
         spawn_system_worker_instance(s.clone());
     }
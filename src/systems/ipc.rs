@@ -0,0 +1,204 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use flume::Sender;
+use serde::{Deserialize, Serialize};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{consts::CACHE_DIR, structures::sound_action::SoundAction};
+
+/// Path of the socket the server listens on, removed on shutdown
+static SOCKET_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Used by `--daemon-socket` and `--send` when no path is given
+pub fn default_socket_path() -> PathBuf {
+    CACHE_DIR.join("ytermusic.sock")
+}
+
+/// A line of JSON received on the socket, like `{"action":"seek","secs":10}`
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Command {
+    PlayPause,
+    Play,
+    Pause,
+    Next,
+    Previous,
+    /// Moves the playback by a number of seconds, backward when negative
+    Seek {
+        secs: f64,
+    },
+    SetVolume {
+        percent: u8,
+    },
+    Status,
+}
+
+/// The reply to the `status` command
+#[derive(Debug, Serialize)]
+pub struct PlayerStatus {
+    pub track: Option<YoutubeMusicVideoRef>,
+    pub position_secs: u32,
+    pub duration_secs: Option<f64>,
+    pub volume: u8,
+    pub paused: bool,
+}
+
+/// Sends a command to the player and returns the JSON reply
+async fn handle_command(line: &str, sender: &Sender<SoundAction>) -> String {
+    let command = match serde_json::from_str::<Command>(line) {
+        Ok(command) => command,
+        Err(e) => return serde_json::json!({ "error": e.to_string() }).to_string(),
+    };
+    let action = match command {
+        Command::PlayPause => SoundAction::PlayPause,
+        Command::Play => SoundAction::Play,
+        Command::Pause => SoundAction::Pause,
+        Command::Next => SoundAction::Next(1),
+        Command::Previous => SoundAction::Previous(1),
+        Command::Seek { secs } => SoundAction::SeekBy(secs),
+        Command::SetVolume { percent } => {
+            SoundAction::FadeVolumeTo(percent, Duration::from_millis(200))
+        }
+        Command::Status => {
+            let (status_sender, status_receiver) = flume::bounded(1);
+            let _ = sender.send(SoundAction::Status(status_sender));
+            return match status_receiver.recv_async().await {
+                Ok(status) => serde_json::to_string(&status).unwrap_or_default(),
+                Err(_) => serde_json::json!({ "error": "The player stopped" }).to_string(),
+            };
+        }
+    };
+    let _ = sender.send(action);
+    serde_json::json!({ "ok": true }).to_string()
+}
+
+/// Listens for newline-delimited JSON commands on a Unix socket
+#[cfg(unix)]
+pub fn spawn_ipc_server(path: PathBuf, sender: Sender<SoundAction>) {
+    use log::{error, info, warn};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::UnixListener,
+    };
+
+    use crate::run_service;
+
+    run_service(async move {
+        if let Err(e) = remove_stale_socket(&path) {
+            error!("Can't listen on {}: {e}", path.display());
+            return;
+        }
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Can't listen on {}: {e}", path.display());
+                return;
+            }
+        };
+        *SOCKET_PATH.lock().unwrap() = Some(path.clone());
+        info!("IPC listening on {}", path.display());
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Can't accept an IPC connection: {e}");
+                    continue;
+                }
+            };
+            let sender = sender.clone();
+            run_service(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let reply = handle_command(&line, &sender).await + "\n";
+                    if writer.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+}
+
+/// Removes the socket left over by a previous run.
+///
+/// Fails if the path isn't a socket or if another instance still listens on it.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> Result<(), String> {
+    use std::{
+        io::ErrorKind,
+        os::unix::{fs::FileTypeExt, net::UnixStream},
+    };
+
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.to_string()),
+    };
+    if !metadata.file_type().is_socket() {
+        return Err("the path exists and isn't a socket".to_owned());
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err("another instance is listening on it".to_owned());
+    }
+    std::fs::remove_file(path).map_err(|e| e.to_string())
+}
+
+/// Removes the socket of the IPC server, if it is listening
+pub fn remove_socket() {
+    if let Some(path) = SOCKET_PATH.lock().unwrap().take() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn spawn_ipc_server(_: PathBuf, _: Sender<SoundAction>) {
+    log::error!("The IPC socket is only available on Unix");
+}
+
+/// Sends one command to a running instance and returns its reply, for `--send`
+#[cfg(unix)]
+pub fn send_command(path: &Path, command: &str) -> Result<String, String> {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        os::unix::net::UnixStream,
+    };
+
+    let mut stream = UnixStream::connect(path)
+        .map_err(|e| format!("Can't connect to {}: {e}", path.display()))?;
+    writeln!(stream, "{}", command.trim()).map_err(|e| e.to_string())?;
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .map_err(|e| e.to_string())?;
+    Ok(reply.trim_end().to_owned())
+}
+
+#[cfg(not(unix))]
+pub fn send_command(_: &Path, _: &str) -> Result<String, String> {
+    Err("The IPC socket is only available on Unix".to_owned())
+}
+
+#[test]
+fn parse_commands() {
+    assert!(matches!(
+        serde_json::from_str(r#"{"action":"play_pause"}"#),
+        Ok(Command::PlayPause)
+    ));
+    assert!(matches!(
+        serde_json::from_str(r#"{"action":"seek","secs":-10}"#),
+        Ok(Command::Seek { secs }) if secs == -10.0
+    ));
+    assert!(matches!(
+        serde_json::from_str(r#"{"action":"set_volume","percent":80}"#),
+        Ok(Command::SetVolume { percent: 80 })
+    ));
+    assert!(serde_json::from_str::<Command>(r#"{"action":"dance"}"#).is_err());
+}
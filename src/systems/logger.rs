@@ -1,7 +1,8 @@
-use std::{io::Write, path::PathBuf};
+use std::{io::Write, path::PathBuf, time::SystemTime};
 
+use chrono::{DateTime, SecondsFormat, Utc};
 use flume::Sender;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 
 static LOG: Lazy<Sender<String>> = Lazy::new(|| {
     let (tx, rx) = flume::unbounded::<String>();
@@ -36,6 +37,21 @@ pub fn get_log_file_path() -> PathBuf {
     }
 }
 
+/// Renames `log.txt` to `log.txt.1`, the older files are shifted and only `max_rotations` are kept
+pub fn rotate_logs(max_rotations: u8) {
+    let path = get_log_file_path();
+    let rotated = |n: u8| PathBuf::from(format!("{}.{n}", path.display()));
+    if max_rotations == 0 {
+        let _ = std::fs::remove_file(&path);
+        return;
+    }
+    let _ = std::fs::remove_file(rotated(max_rotations));
+    for n in (1..max_rotations).rev() {
+        let _ = std::fs::rename(rotated(n), rotated(n + 1));
+    }
+    let _ = std::fs::rename(&path, rotated(1));
+}
+
 static LOGGER: SimpleLogger = SimpleLogger;
 static LEVEL: Lazy<(LevelFilter, Level)> = Lazy::new(|| {
     let logger_env = std::env::var("YTERMUSIC_LOG");
@@ -50,7 +66,26 @@ static LEVEL: Lazy<(LevelFilter, Level)> = Lazy::new(|| {
     }
 });
 
-pub fn init() -> Result<(), SetLoggerError> {
+/// The format of the lines, set once by `init` as the config can't be read while it is loading
+static FORMAT: OnceCell<LogFormat> = OnceCell::new();
+
+/// Logs to the end of the log file, it is rotated first if it is too large
+pub fn init(config: &LoggingConfig) -> Result<(), SetLoggerError> {
+    let path = get_log_file_path();
+    let too_large = std::fs::metadata(&path)
+        .is_ok_and(|x| x.len() > config.max_log_size_mb.saturating_mul(1024 * 1024));
+    if too_large {
+        rotate_logs(config.max_rotations);
+    }
+    if config.format == LogFormat::Text {
+        // Separates the sessions in the file
+        let _ = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(b"\n# YTerMusic log file\n\n"));
+    }
+    let _ = FORMAT.set(config.format);
     log::set_logger(&LOGGER).map(|()| log::set_max_level(LEVEL.0))?;
     info!("Logger mode {}", LEVEL.1);
     Ok(())
@@ -58,7 +93,10 @@ pub fn init() -> Result<(), SetLoggerError> {
 
 use log::{info, Level, LevelFilter, Metadata, Record, SetLoggerError};
 
-use crate::utils::get_project_dirs;
+use crate::{
+    config::{LogFormat, LoggingConfig},
+    utils::get_project_dirs,
+};
 
 static FILTER: &[&str] = &["rustls", "tokio-util", "want-", "mio-"];
 
@@ -74,15 +112,29 @@ impl log::Log for SimpleLogger {
             if FILTER.iter().any(|x| record.file().unwrap().contains(x)) {
                 return;
             }
-            LOG.send(format!(
-                "{} - {} [{}]",
-                record.level(),
-                record.args(),
-                record.file().unwrap_or_default()
-            ))
-            .unwrap();
+            LOG.send(format_record(record)).unwrap();
         }
     }
 
     fn flush(&self) {}
 }
+
+/// Formats a line of the log file
+fn format_record(record: &Record) -> String {
+    match FORMAT.get().copied().unwrap_or_default() {
+        LogFormat::Text => format!(
+            "{} - {} [{}]",
+            record.level(),
+            record.args(),
+            record.file().unwrap_or_default()
+        ),
+        LogFormat::Json => serde_json::json!({
+            "level": record.level().as_str(),
+            "message": record.args().to_string(),
+            "target": record.target(),
+            "timestamp": DateTime::<Utc>::from(SystemTime::now())
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+        })
+        .to_string(),
+    }
+}
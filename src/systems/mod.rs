@@ -1,3 +1,7 @@
+pub mod discord_rpc;
 pub mod download;
+pub mod ipc;
 pub mod logger;
+pub mod notify;
 pub mod player;
+pub mod scrobbler;
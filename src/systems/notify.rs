@@ -0,0 +1,32 @@
+use std::{io::ErrorKind, process::Command, thread};
+
+use log::{info, warn};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{consts::CONFIG, tasks::download::thumbnail_path};
+
+/// Shows a desktop notification for a track that just started when enabled, the song is
+/// logged instead when `notify-send` isn't installed
+pub fn notify_track_started(video: &YoutubeMusicVideoRef) {
+    let config = &CONFIG.integrations;
+    if !config.desktop_notifications {
+        return;
+    }
+    let body = format!("{} - {}", video.author, video.title);
+    let mut command = Command::new("notify-send");
+    command
+        .arg("--app-name=ytermusic")
+        .arg(format!("--expire-time={}", config.notification_timeout_ms));
+    let thumbnail = thumbnail_path(&video.video_id);
+    if thumbnail.exists() {
+        command.arg(format!("--icon={}", thumbnail.display()));
+    }
+    command.arg("Now Playing").arg(&body);
+    // Waited for on its own thread not to block the player
+    thread::spawn(move || match command.status() {
+        Ok(status) if !status.success() => warn!("notify-send failed with {status}"),
+        Ok(_) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => info!("Now Playing: {body}"),
+        Err(e) => warn!("Can't run notify-send: {e}"),
+    });
+}
@@ -1,30 +1,72 @@
 use std::{
     collections::{HashMap, VecDeque},
     sync::atomic::Ordering,
+    time::{Duration, Instant},
 };
 
 use flume::{unbounded, Receiver, Sender};
+use log::{error, info};
 use player::{Guard, PlayError, Player, PlayerOptions, StreamError};
 
 use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::{
     consts::{CACHE_DIR, CONFIG},
-    database,
-    errors::{handle_error, handle_error_option},
-    structures::{app_status::MusicDownloadStatus, media::Media, sound_action::SoundAction},
-    term::{list_selector::ListSelector, playlist::PLAYER_RUNNING, ManagerMessage, Screens},
+    database::{self, DATABASE},
+    errors::{handle_error, handle_play_error, handle_play_error_option},
+    structures::{
+        app_status::MusicDownloadStatus,
+        media::Media,
+        persistence::{
+            load_player_state, save_player_state, set_unsaved_player_state, SavedPlayerState,
+        },
+        repeat_mode::RepeatMode,
+        session_stats::SessionStats,
+        sound_action::SoundAction,
+    },
+    tasks::{api::spawn_autoplay_task, download, last_playlist, loudness::TRACK_GAINS},
+    term::{
+        list_selector::ListSelector, playlist::PLAYER_RUNNING, track_menu::TrackMenu,
+        ManagerMessage, Screens, NOTIFICATION_DURATION,
+    },
 };
 
-use super::download::DOWNLOAD_LIST;
+use super::{download::set_download_list, notify, scrobbler::ScrobbleEvent};
+
+/// Number of skips that can be undone
+const HISTORY_SIZE: usize = 20;
+/// Shortest time between two saves of the player state
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(1);
 
 pub struct PlayerState {
     pub goto: Screens,
     pub list: Vec<YoutubeMusicVideoRef>,
     pub current: usize,
     pub rtcurrent: Option<YoutubeMusicVideoRef>,
+    /// The track queued in the sink after the current one when gapless playback is enabled
+    pub preloaded: Option<YoutubeMusicVideoRef>,
+    /// Whether the next track is being downloaded ahead of the end of the current one
+    pub prebuffering: bool,
+    /// Whether the next track is paused as soon as it starts, to restore the last queue silently
+    pub start_paused: bool,
+    /// The tracks and positions left by the last skips, the latest last
+    pub history: VecDeque<(usize, Duration)>,
+    /// Where the next track starts, to return to the position of a skipped track
+    pub resume_at: Option<Duration>,
+    /// The track the autoplay was last requested after, to request it once per track
+    autoplay_after: Option<String>,
+    /// The state last written to `player_state.json` and when it was written
+    saved_state: (SavedPlayerState, Instant),
+    /// When the playback will be paused by the sleep timer
+    pub sleep_until: Option<Instant>,
+    pub repeat_mode: RepeatMode,
     pub music_status: HashMap<String, MusicDownloadStatus>,
     pub list_selector: ListSelector,
+    /// The menu of the hovered song opened with `c`
+    pub track_menu: Option<TrackMenu>,
+    pub session_stats: SessionStats,
+    /// Whether the session statistics are shown above the player
+    pub show_stats: bool,
     pub controls: Media,
     pub sink: Player,
     pub guard: Guard,
@@ -32,6 +74,10 @@ pub struct PlayerState {
     pub soundaction_sender: Sender<SoundAction>,
     pub soundaction_receiver: Receiver<SoundAction>,
     pub stream_error_receiver: Receiver<StreamError>,
+    /// Where the played tracks are sent when Last.fm is configured
+    pub scrobbler: Option<Sender<ScrobbleEvent>>,
+    /// Where the started tracks are sent when the Discord Rich Presence is enabled
+    pub discord: Option<Sender<YoutubeMusicVideoRef>>,
 }
 
 impl PlayerState {
@@ -39,23 +85,44 @@ impl PlayerState {
         soundaction_sender: Sender<SoundAction>,
         soundaction_receiver: Receiver<SoundAction>,
         updater: Sender<ManagerMessage>,
+        scrobbler: Option<Sender<ScrobbleEvent>>,
+        discord: Option<Sender<YoutubeMusicVideoRef>>,
     ) -> Self {
         let (stream_error_sender, stream_error_receiver) = unbounded::<StreamError>();
-        let (sink, guard) = handle_error_option(
+        // The volume set during the last session takes precedence over the config
+        let saved_state = load_player_state();
+        let initial_volume = saved_state.volume;
+        let (mut sink, mut guard) = handle_play_error_option(
             &updater,
             "player creation error",
             Player::new(
                 stream_error_sender,
                 PlayerOptions {
-                    initial_volume: CONFIG.player.initial_volume,
+                    initial_volume,
+                    crossfade_secs: CONFIG.player.crossfade_secs,
+                    seek_step_secs: CONFIG.playback.seek_step_secs,
+                    eq_bands: CONFIG.eq.bands,
+                    track_gains: CONFIG.playback.normalize.then(|| TRACK_GAINS.clone()),
                 },
             ),
         )
         .unwrap();
-        Self {
+        if let Some(device) = &CONFIG.player.output_device {
+            handle_play_error(
+                &updater,
+                "output device selection",
+                sink.set_output_device(device, &mut guard),
+            );
+        }
+        let mut player = Self {
             controls: Media::new(updater.clone(), soundaction_sender.clone()),
             soundaction_receiver,
             list_selector: ListSelector::default(),
+            scrobbler,
+            discord,
+            track_menu: None,
+            session_stats: SessionStats::default(),
+            show_stats: false,
             music_status: HashMap::new(),
             updater,
             stream_error_receiver,
@@ -66,7 +133,41 @@ impl PlayerState {
             list: Vec::new(),
             current: 0,
             rtcurrent: None,
+            preloaded: None,
+            prebuffering: false,
+            start_paused: false,
+            history: VecDeque::new(),
+            resume_at: None,
+            autoplay_after: None,
+            saved_state: (
+                SavedPlayerState {
+                    volume: initial_volume.min(100),
+                    ..saved_state
+                },
+                Instant::now(),
+            ),
+            sleep_until: None,
+            repeat_mode: saved_state.repeat_mode,
+        };
+        if CONFIG.playback.auto_start_last_playlist {
+            player.restore_last_state();
         }
+        player
+    }
+
+    /// Fills the queue with the one saved when ytermusic was closed
+    fn restore_last_state(&mut self) {
+        let Some(state) = last_playlist::read_last_state() else {
+            return;
+        };
+        info!(
+            "Restoring {} tracks of {} from the last session",
+            state.videos.len(),
+            state.playlist
+        );
+        self.current = state.current.min(state.videos.len() - 1);
+        self.start_paused = !CONFIG.playback.auto_resume;
+        SoundAction::AddVideosToQueue(state.videos).apply_sound_action(self);
     }
 
     pub fn current(&self) -> Option<&YoutubeMusicVideoRef> {
@@ -77,11 +178,61 @@ impl PlayerState {
         self.list.get(self.current.saturating_add_signed(n))
     }
 
+    /// Remembers the playing track and its position before skipping it, to undo the skip
+    pub fn remember_skip(&mut self) {
+        if self.current().is_none() || self.sink.is_finished() {
+            return;
+        }
+        self.history.push_back((
+            self.current,
+            Duration::from_secs(self.sink.elapsed().into()),
+        ));
+        if self.history.len() > HISTORY_SIZE {
+            self.history.pop_front();
+        }
+    }
+
     pub fn set_relative_current(&mut self, n: isize) {
         self.current = self.current.saturating_add_signed(n);
     }
 
+    /// Index of the track played after the current one according to the repeat mode
+    pub fn next_index(&self) -> Option<usize> {
+        match self.repeat_mode {
+            RepeatMode::One => Some(self.current),
+            RepeatMode::All if self.current + 1 >= self.list.len() => Some(0),
+            _ => Some(self.current + 1),
+        }
+        .filter(|x| *x < self.list.len())
+    }
+
+    /// The track played after the current one according to the repeat mode
+    pub fn next_track(&self) -> Option<&YoutubeMusicVideoRef> {
+        self.next_index().and_then(|x| self.list.get(x))
+    }
+
+    /// Requests the tracks played after the last one once the last track of the queue is
+    /// reached, so they are downloaded before the queue runs out
+    fn autoplay_if_needed(&mut self) {
+        if !CONFIG.playback.autoplay || self.next_index().is_some() {
+            return;
+        }
+        let Some(last) = self.list.last() else {
+            return;
+        };
+        if self.autoplay_after.as_ref() == Some(&last.video_id) {
+            return;
+        }
+        self.autoplay_after = Some(last.video_id.clone());
+        spawn_autoplay_task(
+            last.video_id.clone(),
+            self.soundaction_sender.clone(),
+            self.updater.clone(),
+        );
+    }
+
     pub fn update(&mut self) {
+        let current = self.current;
         PLAYER_RUNNING.store(self.current().is_some(), Ordering::SeqCst);
         self.update_controls();
         self.handle_stream_errors();
@@ -91,6 +242,14 @@ impl PlayerState {
         while let Ok(e) = self.soundaction_receiver.try_recv() {
             e.apply_sound_action(self);
         }
+        self.check_sleep_timer();
+        self.save_state_if_changed();
+        if let Some(scrobbler) = &self.scrobbler {
+            let _ = scrobbler.send(ScrobbleEvent::Position(self.sink.elapsed()));
+        }
+        if !self.sink.is_finished() {
+            self.session_stats.update_position(self.sink.elapsed());
+        }
         if self
             .current()
             .as_ref()
@@ -101,6 +260,10 @@ impl PlayerState {
         {
             SoundAction::Next(1).apply_sound_action(self);
         }
+        self.crossfade_if_needed();
+        self.preload_if_needed();
+        self.prebuffer_if_needed();
+        self.autoplay_if_needed();
         if self.sink.is_finished() {
             if self
                 .rtcurrent
@@ -113,7 +276,8 @@ impl PlayerState {
                 })
                 .unwrap_or(false)
             {
-                self.set_relative_current(1);
+                self.session_stats.track_left();
+                self.current = self.next_index().unwrap_or(self.current + 1);
             }
             self.handle_stream_errors();
             self.update_controls();
@@ -138,38 +302,64 @@ impl PlayerState {
             {
                 if let Some(video) = self.current().cloned() {
                     let k = CACHE_DIR.join(format!("downloads/{}.mp4", &video.video_id));
-                    if let Err(e) = self.sink.play(k.as_path(), &self.guard) {
-                        if matches!(e, PlayError::DecoderError(_)) {
-                            // Cleaning the file
-
-                            database::remove_video(&video);
-                            handle_error(
-                                &self.updater,
-                                "invalid cleaning MP4",
-                                std::fs::remove_file(k),
-                            );
-                            handle_error(
-                                &self.updater,
-                                "invalid cleaning JSON",
-                                std::fs::remove_file(
-                                    CACHE_DIR.join(format!("downloads/{}.json", &video.video_id)),
-                                ),
-                            );
-                            self.current = 0;
-                            crate::write();
-                        } else {
-                            self.updater
-                                .send(ManagerMessage::PassTo(
-                                    Screens::DeviceLost,
-                                    Box::new(ManagerMessage::Error(format!("{e}"), Box::new(None))),
-                                ))
-                                .unwrap();
+                    match self.sink.play(k.as_path(), &self.guard) {
+                        Ok(()) => {
+                            if std::mem::take(&mut self.start_paused) {
+                                self.sink.pause();
+                            }
+                            if let Some(position) = self.resume_at.take() {
+                                self.sink.seek_to(position);
+                            }
+                            self.track_started(&video);
+                        }
+                        Err(e) => {
+                            error!("Can't play {}: {e}", video.video_id);
+                            if matches!(e, PlayError::DecoderError(_)) {
+                                // Cleaning the file
+                                self.updater
+                                    .send(ManagerMessage::Notify(
+                                        format!("{}: {}", video.title, e.user_message()),
+                                        NOTIFICATION_DURATION,
+                                    ))
+                                    .unwrap();
+
+                                SoundAction::DeleteVideoUnary(video.clone())
+                                    .apply_sound_action(self);
+                                handle_error(
+                                    &self.updater,
+                                    "invalid cleaning MP4",
+                                    std::fs::remove_file(k),
+                                );
+                                handle_error(
+                                    &self.updater,
+                                    "invalid cleaning JSON",
+                                    std::fs::remove_file(
+                                        CACHE_DIR
+                                            .join(format!("downloads/{}.json", &video.video_id)),
+                                    ),
+                                );
+                                crate::write();
+                            } else {
+                                self.updater
+                                    .send(ManagerMessage::PassTo(
+                                        Screens::DeviceLost,
+                                        Box::new(ManagerMessage::Error(
+                                            e.user_message().to_owned(),
+                                            Box::new(None),
+                                        )),
+                                    ))
+                                    .unwrap();
+                            }
                         }
                     }
                 }
             }
         }
         self.rtcurrent = self.current().cloned();
+        // The actions remember their own changes, this catches the tracks ending
+        if self.current != current {
+            last_playlist::remember_current(self.current);
+        }
         let to_download = self
             .list
             .iter()
@@ -181,14 +371,175 @@ impl PlayerState {
             .take(12)
             .cloned()
             .collect::<VecDeque<_>>();
-        *DOWNLOAD_LIST.lock().unwrap() = to_download;
+        set_download_list(to_download);
+    }
+
+    /// Saves the volume and the repeat mode when they changed, at most once per
+    /// `STATE_SAVE_INTERVAL`
+    fn save_state_if_changed(&mut self) {
+        let state = SavedPlayerState {
+            volume: self.sink.volume_percent(),
+            repeat_mode: self.repeat_mode,
+        };
+        let (saved, saved_at) = self.saved_state;
+        if state == saved {
+            return;
+        }
+        if saved_at.elapsed() >= STATE_SAVE_INTERVAL {
+            save_player_state(&state);
+            self.saved_state = (state, Instant::now());
+        } else {
+            // Written on shutdown if it is quit before the next save
+            set_unsaved_player_state(state);
+        }
+    }
+
+    /// Pauses the playback once the sleep timer expires
+    fn check_sleep_timer(&mut self) {
+        if self.sleep_until.is_some_and(|x| x <= Instant::now()) {
+            self.sleep_until = None;
+            if !self.sink.is_paused() {
+                self.sink.pause();
+            }
+            self.updater
+                .send(ManagerMessage::Notify(
+                    "Sleep timer: playback paused".to_owned(),
+                    NOTIFICATION_DURATION,
+                ))
+                .unwrap();
+        }
+    }
+
+    /// Keeps the track preloaded in the sink in sync with the queue.
+    /// Moves to the next track once the preloaded one starts and preloads the following one
+    /// when the current track is almost finished.
+    fn preload_if_needed(&mut self) {
+        if self.preloaded.is_some() && self.sink.advance_to_preloaded() {
+            self.current = self.next_index().unwrap_or(self.current + 1);
+            self.rtcurrent = self.current().cloned();
+            if let Some(video) = self.preloaded.take() {
+                self.track_started(&video);
+            }
+        }
+        if !self.sink.has_preloaded() {
+            self.preloaded = None;
+        }
+        if self.preloaded.is_some() && self.preloaded.as_ref() != self.next_track() {
+            // The queue changed since the track was preloaded
+            self.sink.cancel_preload();
+            self.preloaded = None;
+        }
+        if !CONFIG.playback.gapless
+            || self.preloaded.is_some()
+            || self.sink.crossfade_secs() > 0
+            || self.sink.is_finished()
+            || self.sink.percentage() < 0.9
+        {
+            return;
+        }
+        let Some(next) = self
+            .next_track()
+            .filter(|x| {
+                self.music_status.get(&x.video_id) == Some(&MusicDownloadStatus::Downloaded)
+            })
+            .cloned()
+        else {
+            return;
+        };
+        let path = CACHE_DIR.join(format!("downloads/{}.mp4", &next.video_id));
+        // On failure the track ends normally and the error is reported when the next one is played
+        if self.sink.preload_next(path.as_path()).is_ok() {
+            self.preloaded = Some(next);
+        }
+    }
+
+    /// Downloads the next track once the current one reaches `prebuffer_threshold` so it can be
+    /// opened without delay
+    fn prebuffer_if_needed(&mut self) {
+        if self.prebuffering
+            || self.sink.is_finished()
+            || self.sink.percentage() < CONFIG.playback.prebuffer_threshold
+        {
+            return;
+        }
+        let Some(next) = self.next_track().cloned() else {
+            return;
+        };
+        if is_downloaded(&next) {
+            return;
+        }
+        self.prebuffering = true;
+        download::start_task_unary(self.soundaction_sender.clone(), next);
+    }
+
+    /// Called when a track finished downloading, fetches the one after the pre-cached track
+    pub fn prebuffer_completed(&mut self, video_id: &str) {
+        if !self.prebuffering || self.next_track().map(|x| x.video_id.as_str()) != Some(video_id) {
+            return;
+        }
+        self.prebuffering = false;
+        let after = self
+            .next_index()
+            .and_then(|x| self.list.get(x + 1))
+            .filter(|x| !is_downloaded(x))
+            .cloned();
+        if let Some(after) = after {
+            download::start_task_unary(self.soundaction_sender.clone(), after);
+        }
+    }
+
+    /// Starts the next track while the current one fades out once the crossfade window is reached
+    fn crossfade_if_needed(&mut self) {
+        let crossfade_fraction = self.sink.crossfade_fraction();
+        if crossfade_fraction <= 0.0
+            || self.sink.is_finished()
+            || self.sink.is_paused()
+            || self.sink.percentage() < 1.0 - crossfade_fraction
+        {
+            return;
+        }
+        let Some(next) = self
+            .next_track()
+            .filter(|x| {
+                self.music_status.get(&x.video_id) == Some(&MusicDownloadStatus::Downloaded)
+            })
+            .cloned()
+        else {
+            return;
+        };
+        let path = CACHE_DIR.join(format!("downloads/{}.mp4", &next.video_id));
+        // On failure the track ends normally and the error is reported when the next one is played
+        if self.sink.crossfade_to(path.as_path(), &self.guard).is_ok() {
+            self.track_started(&next);
+            self.current = self.next_index().unwrap_or(self.current + 1);
+            self.rtcurrent = self.current().cloned();
+        }
     }
 
     fn handle_stream_errors(&self) {
         while let Ok(e) = self.stream_error_receiver.try_recv() {
-            handle_error(&self.updater, "audio device stream error", Err(e));
+            error!("audio device stream error: {e}");
+            handle_error(
+                &self.updater,
+                "audio device stream error",
+                Err(e.user_message()),
+            );
+        }
+    }
+    /// Records the play of a track that just started
+    fn track_started(&mut self, video: &YoutubeMusicVideoRef) {
+        self.prebuffering = false;
+        database::append_play_event(&video.video_id);
+        self.session_stats.track_started(&video.author);
+        if let Some(scrobbler) = &self.scrobbler {
+            let _ = scrobbler.send(ScrobbleEvent::Started(video.clone()));
+        }
+        if let Some(discord) = &self.discord {
+            let _ = discord.send(video.clone());
         }
+        notify::notify_track_started(video);
     }
+
     fn update_controls(&mut self) {
         let current = self.current().cloned();
         let result = self
@@ -199,7 +550,68 @@ impl PlayerState {
     }
 }
 
-pub fn player_system(updater: Sender<ManagerMessage>) -> (Sender<SoundAction>, PlayerState) {
+fn is_downloaded(video: &YoutubeMusicVideoRef) -> bool {
+    DATABASE
+        .read()
+        .unwrap()
+        .iter()
+        .any(|x| x.video_id == video.video_id)
+}
+
+pub fn player_system(
+    updater: Sender<ManagerMessage>,
+    scrobbler: Option<Sender<ScrobbleEvent>>,
+    discord: Option<Sender<YoutubeMusicVideoRef>>,
+) -> (Sender<SoundAction>, PlayerState) {
     let (tx, rx) = flume::unbounded::<SoundAction>();
-    (tx.clone(), PlayerState::new(tx, rx, updater))
+    (
+        tx.clone(),
+        PlayerState::new(tx, rx, updater, scrobbler, discord),
+    )
+}
+
+/// Removes the matching songs from the queue, returns the new index of the current song and
+/// whether it was removed. The song following a removed current song becomes the current one.
+pub fn remove_from_queue<T>(
+    list: &mut Vec<T>,
+    current: usize,
+    removed: impl Fn(&T) -> bool,
+) -> (usize, bool) {
+    let mut new_current = current;
+    let mut current_removed = false;
+    let mut index = 0;
+    list.retain(|x| {
+        let keep = !removed(x);
+        if !keep {
+            match index.cmp(&current) {
+                std::cmp::Ordering::Less => new_current -= 1,
+                std::cmp::Ordering::Equal => current_removed = true,
+                std::cmp::Ordering::Greater => {}
+            }
+        }
+        index += 1;
+        keep
+    });
+    (new_current, current_removed)
+}
+
+#[test]
+fn remove_from_queue_test() {
+    let queue = || vec!["a", "b", "c", "d"];
+    // Before the current song
+    let mut list = queue();
+    assert_eq!(remove_from_queue(&mut list, 2, |x| *x == "a"), (1, false));
+    assert_eq!(list[1], "c");
+    // The current song, the next one is played
+    let mut list = queue();
+    assert_eq!(remove_from_queue(&mut list, 2, |x| *x == "c"), (2, true));
+    assert_eq!(list[2], "d");
+    // After the current song
+    let mut list = queue();
+    assert_eq!(remove_from_queue(&mut list, 2, |x| *x == "d"), (2, false));
+    assert_eq!(list[2], "c");
+    // The last song is playing, the queue ends
+    let mut list = queue();
+    assert_eq!(remove_from_queue(&mut list, 3, |x| *x == "d"), (3, true));
+    assert_eq!(list.get(3), None);
 }
@@ -0,0 +1,448 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use flume::{Receiver, Sender};
+use log::{info, warn};
+use rusty_ytdl::reqwest::Client;
+use serde_json::{json, Value};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{
+    consts::{CACHE_DIR, CONFIG},
+    run_service,
+};
+
+const LASTFM_API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const LISTENBRAINZ_API_URL: &str = "https://api.listenbrainz.org/1/submit-listens";
+/// A track is scrobbled once played for this long and for half of its duration
+const MIN_PLAYED_SECS: u32 = 30;
+/// How often the failed scrobbles are checked when no event is received
+const TICK: Duration = Duration::from_secs(10);
+const MIN_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+/// Error code of Last.fm for an invalid or revoked session key
+const INVALID_SESSION: u64 = 9;
+/// Error codes of Last.fm worth retrying: operation failed, invalid session, service offline,
+/// temporarily unavailable and rate limit exceeded
+const LASTFM_TRANSIENT_ERRORS: [u64; 5] = [8, INVALID_SESSION, 11, 16, 29];
+
+/// What the player tells the scrobbler
+pub enum ScrobbleEvent {
+    /// A track started playing
+    Started(YoutubeMusicVideoRef),
+    /// The position in the current track, in seconds
+    Position(u32),
+}
+
+/// Starts the scrobbler when Last.fm or ListenBrainz is configured, the player sends its
+/// events to the returned channel
+pub fn spawn_scrobbler() -> Option<Sender<ScrobbleEvent>> {
+    let config = &CONFIG.scrobbler;
+    let lastfm = (|| {
+        Some(LastFm::new(Credentials {
+            api_key: config.lastfm_api_key.clone()?,
+            api_secret: config.lastfm_api_secret.clone()?,
+            username: config.lastfm_username.clone()?,
+            password: config.lastfm_password.clone()?,
+        }))
+    })();
+    let listenbrainz = config.listenbrainz_token.clone().map(|token| ListenBrainz {
+        token,
+        retry: Retry::default(),
+    });
+    if lastfm.is_none() && listenbrainz.is_none() {
+        return None;
+    }
+    let (sender, receiver) = flume::unbounded();
+    run_service(async move {
+        info!(
+            "Scrobbler on (Last.fm: {}, ListenBrainz: {})",
+            lastfm.is_some(),
+            listenbrainz.is_some()
+        );
+        let client = Client::builder()
+            .timeout(Duration::from_secs(CONFIG.network.request_timeout_secs))
+            .build()
+            .unwrap_or_default();
+        Scrobbler {
+            client,
+            playing: None,
+            lastfm,
+            listenbrainz,
+        }
+        .run(receiver)
+        .await;
+    });
+    Some(sender)
+}
+
+#[derive(Clone)]
+struct Track {
+    artist: String,
+    title: String,
+    album: String,
+    duration: Option<u32>,
+    /// When the track started, in seconds since the epoch
+    timestamp: u64,
+}
+
+impl Track {
+    fn lastfm_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = vec![
+            ("artist", self.artist.clone()),
+            ("track", self.title.clone()),
+        ];
+        if !self.album.is_empty() {
+            params.push(("album", self.album.clone()));
+        }
+        if let Some(duration) = self.duration {
+            params.push(("duration", duration.to_string()));
+        }
+        params
+    }
+
+    /// The listen of the track, `listened_at` is only set once it was listened to
+    fn listenbrainz_payload(&self, listened: bool) -> Value {
+        let mut metadata = json!({
+            "artist_name": self.artist,
+            "track_name": self.title,
+        });
+        if !self.album.is_empty() {
+            metadata["release_name"] = json!(self.album);
+        }
+        if let Some(duration) = self.duration {
+            metadata["additional_info"] = json!({ "duration_ms": u64::from(duration) * 1000 });
+        }
+        let mut listen = json!({ "track_metadata": metadata });
+        if listened {
+            listen["listened_at"] = json!(self.timestamp);
+        }
+        listen
+    }
+}
+
+enum ApiError {
+    Request(String),
+    Api(u64, String),
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Request(e) => write!(f, "{e}"),
+            Self::Api(code, message) => write!(f, "error {code}: {message}"),
+        }
+    }
+}
+
+/// The scrobbles a service hasn't received yet and when to send them again
+struct Retry {
+    /// The oldest first
+    pending: VecDeque<Track>,
+    retry_at: Option<Instant>,
+    backoff: Duration,
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            pending: VecDeque::new(),
+            retry_at: None,
+            backoff: MIN_BACKOFF,
+        }
+    }
+}
+
+impl Retry {
+    /// The next track to send unless waiting after a failure
+    fn next(&self) -> Option<&Track> {
+        if self.retry_at.is_some_and(|x| x > Instant::now()) {
+            return None;
+        }
+        self.pending.front()
+    }
+
+    fn succeeded(&mut self) {
+        self.pending.pop_front();
+        self.retry_at = None;
+        self.backoff = MIN_BACKOFF;
+    }
+
+    /// Drops the track the service will never accept, so it doesn't block the next ones
+    fn rejected(&mut self, service: &str, error: ApiError) {
+        warn!("{service} rejected a scrobble, dropping it: {error}");
+        self.succeeded();
+    }
+
+    fn failed(&mut self, service: &str, error: ApiError) {
+        warn!(
+            "Can't scrobble to {service}, retrying in {}s: {error}",
+            self.backoff.as_secs()
+        );
+        self.retry_at = Some(Instant::now() + self.backoff);
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+struct Scrobbler {
+    client: Client,
+    /// The current track, how long it was played and whether it was scrobbled
+    playing: Option<(Track, u32, bool)>,
+    lastfm: Option<LastFm>,
+    listenbrainz: Option<ListenBrainz>,
+}
+
+impl Scrobbler {
+    async fn run(mut self, receiver: Receiver<ScrobbleEvent>) {
+        loop {
+            match tokio::time::timeout(TICK, receiver.recv_async()).await {
+                Ok(Ok(event)) => self.handle(event).await,
+                // The player stopped
+                Ok(Err(_)) => break,
+                Err(_) => {}
+            }
+            if let Some(lastfm) = &mut self.lastfm {
+                lastfm.send_pending(&self.client).await;
+            }
+            if let Some(listenbrainz) = &mut self.listenbrainz {
+                listenbrainz.send_pending(&self.client).await;
+            }
+        }
+    }
+
+    async fn handle(&mut self, event: ScrobbleEvent) {
+        match event {
+            ScrobbleEvent::Started(video) => {
+                let track = Track {
+                    artist: video.author,
+                    title: video.title,
+                    album: video.album,
+                    duration: video.duration_seconds,
+                    timestamp: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs(),
+                };
+                if let Some(lastfm) = &mut self.lastfm {
+                    lastfm.now_playing(&self.client, &track).await;
+                }
+                if let Some(listenbrainz) = &self.listenbrainz {
+                    listenbrainz.now_playing(&self.client, &track).await;
+                }
+                self.playing = Some((track, 0, false));
+            }
+            ScrobbleEvent::Position(secs) => {
+                let Some((track, played, scrobbled)) = &mut self.playing else {
+                    return;
+                };
+                *played = (*played).max(secs);
+                if !*scrobbled
+                    && *played > MIN_PLAYED_SECS
+                    && track.duration.is_none_or(|duration| *played * 2 > duration)
+                {
+                    *scrobbled = true;
+                    if let Some(lastfm) = &mut self.lastfm {
+                        lastfm.retry.pending.push_back(track.clone());
+                    }
+                    if let Some(listenbrainz) = &mut self.listenbrainz {
+                        listenbrainz.retry.pending.push_back(track.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+struct Credentials {
+    api_key: String,
+    api_secret: String,
+    username: String,
+    password: String,
+}
+
+/// The Last.fm Scrobbler API 2.0
+struct LastFm {
+    credentials: Credentials,
+    session: Option<String>,
+    retry: Retry,
+}
+
+impl LastFm {
+    fn new(credentials: Credentials) -> Self {
+        Self {
+            credentials,
+            session: std::fs::read_to_string(CACHE_DIR.join("lastfm_session"))
+                .ok()
+                .map(|x| x.trim().to_owned()),
+            retry: Retry::default(),
+        }
+    }
+
+    async fn now_playing(&mut self, client: &Client, track: &Track) {
+        let mut params = track.lastfm_params();
+        params.push(("method", "track.updateNowPlaying".to_owned()));
+        if let Err(e) = self.call_authenticated(client, params).await {
+            warn!("Can't update the Last.fm now playing track: {e}");
+        }
+    }
+
+    async fn send_pending(&mut self, client: &Client) {
+        while let Some(track) = self.retry.next() {
+            let mut params = track.lastfm_params();
+            params.push(("timestamp", track.timestamp.to_string()));
+            params.push(("method", "track.scrobble".to_owned()));
+            match self.call_authenticated(client, params).await {
+                Ok(_) => self.retry.succeeded(),
+                Err(ApiError::Api(code, message)) if !LASTFM_TRANSIENT_ERRORS.contains(&code) => {
+                    self.retry.rejected("Last.fm", ApiError::Api(code, message))
+                }
+                Err(e) => return self.retry.failed("Last.fm", e),
+            }
+        }
+    }
+
+    /// Calls a method needing a session, which is created and cached if needed
+    async fn call_authenticated(
+        &mut self,
+        client: &Client,
+        mut params: Vec<(&'static str, String)>,
+    ) -> Result<Value, ApiError> {
+        let session = match &self.session {
+            Some(session) => session.clone(),
+            None => {
+                let session = self.create_session(client).await?;
+                self.session = Some(session.clone());
+                session
+            }
+        };
+        params.push(("sk", session));
+        let result = self.call(client, params).await;
+        if let Err(ApiError::Api(INVALID_SESSION, _)) = result {
+            self.session = None;
+            let _ = std::fs::remove_file(CACHE_DIR.join("lastfm_session"));
+        }
+        result
+    }
+
+    async fn create_session(&self, client: &Client) -> Result<String, ApiError> {
+        let response = self
+            .call(
+                client,
+                vec![
+                    ("method", "auth.getMobileSession".to_owned()),
+                    ("username", self.credentials.username.clone()),
+                    ("password", self.credentials.password.clone()),
+                ],
+            )
+            .await?;
+        let session = response["session"]["key"]
+            .as_str()
+            .ok_or_else(|| ApiError::Request("No session key in the response".to_owned()))?
+            .to_owned();
+        if let Err(e) = std::fs::write(CACHE_DIR.join("lastfm_session"), &session) {
+            warn!("Can't cache the Last.fm session: {e}");
+        }
+        info!("Created a Last.fm session");
+        Ok(session)
+    }
+
+    /// Calls a signed method of the API
+    async fn call(
+        &self,
+        client: &Client,
+        mut params: Vec<(&'static str, String)>,
+    ) -> Result<Value, ApiError> {
+        params.push(("api_key", self.credentials.api_key.clone()));
+        params.sort_by_key(|(key, _)| *key);
+        let signature = params
+            .iter()
+            .map(|(key, value)| format!("{key}{value}"))
+            .collect::<String>()
+            + &self.credentials.api_secret;
+        params.push(("api_sig", format!("{:x}", md5::compute(signature))));
+        params.push(("format", "json".to_owned()));
+        let response = async {
+            client
+                .post(LASTFM_API_URL)
+                .form(&params)
+                .send()
+                .await?
+                .text()
+                .await
+        }
+        .await
+        .map_err(|e| ApiError::Request(e.to_string()))?;
+        let value: Value =
+            serde_json::from_str(&response).map_err(|e| ApiError::Request(e.to_string()))?;
+        match value["error"].as_u64() {
+            Some(code) => Err(ApiError::Api(
+                code,
+                value["message"].as_str().unwrap_or_default().to_owned(),
+            )),
+            None => Ok(value),
+        }
+    }
+}
+
+/// The ListenBrainz API, authenticated with a user token
+struct ListenBrainz {
+    token: String,
+    retry: Retry,
+}
+
+impl ListenBrainz {
+    async fn now_playing(&self, client: &Client, track: &Track) {
+        if let Err(e) = self
+            .submit(client, "playing_now", track.listenbrainz_payload(false))
+            .await
+        {
+            warn!("Can't update the ListenBrainz now playing track: {e}");
+        }
+    }
+
+    async fn send_pending(&mut self, client: &Client) {
+        while let Some(track) = self.retry.next() {
+            let listen = track.listenbrainz_payload(true);
+            match self.submit(client, "single", listen).await {
+                Ok(()) => self.retry.succeeded(),
+                // A client error other than the rate limit won't be fixed by retrying
+                Err(ApiError::Api(status, message))
+                    if (400..500).contains(&status) && status != 429 =>
+                {
+                    self.retry
+                        .rejected("ListenBrainz", ApiError::Api(status, message))
+                }
+                Err(e) => return self.retry.failed("ListenBrainz", e),
+            }
+        }
+    }
+
+    async fn submit(
+        &self,
+        client: &Client,
+        listen_type: &str,
+        listen: Value,
+    ) -> Result<(), ApiError> {
+        let body = json!({
+            "listen_type": listen_type,
+            "payload": [listen],
+        });
+        let response = client
+            .post(LISTENBRAINZ_API_URL)
+            .header("Authorization", format!("Token {}", self.token))
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await
+            .map_err(|e| ApiError::Request(e.to_string()))?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        let message = response.text().await.unwrap_or_default();
+        Err(ApiError::Api(u64::from(status.as_u16()), message))
+    }
+}
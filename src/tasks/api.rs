@@ -1,16 +1,25 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
 
+use arc_swap::ArcSwapOption;
 use flume::Sender;
-use log::{error, info};
+use log::{error, info, warn};
 use once_cell::sync::Lazy;
 use tokio::task::JoinSet;
-use ytpapi2::{Endpoint, YoutubeMusicInstance, YoutubeMusicPlaylistRef};
+use ytpapi2::{
+    AlbumDetails, ArtistRef, Endpoint, LikeStatus, YoutubeMusicInstance, YoutubeMusicPlaylistRef,
+};
 
 use crate::{
+    connect_api,
     consts::CONFIG,
-    get_header_file, run_service,
-    structures::performance,
-    term::{ManagerMessage, Screens},
+    database, get_header_file, reload_auto_cookies, run_service,
+    structures::{performance, sound_action::SoundAction},
+    term::{artist_browser::Release, ManagerMessage, Screens, NOTIFICATION_DURATION},
+    DATABASE,
 };
 
 pub fn get_text_cookies_expired_or_invalid() -> String {
@@ -21,20 +30,107 @@ pub fn get_text_cookies_expired_or_invalid() -> String {
     )
 }
 
+/// The connection to YouTube Music, replaced when the header file changes
+pub static API: ArcSwapOption<YoutubeMusicInstance> = ArcSwapOption::const_empty();
+
+/// The playlists of the library, offered by the track menu to save a track to YouTube Music
+pub static LIBRARY_PLAYLISTS: Lazy<Mutex<Vec<YoutubeMusicPlaylistRef>>> =
+    Lazy::new(|| Mutex::new(vec![]));
+
+/// The ratings changed during the session, they override the ones of the loaded tracks
+pub static LIKE_STATUSES: Lazy<Mutex<HashMap<String, LikeStatus>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Makes the requests refused at the same time wait for a single reconnection
+static REAUTHENTICATION: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Reconnects with the cookies of the browser when `--with-auto-cookies` is used
+async fn reauthenticate(expired: &Arc<YoutubeMusicInstance>) -> Option<Arc<YoutubeMusicInstance>> {
+    let _guard = REAUTHENTICATION.lock().await;
+    if let Some(api) = API.load_full().filter(|x| !Arc::ptr_eq(x, expired)) {
+        // Already reconnected by another request
+        return Some(api);
+    }
+    if !tokio::task::spawn_blocking(reload_auto_cookies)
+        .await
+        .unwrap_or(false)
+    {
+        return None;
+    }
+    match connect_api().await {
+        Ok(api) => {
+            info!("Reconnected with the cookies of the browser");
+            let api = Arc::new(api);
+            API.store(Some(api.clone()));
+            Some(api)
+        }
+        Err(e) => {
+            error!("Can't reconnect: {e:?}");
+            None
+        }
+    }
+}
+
+/// Calls YouTube Music, and once more with fresh cookies if they expired
+async fn call_api<T, F, Fut>(
+    api: Arc<YoutubeMusicInstance>,
+    updater_s: &Sender<ManagerMessage>,
+    call: F,
+) -> ytpapi2::Result<T>
+where
+    F: Fn(Arc<YoutubeMusicInstance>) -> Fut,
+    Fut: Future<Output = ytpapi2::Result<T>>,
+{
+    let result = match call(api.clone()).await {
+        Err(e) if e.is_unauthorized() => {
+            warn!("The authentication expired: {e:?}");
+            match reauthenticate(&api).await {
+                Some(api) => call(api).await,
+                None => Err(e),
+            }
+        }
+        result => result,
+    };
+    if result.as_ref().is_err_and(|e| e.is_unauthorized()) {
+        let _ = updater_s.send(ManagerMessage::AuthExpired);
+    }
+    if let Err(e) = &result {
+        report_timeout(e, updater_s);
+    }
+    result
+}
+
+/// Tells how to wait longer for YouTube Music when a request timed out
+fn report_timeout(e: &ytpapi2::YoutubeMusicError, updater_s: &Sender<ManagerMessage>) {
+    if !e.is_timeout() {
+        return;
+    }
+    let message = format!(
+        "YouTube Music didn't answer within {}s, the timeout can be increased with \
+        `request_timeout_secs` in config.toml",
+        CONFIG.network.request_timeout_secs
+    );
+    warn!("{message}");
+    let _ = updater_s.send(ManagerMessage::Notify(message, NOTIFICATION_DURATION));
+}
+
 pub fn spawn_api_task(updater_s: Sender<ManagerMessage>) {
     run_service(async move {
         info!("API task on");
         let guard = performance::guard("API task");
-        let client =
-            YoutubeMusicInstance::from_header_file(get_header_file().unwrap().1.as_path()).await;
+        let client = connect_api().await;
         match client {
             Ok(api) => {
                 let api = Arc::new(api);
+                API.store(Some(api.clone()));
                 let mut set = JoinSet::new();
                 let api_ = api.clone();
                 let updater_s_ = updater_s.clone();
                 set.spawn(async move {
-                    let search_results = api_.get_home(2).await;
+                    let search_results = call_api(api_.clone(), &updater_s_, |api| async move {
+                        api.get_home(2).await
+                    })
+                    .await;
                     match search_results {
                         Ok(e) => {
                             for playlist in e.playlists {
@@ -53,7 +149,10 @@ pub fn spawn_api_task(updater_s: Sender<ManagerMessage>) {
                 let api_ = api.clone();
                 let updater_s_ = updater_s.clone();
                 set.spawn(async move {
-                    let search_results = api_.get_library(&Endpoint::MusicLikedPlaylists, 2).await;
+                    let search_results = call_api(api_.clone(), &updater_s_, |api| async move {
+                        api.get_library(&Endpoint::MusicLikedPlaylists, 2).await
+                    })
+                    .await;
                     match search_results {
                         Ok(e) => {
                             for playlist in e {
@@ -72,9 +171,17 @@ pub fn spawn_api_task(updater_s: Sender<ManagerMessage>) {
                 let api_ = api.clone();
                 let updater_s_ = updater_s.clone();
                 set.spawn(async move {
-                    let search_results = api_.get_library(&Endpoint::MusicLibraryLanding, 2).await;
+                    let search_results = call_api(api_.clone(), &updater_s_, |api| async move {
+                        api.get_library(&Endpoint::MusicLibraryLanding, 2).await
+                    })
+                    .await;
                     match search_results {
                         Ok(e) => {
+                            *LIBRARY_PLAYLISTS.lock().unwrap() = e
+                                .iter()
+                                .filter(|x| x.browse_id.starts_with("VL"))
+                                .cloned()
+                                .collect();
                             for playlist in e {
                                 spawn_browse_playlist_task(
                                     playlist.clone(),
@@ -113,8 +220,21 @@ pub fn spawn_api_task(updater_s: Sender<ManagerMessage>) {
                         )
                         .unwrap();
                 }
+                ytpapi2::YoutubeMusicError::InvalidProxy(proxy) => {
+                    error!("Invalid proxy {proxy}");
+                    updater_s
+                        .send(
+                            ManagerMessage::Error(
+                                format!("Invalid `proxy_url` in the `[network]` section of the config: {proxy}"),
+                                Box::new(Some(ManagerMessage::Quit)),
+                            )
+                            .pass_to(Screens::DeviceLost),
+                        )
+                        .unwrap();
+                }
                 e => {
                     error!("{e:?}");
+                    report_timeout(e, &updater_s);
                 }
             },
         }
@@ -122,6 +242,340 @@ pub fn spawn_api_task(updater_s: Sender<ManagerMessage>) {
     });
 }
 
+/// A page of YouTube Music listing playlists or radios, opened from the playlist chooser
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiscoverPage {
+    Charts,
+    NewReleases,
+    Radio,
+}
+
+impl DiscoverPage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Charts => "Charts",
+            Self::NewReleases => "New Releases",
+            Self::Radio => "Radio stations",
+        }
+    }
+}
+
+/// The number of downloaded songs whose radio is listed with the radio stations
+const SEEDED_RADIOS: usize = 3;
+
+/// Adds the playlists of the charts or of the new releases, or the radio stations, to the chooser
+pub fn spawn_discover_task(page: DiscoverPage, updater_s: Sender<ManagerMessage>) {
+    run_service(async move {
+        let Some(api) = API.load_full() else {
+            let _ = updater_s.send(ManagerMessage::Notify(
+                "Not connected to YouTube Music".to_owned(),
+                NOTIFICATION_DURATION,
+            ));
+            return;
+        };
+        if page == DiscoverPage::Radio {
+            spawn_radio_tasks(api, updater_s);
+            return;
+        }
+        let playlists = call_api(api.clone(), &updater_s, |api| async move {
+            if page == DiscoverPage::Charts {
+                api.get_trending().await
+            } else {
+                api.get_new_releases().await
+            }
+        })
+        .await;
+        match playlists {
+            Ok(playlists) => {
+                for playlist in playlists {
+                    spawn_browse_playlist_task(playlist, api.clone(), updater_s.clone());
+                }
+            }
+            Err(e) => {
+                error!("{page:?} -> {e:?}");
+                let _ = updater_s.send(ManagerMessage::Notify(
+                    format!("Can't load the {}", page.name()),
+                    NOTIFICATION_DURATION,
+                ));
+            }
+        }
+    });
+}
+
+/// Adds the configured radio stations and the radios of the last downloaded songs to the chooser
+fn spawn_radio_tasks(api: Arc<YoutubeMusicInstance>, updater_s: Sender<ManagerMessage>) {
+    let mut stations = CONFIG
+        .playlist
+        .radio_stations
+        .iter()
+        .map(|id| (format!("Radio {id}"), id.clone()))
+        .collect::<Vec<_>>();
+    stations.extend(
+        DATABASE
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .take(SEEDED_RADIOS)
+            .map(|video| {
+                (
+                    format!("Radio: {}", video.title),
+                    format!("RDAMVM{}", video.video_id),
+                )
+            }),
+    );
+    for (name, radio_id) in stations {
+        let (api, updater_s) = (api.clone(), updater_s.clone());
+        run_service(async move {
+            let radio_id_ = &radio_id;
+            let videos = call_api(api, &updater_s, |api| async move {
+                api.get_radio(radio_id_, 2).await
+            })
+            .await;
+            match videos {
+                Ok(videos) => {
+                    let _ = updater_s.send(
+                        ManagerMessage::AddElementToChooser((name, videos))
+                            .pass_to(Screens::Playlist),
+                    );
+                }
+                Err(e) => error!("Radio {radio_id} -> {e:?}"),
+            }
+        });
+    }
+}
+
+/// Sends the top songs, then the albums and the singles of an artist to the artist browser
+pub fn spawn_artist_task(channel_id: String, updater_s: Sender<ManagerMessage>) {
+    run_service(async move {
+        let Some(api) = API.load_full() else {
+            let _ = updater_s.send(ManagerMessage::Notify(
+                "Not connected to YouTube Music".to_owned(),
+                NOTIFICATION_DURATION,
+            ));
+            return;
+        };
+        let channel_id_ = &channel_id;
+        let page = match call_api(api.clone(), &updater_s, |api| async move {
+            api.get_artist(channel_id_).await
+        })
+        .await
+        {
+            Ok(page) => page,
+            Err(e) => {
+                error!("Artist {channel_id} -> {e:?}");
+                let _ = updater_s.send(ManagerMessage::Notify(
+                    "Can't load the artist".to_owned(),
+                    NOTIFICATION_DURATION,
+                ));
+                return;
+            }
+        };
+        let send = |updater_s: &Sender<ManagerMessage>, release, details| {
+            let _ = updater_s.send(
+                ManagerMessage::AddArtistRelease(channel_id.clone(), release, details)
+                    .pass_to(Screens::ArtistBrowser),
+            );
+        };
+        if !page.top_songs.is_empty() {
+            let details = AlbumDetails {
+                browse_id: String::new(),
+                title: "Top songs".to_owned(),
+                artist: page.name.clone(),
+                year: None,
+                label: None,
+                tracks: page.top_songs,
+            };
+            send(&updater_s, Release::TopSongs, details);
+        }
+        let mut albums = JoinSet::new();
+        let releases = (page.albums.into_iter().map(|x| (Release::Album, x)))
+            .chain(page.singles.into_iter().map(|x| (Release::Single, x)));
+        for (release, album) in releases {
+            let (api, updater_s) = (api.clone(), updater_s.clone());
+            albums.spawn(async move {
+                let browse_id = &album.browse_id;
+                let details = call_api(api, &updater_s, |api| async move {
+                    api.get_album(browse_id, 0).await
+                })
+                .await;
+                (release, album, details)
+            });
+        }
+        while let Some(result) = albums.join_next().await {
+            match result {
+                Ok((release, _, Ok(details))) if !details.tracks.is_empty() => {
+                    send(&updater_s, release, details)
+                }
+                Ok((_, _, Ok(_))) => {}
+                Ok((_, album, Err(e))) => error!("Album {} -> {e:?}", album.browse_id),
+                Err(e) => error!("{e:?}"),
+            }
+        }
+    });
+}
+
+/// Sends the tracks and the metadata of an album to the album view
+pub fn spawn_album_task(browse_id: String, updater_s: Sender<ManagerMessage>) {
+    run_service(async move {
+        let Some(api) = API.load_full() else {
+            let _ = updater_s.send(ManagerMessage::Notify(
+                "Not connected to YouTube Music".to_owned(),
+                NOTIFICATION_DURATION,
+            ));
+            return;
+        };
+        let browse_id_ = &browse_id;
+        let details = call_api(api, &updater_s, |api| async move {
+            api.get_album(browse_id_, 0).await
+        })
+        .await;
+        match details {
+            Ok(details) => {
+                let _ = updater_s
+                    .send(ManagerMessage::AlbumLoaded(details).pass_to(Screens::AlbumView));
+            }
+            Err(e) => {
+                error!("Album {browse_id} -> {e:?}");
+                let _ = updater_s.send(ManagerMessage::Notify(
+                    "Can't load the album".to_owned(),
+                    NOTIFICATION_DURATION,
+                ));
+            }
+        }
+    });
+}
+
+/// Replaces the queue with the radio of an artist
+pub fn spawn_artist_radio_task(
+    artist: ArtistRef,
+    action_sender: Sender<SoundAction>,
+    updater_s: Sender<ManagerMessage>,
+) {
+    run_service(async move {
+        let Some(api) = API.load_full() else {
+            let _ = updater_s.send(ManagerMessage::Notify(
+                "Not connected to YouTube Music".to_owned(),
+                NOTIFICATION_DURATION,
+            ));
+            return;
+        };
+        let channel_id = &artist.channel_id;
+        let videos = call_api(api, &updater_s, |api| async move {
+            api.get_artist_radio(channel_id, 2).await
+        })
+        .await;
+        let message = match videos {
+            Ok(videos) => {
+                let _ = action_sender.send(SoundAction::ReplaceQueue(videos));
+                format!("Playing the radio of {}", artist.name)
+            }
+            Err(e) => {
+                error!("Artist radio {channel_id} -> {e:?}");
+                format!("No radio for {}", artist.name)
+            }
+        };
+        let _ = updater_s.send(ManagerMessage::Notify(message, NOTIFICATION_DURATION));
+    });
+}
+
+/// Adds the tracks YouTube Music plays after a track to the queue
+pub fn spawn_autoplay_task(
+    video_id: String,
+    action_sender: Sender<SoundAction>,
+    updater_s: Sender<ManagerMessage>,
+) {
+    run_service(async move {
+        let Some(api) = API.load_full() else {
+            return;
+        };
+        let video_id_ = &video_id;
+        let videos = call_api(api, &updater_s, |api| async move {
+            api.get_watch_next(video_id_, None).await
+        })
+        .await;
+        match videos {
+            Ok(videos) if !videos.is_empty() => {
+                let message = format!("Autoplay added {} tracks to the queue", videos.len());
+                let _ = action_sender.send(SoundAction::AddVideosToQueue(videos));
+                let _ = updater_s.send(ManagerMessage::Notify(message, NOTIFICATION_DURATION));
+            }
+            Ok(_) => warn!("No track to autoplay after {video_id}"),
+            Err(e) => error!("Autoplay after {video_id} -> {e:?}"),
+        }
+    });
+}
+
+/// Adds a track to a playlist of the YouTube Music library
+pub fn spawn_save_to_playlist_task(
+    playlist_id: String,
+    video_id: String,
+    updater_s: Sender<ManagerMessage>,
+) {
+    run_service(async move {
+        let Some(api) = API.load_full() else {
+            let _ = updater_s.send(ManagerMessage::Notify(
+                "Not connected to YouTube Music".to_owned(),
+                NOTIFICATION_DURATION,
+            ));
+            return;
+        };
+        let result = call_api(api, &updater_s, |api| {
+            let (playlist_id, video_id) = (&playlist_id, &video_id);
+            async move { api.add_to_playlist(playlist_id, &[video_id]).await }
+        })
+        .await;
+        let message = match result {
+            Ok(()) => "Saved to the YouTube Music playlist".to_owned(),
+            Err(e) => {
+                error!("Can't add {video_id} to {playlist_id}: {e:?}");
+                "Can't save to the YouTube Music playlist".to_owned()
+            }
+        };
+        let _ = updater_s.send(ManagerMessage::Notify(message, NOTIFICATION_DURATION));
+    });
+}
+
+/// Likes a track, or removes its like, on YouTube Music
+pub fn spawn_like_task(video_id: String, like: bool, updater_s: Sender<ManagerMessage>) {
+    run_service(async move {
+        let Some(api) = API.load_full() else {
+            let _ = updater_s.send(ManagerMessage::Notify(
+                "Not connected to YouTube Music".to_owned(),
+                NOTIFICATION_DURATION,
+            ));
+            return;
+        };
+        let result = call_api(api, &updater_s, |api| {
+            let video_id = &video_id;
+            async move {
+                if like {
+                    api.like_video(video_id).await
+                } else {
+                    api.unlike_video(video_id).await
+                }
+            }
+        })
+        .await;
+        let message = match result {
+            Ok(()) => {
+                let status = if like {
+                    LikeStatus::Like
+                } else {
+                    LikeStatus::Indifferent
+                };
+                LIKE_STATUSES.lock().unwrap().insert(video_id, status);
+                if like { "Liked" } else { "Removed the like" }.to_owned()
+            }
+            Err(e) => {
+                error!("Can't change the rating of {video_id}: {e:?}");
+                "Can't change the rating on YouTube Music".to_owned()
+            }
+        };
+        let _ = updater_s.send(ManagerMessage::Notify(message, NOTIFICATION_DURATION));
+    });
+}
+
 static BROWSED_PLAYLISTS: Lazy<Mutex<Vec<(String, String)>>> = Lazy::new(|| Mutex::new(vec![]));
 
 fn spawn_browse_playlist_task(
@@ -158,12 +612,18 @@ fn spawn_browse_playlist_task(
     run_service(async move {
         let guard = format!("Browse playlist {} {}", playlist.name, playlist.browse_id);
         let guard = performance::guard(&guard);
-        match api.get_playlist(&playlist, 5).await {
+        let playlist_ = &playlist;
+        let videos = call_api(api, &updater_s, |api| async move {
+            api.get_playlist(playlist_, 5).await
+        })
+        .await;
+        match videos {
             Ok(videos) => {
                 if videos.len() < 2 {
                     info!("Playlist {} is too small so skipped", playlist.name);
                     return;
                 }
+                database::save_fetched_playlist(playlist.clone(), videos.clone());
                 let _ = updater_s.send(
                     ManagerMessage::AddElementToChooser((
                         format!("{} ({})", playlist.name, playlist.subtitle),
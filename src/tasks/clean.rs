@@ -1,20 +1,80 @@
-use crate::{consts::CACHE_DIR, run_service, structures::performance};
+use std::{
+    collections::HashSet,
+    time::{Duration, SystemTime},
+};
 
-/// This function is called on start to clean the database and the files
-/// that are incompletely downloaded due to a crash.
+use log::{info, warn};
+
+use crate::{
+    consts::CACHE_DIR, read, run_service, structures::performance, systems::download,
+    tasks::download::IN_DOWNLOAD, DATABASE,
+};
+
+/// Time between two compactions of the cache
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+/// Files modified more recently may belong to a running download and are kept
+const MIN_FILE_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// This function is called on start to clean the files that are not referenced by the
+/// database, like the ones incompletely downloaded due to a crash, then runs every day.
+///
+/// Must be called after `download::spawn_system` restored the downloads of the previous
+/// session, so their partial files are kept.
 pub fn spawn_clean_task() {
     run_service(async move {
-        let guard = performance::guard("Clean task");
-        for i in std::fs::read_dir(CACHE_DIR.join("downloads")).unwrap() {
-            let path = i.unwrap().path();
-            if path.ends_with(".mp4") {
-                let mut path1 = path.clone();
-                path1.set_extension("json");
-                if !path1.exists() {
-                    std::fs::remove_file(&path).unwrap();
-                }
-            }
+        let mut interval = tokio::time::interval(COMPACTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            let guard = performance::guard("Clean task");
+            compact();
+            drop(guard);
         }
-        drop(guard);
     });
 }
+
+/// Deletes the downloaded files whose video isn't in the database nor waiting to be downloaded.
+/// Returns the number of bytes reclaimed.
+pub fn compact() -> u64 {
+    // The database may not be loaded yet on startup
+    let Some(stored) = read() else {
+        warn!("Can't read the database, skipping the cache compaction");
+        return 0;
+    };
+    let referenced = stored
+        .iter()
+        .chain(DATABASE.read().unwrap().iter())
+        .map(|video| video.video_id.clone())
+        .chain(IN_DOWNLOAD.lock().unwrap().keys().cloned())
+        .chain(download::queued().into_iter().map(|video| video.video_id))
+        .collect::<HashSet<_>>();
+    let Ok(entries) = std::fs::read_dir(CACHE_DIR.join("downloads")) else {
+        return 0;
+    };
+    let mut reclaimed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Some(video_id) = path.file_stem().map(|x| x.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        if referenced.contains(&video_id) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let is_recent = metadata
+            .modified()
+            .ok()
+            .and_then(|x| SystemTime::now().duration_since(x).ok())
+            .is_none_or(|age| age < MIN_FILE_AGE);
+        if !metadata.is_file() || is_recent {
+            continue;
+        }
+        match std::fs::remove_file(&path) {
+            Ok(_) => reclaimed += metadata.len(),
+            Err(e) => warn!("Can't remove {path:?}: {e}"),
+        }
+    }
+    info!("Cache compaction reclaimed {reclaimed} bytes");
+    reclaimed
+}
@@ -1,59 +1,237 @@
 use std::{
-    collections::HashSet,
+    collections::HashMap,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use flume::Sender;
-use log::error;
+use log::{error, warn};
 use once_cell::sync::Lazy;
+use player::{Decoder, Source};
 use rusty_ytdl::{
-    DownloadOptions, Video, VideoError, VideoOptions, VideoQuality, VideoSearchOptions,
+    choose_format, reqwest,
+    stream::{NonLiveStream, NonLiveStreamOptions, Stream},
+    DownloadOptions, RequestOptions, Video, VideoError, VideoOptions, VideoQuality,
+    VideoSearchOptions,
 };
 use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::{
-    consts::CACHE_DIR,
+    consts::{CACHE_DIR, CONFIG},
     run_service,
     structures::{app_status::MusicDownloadStatus, sound_action::SoundAction},
-    systems::download::HANDLES,
+    systems::download::{spawn_download, CANCELLED, DOWNLOAD_STATE},
+    tasks::loudness::compute_gain,
+    term::{ManagerMessage, Screens, NOTIFICATION_DURATION},
+    utils::{check_disk_space, format_duration, DiskSpaceError},
+    DATABASE,
 };
 
-fn new_video_with_id(id: &str) -> Result<Video, VideoError> {
+fn video_options() -> VideoOptions {
     let search_options = VideoSearchOptions::Custom(Arc::new(|format| {
         format.has_audio && !format.has_video && format.mime_type.container == "mp4"
     }));
-    let video_options = VideoOptions {
+    VideoOptions {
         quality: VideoQuality::Custom(
             search_options.clone(),
             Arc::new(|x, y| x.audio_bitrate.cmp(&y.audio_bitrate)),
         ),
         filter: search_options,
         download_options: DownloadOptions {
-            dl_chunk_size: Some(1024 * 100_u64),
+            dl_chunk_size: Some(DL_CHUNK_SIZE),
+        },
+        request_options: RequestOptions {
+            client: Some(http_client()),
+            ..Default::default()
         },
-        ..Default::default()
+    }
+}
+
+/// The proxy set in the `[network]` section of the config, it is ignored if invalid
+fn proxy() -> Option<reqwest::Proxy> {
+    let url = CONFIG.network.proxy_url.as_deref()?;
+    reqwest::Proxy::all(url)
+        .map_err(|e| error!("Invalid proxy_url `{url}`: {e}"))
+        .ok()
+}
+
+/// A client going through the proxy of the config, with the timeout of the downloads
+fn http_client() -> reqwest::Client {
+    let builder = reqwest::Client::builder()
+        .timeout(Duration::from_secs(CONFIG.network.download_timeout_secs));
+    match proxy() {
+        Some(proxy) => builder.proxy(proxy),
+        None => builder,
+    }
+    .build()
+    .unwrap_or_default()
+}
+
+const DL_CHUNK_SIZE: u64 = 1024 * 100;
+/// Length of audio decoded after a download to check that the file can be played
+const CHECKED_DURATION: Duration = Duration::from_secs(5);
+/// Number of bytes written between two updates of the progress in `DOWNLOAD_STATE`
+const PROGRESS_STEP: usize = 64 * 1024;
+
+fn new_video_with_id(id: &str) -> Result<Video, VideoError> {
+    Video::new_with_options(id, video_options())
+}
+
+/// Returns the `Content-Length` of the stream and whether it accepts range requests
+async fn probe_link(link: &str) -> Result<(u64, bool), VideoError> {
+    let response = http_client()
+        .head(link)
+        .send()
+        .await
+        .map_err(VideoError::Reqwest)?
+        .error_for_status()
+        .map_err(VideoError::Reqwest)?;
+    let headers = response.headers();
+    let length = headers
+        .get(reqwest::header::CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.parse::<u64>().ok())
+        .unwrap_or(0);
+    let accept_ranges = headers
+        .get(reqwest::header::ACCEPT_RANGES)
+        .is_some_and(|x| x.as_bytes() == b"bytes");
+    Ok((length, accept_ranges))
+}
+
+/// Saves the thumbnail of a song in `CACHE_DIR/thumbs/` for the album art
+async fn download_thumbnail(song: &YoutubeMusicVideoRef) {
+    let Some(url) = &song.thumbnail_url else {
+        return;
     };
+    let result = async {
+        let data = http_client()
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        Ok::<_, reqwest::Error>(data)
+    }
+    .await;
+    match result {
+        Ok(data) => {
+            let path = thumbnail_path(&song.video_id);
+            if let Err(e) = std::fs::create_dir_all(CACHE_DIR.join("thumbs"))
+                .and_then(|_| std::fs::write(path, data))
+            {
+                warn!("Can't save the thumbnail of {}: {e}", song.video_id);
+            }
+        }
+        Err(e) => warn!("Can't download the thumbnail of {}: {e}", song.video_id),
+    }
+}
+
+/// Path of the cached thumbnail of a song
+pub fn thumbnail_path(video_id: &str) -> PathBuf {
+    CACHE_DIR.join(format!("thumbs/{video_id}.jpg"))
+}
+
+#[derive(Debug)]
+pub enum DownloadError {
+    Video(VideoError),
+    DiskSpace(DiskSpaceError),
+    /// The downloaded file can't be decoded, it is removed
+    InvalidAudio(String),
+}
+
+impl From<VideoError> for DownloadError {
+    fn from(e: VideoError) -> Self {
+        Self::Video(e)
+    }
+}
 
-    Video::new_with_options(id, video_options)
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Video(VideoError::Reqwest(e)) if e.is_timeout() => write!(
+                f,
+                "{e} (the timeout can be increased with `download_timeout_secs` in config.toml)"
+            ),
+            Self::Video(e) => e.fmt(f),
+            Self::DiskSpace(e) => e.fmt(f),
+            Self::InvalidAudio(e) => write!(f, "The downloaded file can't be played: {e}"),
+        }
+    }
 }
 
 pub async fn download<P: AsRef<std::path::Path>>(
     video: &Video<'_>,
     path: P,
     sender: Sender<SoundAction>,
-) -> Result<(), VideoError> {
+) -> Result<(), DownloadError> {
     use std::io::Write;
-    let stream = video.stream().await?;
 
-    let length = stream.content_length();
+    let options = video_options();
+    let info = video.get_info().await?;
+    let format =
+        choose_format(&info.formats, &options).map_err(|_| VideoError::VideoSourceNotFound)?;
+    let (length, accept_ranges) = probe_link(&format.url).await?;
+    // Keeping some margin for the file system overhead
+    if let Some(dir) = path.as_ref().parent() {
+        check_disk_space(dir, length + length / 5).map_err(DownloadError::DiskSpace)?;
+    }
+    if length == 0 {
+        return Err(VideoError::DownloadError(
+            "The server didn't send a content length".to_owned(),
+        )
+        .into());
+    }
+
+    // A non-empty partial file left by an interrupted download is completed if the server
+    // supports range requests, otherwise the download restarts from zero
+    let partial = std::fs::metadata(&path).map(|x| x.len()).unwrap_or(0);
+    let offset = if partial > 0 && partial < length && accept_ranges {
+        sender
+            .send(SoundAction::VideoStatusUpdate(
+                video.get_video_id(),
+                MusicDownloadStatus::Resuming,
+            ))
+            .unwrap();
+        partial
+    } else {
+        0
+    };
+
+    let stream = NonLiveStream::new(NonLiveStreamOptions {
+        client: Some(reqwest_middleware::ClientBuilder::new(http_client()).build()),
+        link: format.url,
+        content_length: length,
+        dl_chunk_size: DL_CHUNK_SIZE,
+        start: offset,
+        end: offset + DL_CHUNK_SIZE,
+    })?;
 
-    let mut file =
-        std::fs::File::create(&path).map_err(|e| VideoError::DownloadError(e.to_string()))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(&path)
+        .map_err(|e| VideoError::DownloadError(e.to_string()))?;
 
-    let mut total = 0;
+    let mut total = offset as usize;
+    let mut reported = total;
     while let Some(chunk) = stream.chunk().await? {
         total += chunk.len();
 
+        if total - reported >= PROGRESS_STEP {
+            reported = total;
+            DOWNLOAD_STATE.write().unwrap().update(
+                &video.get_video_id(),
+                total as u64,
+                Some(length),
+            );
+        }
+
         sender
             .send(SoundAction::VideoStatusUpdate(
                 video.get_video_id(),
@@ -68,18 +246,67 @@ pub async fn download<P: AsRef<std::path::Path>>(
     file.flush()
         .map_err(|e| VideoError::DownloadError(e.to_string()))?;
 
-    if total != length || length == 0 {
+    if total != length as usize {
         std::fs::remove_file(path).map_err(|e| VideoError::DownloadError(e.to_string()))?;
         return Err(VideoError::DownloadError(format!(
             "Downloaded file is not the same size as the content length ({}/{})",
             total, length
-        )));
+        ))
+        .into());
     }
 
+    // Decoding is slow enough to be kept out of the runtime threads
+    let downloaded = path.as_ref().to_path_buf();
+    let checked = tokio::task::spawn_blocking(move || check_audio(&downloaded))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|x| x);
+    if let Err(e) = checked {
+        std::fs::remove_file(path).map_err(|e| VideoError::DownloadError(e.to_string()))?;
+        return Err(DownloadError::InvalidAudio(e));
+    }
+
+    Ok(())
+}
+
+/// Decodes the first seconds of a downloaded file to catch the corrupted ones
+fn check_audio(path: &Path) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let mut decoder = Decoder::new_decoder(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let duration = decoder
+        .total_duration()
+        .map_or(CHECKED_DURATION, |x| x.min(CHECKED_DURATION));
+    let samples_per_second = f64::from(decoder.sample_rate()) * f64::from(decoder.channels());
+    let expected = (duration.as_secs_f64() * samples_per_second) as usize;
+    let decoded = decoder.by_ref().take(expected).count();
+    // The duration of the container may be a bit longer than the audio
+    if (decoded as f64) < (duration.as_secs_f64() - 0.5) * samples_per_second {
+        return Err(format!(
+            "only {:.1}s of audio could be decoded",
+            decoded as f64 / samples_per_second
+        ));
+    }
     Ok(())
 }
 
-async fn handle_download(id: &str, sender: Sender<SoundAction>) -> Result<(), VideoError> {
+/// Network errors, server errors and I/O errors are worth retrying, client errors like 404 aren't
+fn is_retryable(error: &DownloadError) -> bool {
+    let error = match error {
+        DownloadError::Video(error) => error,
+        // The download may have been corrupted on the way
+        DownloadError::InvalidAudio(_) => return true,
+        DownloadError::DiskSpace(_) => return false,
+    };
+    let status = match error {
+        VideoError::Reqwest(e) => e.status(),
+        VideoError::ReqwestMiddleware(e) => e.status(),
+        VideoError::DownloadError(_) => None,
+        _ => return false,
+    };
+    status.is_none_or(|x| x.is_server_error())
+}
+
+async fn handle_download(id: &str, sender: Sender<SoundAction>) -> Result<(), DownloadError> {
     let idc = id.to_string();
 
     let video = new_video_with_id(id)?;
@@ -91,7 +318,16 @@ async fn handle_download(id: &str, sender: Sender<SoundAction>) -> Result<(), Vi
         ))
         .unwrap();
     let file = CACHE_DIR.join("downloads").join(format!("{id}.mp4"));
-    download(&video, file, sender.clone()).await?;
+    let mut attempt = 0;
+    while let Err(e) = download(&video, &file, sender.clone()).await {
+        if attempt >= CONFIG.network.download_retries || !is_retryable(&e) {
+            return Err(e);
+        }
+        let backoff = CONFIG.network.download_backoff_ms << attempt;
+        warn!("Download of {id} failed ({e}), retrying in {backoff}ms");
+        tokio::time::sleep(Duration::from_millis(backoff)).await;
+        attempt += 1;
+    }
     sender
         .send(SoundAction::VideoStatusUpdate(
             idc.clone(),
@@ -130,22 +366,22 @@ async fn handle_download(id: &str, sender: Sender<SoundAction>) -> Result<(), Vi
     Ok(())
 }
 
-pub static IN_DOWNLOAD: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+pub static IN_DOWNLOAD: Lazy<Mutex<HashMap<String, YoutubeMusicVideoRef>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 pub async fn start_download(song: YoutubeMusicVideoRef, s: &Sender<SoundAction>) -> bool {
     {
         let mut downloads = IN_DOWNLOAD.lock().unwrap();
-        if downloads.contains(&song.video_id) {
+        if downloads.contains_key(&song.video_id) {
             return false;
         }
-        downloads.insert(song.video_id.clone());
+        downloads.insert(song.video_id.clone(), song.clone());
     }
     s.send(SoundAction::VideoStatusUpdate(
         song.video_id.clone(),
         MusicDownloadStatus::Downloading(1),
     ))
     .unwrap();
-    let download_path_mp4 = CACHE_DIR.join(format!("downloads/{}.mp4", &song.video_id));
     let download_path_json = CACHE_DIR.join(format!("downloads/{}.json", &song.video_id));
     if download_path_json.exists() {
         s.send(SoundAction::VideoStatusUpdate(
@@ -155,9 +391,7 @@ pub async fn start_download(song: YoutubeMusicVideoRef, s: &Sender<SoundAction>)
         .unwrap();
         return true;
     }
-    if download_path_mp4.exists() {
-        std::fs::remove_file(&download_path_mp4).unwrap();
-    }
+    DOWNLOAD_STATE.write().unwrap().start(&song.video_id);
     match handle_download(&song.video_id, s.clone()).await {
         Ok(_) => {
             std::fs::write(download_path_json, serde_json::to_string(&song).unwrap()).unwrap();
@@ -168,26 +402,88 @@ pub async fn start_download(song: YoutubeMusicVideoRef, s: &Sender<SoundAction>)
             ))
             .unwrap();
             IN_DOWNLOAD.lock().unwrap().remove(&song.video_id);
+            DOWNLOAD_STATE.write().unwrap().finish(&song, true);
+            // The track can be played meanwhile, unchanged until its gain is saved
+            let song_ = song.clone();
+            run_service(async move {
+                if CONFIG.playback.normalize {
+                    let path = CACHE_DIR.join(format!("downloads/{}.mp4", &song_.video_id));
+                    compute_gain(&song_.video_id, path).await;
+                }
+                if CONFIG.ui.album_art {
+                    download_thumbnail(&song_).await;
+                }
+            });
             true
         }
         Err(e) => {
-            if download_path_mp4.exists() {
-                std::fs::remove_file(download_path_mp4).unwrap();
-            }
+            // The partial file is kept so the next attempt can resume it
             s.send(SoundAction::VideoStatusUpdate(
                 song.video_id.clone(),
                 MusicDownloadStatus::DownloadFailed,
             ))
             .unwrap();
             error!("Error downloading {}: {e}", song.video_id);
+            DOWNLOAD_STATE.write().unwrap().finish(&song, false);
             false
         }
     }
 }
 pub fn start_task_unary(s: Sender<SoundAction>, song: YoutubeMusicVideoRef) {
-    HANDLES.lock().unwrap().push(run_service(async move {
-        start_download(song, &s).await;
-    }));
+    CANCELLED.lock().unwrap().remove(&song.video_id);
+    spawn_download(song, s);
+}
+
+/// Reads the title, author and duration of a video that isn't in the database
+async fn fetch_video(id: &str) -> Result<YoutubeMusicVideoRef, VideoError> {
+    let details = new_video_with_id(id)?.get_basic_info().await?.video_details;
+    let seconds = details.length_seconds.parse::<u32>().ok();
+    Ok(YoutubeMusicVideoRef {
+        title: details.title,
+        author: details
+            .author
+            .map(|x| x.name)
+            .unwrap_or(details.owner_channel_name),
+        album: String::new(),
+        video_id: details.video_id,
+        duration: seconds
+            .map(|x| format_duration(x.into()))
+            .unwrap_or_default(),
+        duration_seconds: seconds,
+        thumbnail_url: details.thumbnails.last().map(|x| x.url.clone()),
+        like_status: None,
+        explicit: false,
+        year: None,
+    })
+}
+
+/// Plays a video first, downloading it if needed, for `--play`
+pub fn spawn_play_task(id: String, s: Sender<SoundAction>, updater: Sender<ManagerMessage>) {
+    run_service(async move {
+        let cached = DATABASE
+            .read()
+            .unwrap()
+            .iter()
+            .find(|x| x.video_id == id)
+            .cloned();
+        let video = match cached {
+            Some(video) => video,
+            None => match fetch_video(&id).await {
+                Ok(video) => video,
+                Err(e) => {
+                    error!("Can't get the video {id}: {e}");
+                    let _ = updater.send(ManagerMessage::Notify(
+                        format!("Can't play {id}: {e}"),
+                        NOTIFICATION_DURATION,
+                    ));
+                    return;
+                }
+            },
+        };
+        s.send(SoundAction::PlayVideoUnary(video.clone())).unwrap();
+        start_task_unary(s, video);
+        let _ = updater.send(ManagerMessage::ChangeState(Screens::MusicPlayer));
+    });
 }
 
 #[tokio::test]
@@ -0,0 +1,78 @@
+use std::{sync::Arc, time::Duration};
+
+use flume::Sender;
+use log::{error, info, warn};
+use notify::{EventKind, RecursiveMode, Watcher};
+use ytpapi2::YoutubeMusicInstance;
+
+use crate::{
+    client_options, configure_api, get_header_file, run_service,
+    term::{ManagerMessage, NOTIFICATION_DURATION},
+};
+
+use super::api::API;
+
+/// Editors often write a file several times in a row, the events are merged for this long
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Reconnects to YouTube Music with the new cookies each time `headers.txt` changes
+pub fn spawn_header_watcher_task(updater_s: Sender<ManagerMessage>) {
+    let Ok((_, path)) = get_header_file() else {
+        return;
+    };
+    let (sender, receiver) = flume::unbounded();
+    let mut watcher = match notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Can't watch the header file: {e}");
+            return;
+        }
+    };
+    // The directory is watched as editors may replace the file instead of modifying it
+    let Some(directory) = path.parent() else {
+        return;
+    };
+    if let Err(e) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+        warn!("Can't watch {}: {e}", directory.display());
+        return;
+    }
+    run_service(async move {
+        // Dropping the watcher stops it
+        let _watcher = watcher;
+        info!("Header watcher task on");
+        while let Ok(event) = receiver.recv_async().await {
+            let Ok(event) = event else {
+                continue;
+            };
+            if !matches!(event.kind, EventKind::Modify(_) | EventKind::Create(_))
+                || !event
+                    .paths
+                    .iter()
+                    .any(|x| x.file_name() == path.file_name())
+            {
+                continue;
+            }
+            tokio::time::sleep(DEBOUNCE).await;
+            receiver.drain();
+            match YoutubeMusicInstance::from_header_file(&path, client_options())
+                .await
+                .map(configure_api)
+            {
+                Ok(api) => {
+                    API.store(Some(Arc::new(api)));
+                    info!("Reloaded the authentication from {}", path.display());
+                    let _ = updater_s.send(ManagerMessage::AuthRefreshed);
+                }
+                Err(e) => {
+                    error!("Can't reload the header file: {e:?}");
+                    let _ = updater_s.send(ManagerMessage::Notify(
+                        "Can't reload the authentication, the cookies are invalid".to_owned(),
+                        NOTIFICATION_DURATION,
+                    ));
+                }
+            }
+        }
+    });
+}
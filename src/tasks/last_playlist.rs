@@ -1,12 +1,17 @@
+use std::sync::Mutex;
+
 use flume::Sender;
-use log::info;
+use log::{error, info};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::{
-    consts::CACHE_DIR,
+    consts::{CACHE_DIR, CONFIG},
     run_service,
     structures::performance,
     term::{ManagerMessage, Screens},
+    utils::write_atomic,
 };
 
 pub fn spawn_last_playlist_task(updater_s: Sender<ManagerMessage>) {
@@ -26,3 +31,61 @@ pub fn spawn_last_playlist_task(updater_s: Sender<ManagerMessage>) {
         Some(())
     });
 }
+
+/// The queue when ytermusic was closed, saved to `last_state.json` with `auto_start_last_playlist`
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LastState {
+    /// Name of the last playlist opened in the playlist chooser
+    pub playlist: String,
+    pub videos: Vec<YoutubeMusicVideoRef>,
+    /// Index of the track played in `videos`
+    pub current: usize,
+}
+
+static LAST_STATE: Lazy<Mutex<LastState>> = Lazy::new(Default::default);
+
+/// Remembers the playlist opened in the playlist chooser
+pub fn remember_playlist(name: &str) {
+    LAST_STATE.lock().unwrap().playlist = name.to_owned();
+}
+
+/// Remembers the queue of the player, called when its tracks change
+pub fn remember_queue(videos: &[YoutubeMusicVideoRef], current: usize) {
+    let mut state = LAST_STATE.lock().unwrap();
+    state.current = current;
+    state.videos = videos.to_vec();
+}
+
+/// Remembers the index of the track played in the queue, called when it changes
+pub fn remember_current(current: usize) {
+    LAST_STATE.lock().unwrap().current = current;
+}
+
+/// Writes the remembered queue to `last_state.json`, called when ytermusic is closed
+pub fn save_last_state() {
+    if !CONFIG.playback.auto_start_last_playlist {
+        return;
+    }
+    // The lock may be held by the thread that panicked
+    let Ok(state) = LAST_STATE.try_lock() else {
+        return;
+    };
+    let result = serde_json::to_string(&*state)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            write_atomic(&CACHE_DIR.join("last_state.json"), json.as_bytes())
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        error!("Can't save the last state: {e}");
+    }
+}
+
+/// Reads the queue saved by `save_last_state`
+pub fn read_last_state() -> Option<LastState> {
+    let state = std::fs::read_to_string(CACHE_DIR.join("last_state.json")).ok()?;
+    serde_json::from_str(&state)
+        .map_err(|e| error!("Can't read the last state: {e}"))
+        .ok()
+        .filter(|x: &LastState| !x.videos.is_empty())
+}
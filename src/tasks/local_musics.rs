@@ -1,22 +1,36 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
 use flume::Sender;
-use log::info;
-use rand::seq::SliceRandom;
+use log::{error, info, warn};
+use notify::{event::ModifyKind, EventKind, RecursiveMode, Watcher};
+use player::Decoder;
 use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::{
-    consts::{CACHE_DIR, CONFIG},
-    read, run_service,
+    append,
+    consts::CACHE_DIR,
+    get_most_played, get_recently_played, read, rebuild_index, run_service,
     structures::performance,
-    term::{ManagerMessage, Screens},
+    tasks::download::IN_DOWNLOAD,
+    term::{ManagerMessage, Screens, NOTIFICATION_DURATION},
+    utils::format_duration,
     DATABASE,
 };
 
+/// A copied file is added once its size stopped changing for this long
+const SETTLE_DELAY: Duration = Duration::from_secs(1);
+
 pub fn spawn_local_musics_task(updater_s: Sender<ManagerMessage>) {
     run_service(async move {
         info!("Database getter task on");
         let guard = performance::guard("Local musics");
         if let Some(videos) = read() {
-            shuffle_and_send(videos, &updater_s);
+            store_and_send(videos, &updater_s);
         } else {
             let mut videos = Vec::new();
             for files in std::fs::read_dir(CACHE_DIR.join("downloads")).unwrap() {
@@ -28,7 +42,7 @@ pub fn spawn_local_musics_task(updater_s: Sender<ManagerMessage>) {
                     videos.push(video);
                 }
             }
-            shuffle_and_send(videos, &updater_s);
+            store_and_send(videos, &updater_s);
 
             crate::write();
         }
@@ -36,11 +50,25 @@ pub fn spawn_local_musics_task(updater_s: Sender<ManagerMessage>) {
     });
 }
 
-fn shuffle_and_send(mut videos: Vec<YoutubeMusicVideoRef>, updater_s: &Sender<ManagerMessage>) {
+/// Number of tracks in the usage-sorted playlists
+const USAGE_PLAYLIST_SIZE: usize = 50;
+
+fn store_and_send(videos: Vec<YoutubeMusicVideoRef>, updater_s: &Sender<ManagerMessage>) {
     DATABASE.write().unwrap().clone_from(&videos);
+    rebuild_index();
 
-    if CONFIG.player.shuffle {
-        videos.shuffle(&mut rand::thread_rng());
+    for (name, videos) in [
+        ("Most played", get_most_played(USAGE_PLAYLIST_SIZE)),
+        ("Recently played", get_recently_played(USAGE_PLAYLIST_SIZE)),
+    ] {
+        if !videos.is_empty() {
+            updater_s
+                .send(
+                    ManagerMessage::AddElementToChooser((name.to_owned(), videos))
+                        .pass_to(Screens::Playlist),
+                )
+                .unwrap();
+        }
     }
 
     updater_s
@@ -50,3 +78,161 @@ fn shuffle_and_send(mut videos: Vec<YoutubeMusicVideoRef>, updater_s: &Sender<Ma
         )
         .unwrap();
 }
+
+/// Adds the audio files put in the downloads directory by other programs, like yt-dlp
+pub fn spawn_downloads_watcher_task(updater_s: Sender<ManagerMessage>) {
+    let directory = CACHE_DIR.join("downloads");
+    let (sender, receiver) = flume::unbounded();
+    let mut watcher = match notify::recommended_watcher(move |event| {
+        let _ = sender.send(event);
+    }) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            warn!("Can't watch the downloads: {e}");
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&directory, RecursiveMode::NonRecursive) {
+        warn!("Can't watch {}: {e}", directory.display());
+        return;
+    }
+    run_service(async move {
+        // Dropping the watcher stops it
+        let _watcher = watcher;
+        info!("Downloads watcher task on");
+        while let Ok(event) = receiver.recv_async().await {
+            let Ok(event) = event else {
+                continue;
+            };
+            // yt-dlp writes a `.part` file and renames it once done
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_))
+            ) {
+                continue;
+            }
+            for path in event.paths {
+                let updater_s = updater_s.clone();
+                run_service(async move {
+                    if let Some(message) = add_external_file(&path).await {
+                        let _ =
+                            updater_s.send(ManagerMessage::Notify(message, NOTIFICATION_DURATION));
+                        let videos = DATABASE.read().unwrap().clone();
+                        let _ = updater_s.send(
+                            ManagerMessage::AddElementToChooser((
+                                "Local musics".to_owned(),
+                                videos,
+                            ))
+                            .pass_to(Screens::Playlist),
+                        );
+                    }
+                });
+            }
+        }
+    });
+}
+
+/// The id of a video from the name of its file, `{video_id}` or `{title} [{video_id}]` for yt-dlp
+fn video_id_from_file_name(stem: &str) -> Option<&str> {
+    let id = match stem.strip_suffix(']') {
+        Some(stem) => &stem[stem.rfind('[')? + 1..],
+        None => stem,
+    };
+    (id.len() == 11
+        && id
+            .chars()
+            .all(|x| x.is_ascii_alphanumeric() || x == '-' || x == '_'))
+    .then_some(id)
+}
+
+/// Adds an audio file that appeared in the downloads, returns the message to show if it was added
+async fn add_external_file(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    if !matches!(extension.as_str(), "m4a" | "mp4" | "webm") {
+        return None;
+    }
+    let stem = path.file_stem()?.to_str()?;
+    let id = video_id_from_file_name(stem)?.to_owned();
+    // The downloads of ytermusic are added once done
+    if IN_DOWNLOAD.lock().unwrap().contains_key(&id)
+        || DATABASE.read().unwrap().iter().any(|x| x.video_id == id)
+    {
+        return None;
+    }
+    if extension == "webm" {
+        warn!(
+            "{} can't be played, only MP4 audio is supported",
+            path.display()
+        );
+        return Some(format!("Can't add {stem}, WebM files can't be played"));
+    }
+    wait_until_written(path).await?;
+    let target = CACHE_DIR.join(format!("downloads/{id}.mp4"));
+    if path != target {
+        if let Err(e) = std::fs::rename(path, &target) {
+            error!("Can't move {} to {}: {e}", path.display(), target.display());
+            return None;
+        }
+    }
+    let video = video_from_file(&id, stem, &target);
+    let message = format!("Added {} to the local musics", video.title);
+    if let Err(e) = std::fs::write(
+        CACHE_DIR.join(format!("downloads/{id}.json")),
+        serde_json::to_string(&video).unwrap(),
+    ) {
+        error!("Can't write the metadata of {id}: {e}");
+    }
+    append(video).then_some(message)
+}
+
+/// Waits for the size of the file to stop changing, returns `None` if it was removed
+async fn wait_until_written(path: &Path) -> Option<()> {
+    let mut size = std::fs::metadata(path).ok()?.len();
+    loop {
+        tokio::time::sleep(SETTLE_DELAY).await;
+        let new_size = std::fs::metadata(path).ok()?.len();
+        if new_size == size && size > 0 {
+            return Some(());
+        }
+        size = new_size;
+    }
+}
+
+/// Builds the video from the tags of the file, the file name is the title when there is none
+fn video_from_file(id: &str, stem: &str, path: &PathBuf) -> YoutubeMusicVideoRef {
+    let tags = File::open(path)
+        .ok()
+        .and_then(|file| Decoder::read_tags(BufReader::new(file)))
+        .unwrap_or_default();
+    let duration_seconds = tags.duration.map(|x| x.as_secs() as u32);
+    let title = stem
+        .strip_suffix(&format!("[{id}]"))
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .unwrap_or(id);
+    YoutubeMusicVideoRef {
+        title: tags.title.unwrap_or_else(|| title.to_owned()),
+        author: tags.artist.unwrap_or_else(|| "Unknown artist".to_owned()),
+        album: tags.album.unwrap_or_default(),
+        video_id: id.to_owned(),
+        duration: duration_seconds
+            .map(|x| format_duration(x.into()))
+            .unwrap_or_default(),
+        duration_seconds,
+        thumbnail_url: None,
+        like_status: None,
+        explicit: false,
+        year: None,
+    }
+}
+
+#[test]
+fn video_id_from_file_name_test() {
+    assert_eq!(video_id_from_file_name("dQw4w9WgXcQ"), Some("dQw4w9WgXcQ"));
+    assert_eq!(
+        video_id_from_file_name("Never Gonna Give You Up [dQw4w9WgXcQ]"),
+        Some("dQw4w9WgXcQ")
+    );
+    assert_eq!(video_id_from_file_name("My song"), None);
+    assert_eq!(video_id_from_file_name("Live [2019]"), None);
+}
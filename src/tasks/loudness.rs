@@ -0,0 +1,214 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    f64::consts::PI,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use log::{error, warn};
+use once_cell::sync::Lazy;
+use player::{cpal::Sample, Decoder, Source};
+
+use crate::{consts::CACHE_DIR, utils::write_atomic};
+
+/// Loudness the tracks are leveled to, in LUFS
+const TARGET_LOUDNESS: f64 = -14.0;
+/// Largest gain applied to a track, in dB, so the quiet ones don't clip
+const MAX_GAIN: f64 = 10.0;
+/// Blocks quieter than this are ignored, in LUFS
+const ABSOLUTE_GATE: f64 = -70.0;
+/// Blocks quieter than the loudness of the other blocks minus this are ignored, in LU
+const RELATIVE_GATE: f64 = 10.0;
+const GAINS_FILE: &str = "gains.json";
+
+/// The gains leveling the downloaded tracks in dB by video id, shared with the player
+pub static TRACK_GAINS: Lazy<Arc<RwLock<HashMap<String, f32>>>> =
+    Lazy::new(|| Arc::new(RwLock::new(load_gains())));
+
+fn load_gains() -> HashMap<String, f32> {
+    let Ok(content) = std::fs::read_to_string(CACHE_DIR.join(GAINS_FILE)) else {
+        return HashMap::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        error!("Can't read {GAINS_FILE}: {e}");
+        HashMap::new()
+    })
+}
+
+/// Measures the loudness of a downloaded track and saves the gain leveling it to
+/// `TARGET_LOUDNESS` in `gains.json`
+pub async fn compute_gain(video_id: &str, path: PathBuf) {
+    let measured = tokio::task::spawn_blocking(move || measure_file(&path))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|x| x);
+    let loudness = match measured {
+        Ok(Some(loudness)) => loudness,
+        // A silent track is played unchanged
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Can't measure the loudness of {video_id}: {e}");
+            return;
+        }
+    };
+    let gain = (TARGET_LOUDNESS - loudness).clamp(-MAX_GAIN, MAX_GAIN) as f32;
+    let mut gains = TRACK_GAINS.write().unwrap();
+    gains.insert(video_id.to_owned(), gain);
+    // The lock is held while writing so the concurrent downloads can't mix their writes
+    let result = serde_json::to_string(&*gains)
+        .map_err(|e| e.to_string())
+        .and_then(|json| {
+            write_atomic(&CACHE_DIR.join(GAINS_FILE), json.as_bytes()).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        error!("Can't save the gains: {e}");
+    }
+}
+
+/// Decodes a whole file to measure its integrated loudness, `None` when it is silent
+fn measure_file(path: &Path) -> Result<Option<f64>, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let decoder = Decoder::new_decoder(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let mut meter = LoudnessMeter::new(decoder.channels(), decoder.sample_rate());
+    for sample in decoder {
+        meter.push(f64::from(sample.to_f32()));
+    }
+    Ok(meter.integrated())
+}
+
+/// A biquad in transposed direct form II, `a0` being 1
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The K-weighting of ITU-R BS.1770: a high shelf modeling the head followed by a high pass
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = f64::from(sample_rate);
+    let k = (PI * 1_681.974_450_955_533 / rate).tan();
+    let q = 0.707_175_236_955_419_6;
+    let vh = 10f64.powf(3.999_843_853_973_347 / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+    let k = (PI * 38.135_470_876_024_44 / rate).tan();
+    let q = 0.500_327_037_323_877_3;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+    [shelf, high_pass]
+}
+
+fn loudness(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// Integrated loudness of EBU R128 over gated blocks of 400ms overlapping by 75%
+struct LoudnessMeter {
+    /// The K-weighting filters of each channel
+    filters: Vec<[Biquad; 2]>,
+    /// Channel of the next sample
+    channel: usize,
+    /// Frames in a step of 100ms
+    step_len: usize,
+    /// Frames and sum of the weighted squares of the running step
+    step: (usize, f64),
+    /// Mean squares of the last steps, summed over the channels
+    steps: VecDeque<f64>,
+    /// Mean square of each block, summed over the channels
+    blocks: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    fn new(channels: u16, sample_rate: u32) -> Self {
+        Self {
+            filters: vec![k_weighting(sample_rate); usize::from(channels.max(1))],
+            channel: 0,
+            step_len: (sample_rate as usize / 10).max(1),
+            step: (0, 0.0),
+            steps: VecDeque::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Adds an interleaved sample
+    fn push(&mut self, sample: f64) {
+        let [shelf, high_pass] = &mut self.filters[self.channel];
+        let weighted = high_pass.process(shelf.process(sample));
+        self.step.1 += weighted * weighted;
+        self.channel += 1;
+        if self.channel < self.filters.len() {
+            return;
+        }
+        self.channel = 0;
+        self.step.0 += 1;
+        if self.step.0 < self.step_len {
+            return;
+        }
+        self.steps.push_back(self.step.1 / self.step_len as f64);
+        self.step = (0, 0.0);
+        if self.steps.len() == 4 {
+            self.blocks.push(self.steps.iter().sum::<f64>() / 4.0);
+            self.steps.pop_front();
+        }
+    }
+
+    /// The loudness of the samples in LUFS, `None` when no block is above the absolute gate
+    fn integrated(&self) -> Option<f64> {
+        let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+        let audible = self
+            .blocks
+            .iter()
+            .copied()
+            .filter(|x| loudness(*x) > ABSOLUTE_GATE)
+            .collect::<Vec<_>>();
+        if audible.is_empty() {
+            return None;
+        }
+        let gate = loudness(mean(&audible)) - RELATIVE_GATE;
+        let gated = audible
+            .into_iter()
+            .filter(|x| loudness(*x) > gate)
+            .collect::<Vec<_>>();
+        Some(loudness(mean(&gated)))
+    }
+}
+
+#[test]
+fn loudness_meter_test() {
+    // A stereo 1 kHz sine at -23 dBFS is measured at -23 LUFS (EBU Tech 3341, case 1)
+    let amplitude = 10f64.powf(-23.0 / 20.0);
+    let mut meter = LoudnessMeter::new(2, 48000);
+    for i in 0..48000 * 5 {
+        let sample = amplitude * (2.0 * PI * 1000.0 * f64::from(i) / 48000.0).sin();
+        meter.push(sample);
+        meter.push(sample);
+    }
+    let measured = meter.integrated().unwrap();
+    assert!((measured + 23.0).abs() < 0.1, "{measured}");
+    assert_eq!(LoudnessMeter::new(2, 48000).integrated(), None);
+}
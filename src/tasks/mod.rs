@@ -1,5 +1,7 @@
 pub mod api;
 pub mod clean;
 pub mod download;
+pub mod header_watcher;
 pub mod last_playlist;
 pub mod local_musics;
+pub mod loudness;
@@ -0,0 +1,151 @@
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{
+    cursor::{MoveTo, RestorePosition, SavePosition},
+    execute,
+    style::Print,
+};
+use image::imageops::FilterType;
+use log::warn;
+use once_cell::sync::Lazy;
+use ratatui::{layout::Rect, Frame};
+
+use crate::tasks::download::thumbnail_path;
+
+/// Id of the image in the terminal, transmitting a new image with it replaces the previous one
+const IMAGE_ID: u32 = 1;
+/// Maximum size of the payload of a graphics escape sequence
+const CHUNK_SIZE: usize = 4096;
+/// Used when the terminal doesn't report its size in pixels
+const DEFAULT_CELL_SIZE: (u16, u16) = (8, 16);
+
+/// Whether the terminal supports the Kitty graphics protocol
+static SUPPORTED: Lazy<bool> = Lazy::new(|| {
+    let term = std::env::var("TERM").unwrap_or_default();
+    let term_program = std::env::var("TERM_PROGRAM").unwrap_or_default();
+    term.contains("kitty") || matches!(term_program.as_str(), "WezTerm" | "ghostty" | "kitty")
+});
+
+/// The song and area of the image currently displayed
+static DISPLAYED: Mutex<Option<(String, Rect)>> = Mutex::new(None);
+
+/// Set while a popup is drawn above the player, the image would be shown on top of it
+static HIDDEN: AtomicBool = AtomicBool::new(false);
+
+pub fn set_album_art_hidden(hidden: bool) {
+    HIDDEN.store(hidden, Ordering::SeqCst);
+}
+
+/// Shows the cached thumbnail of a song, the image is only sent to the terminal when the song
+/// or the area changes
+pub fn render_album_art(video_id: &str, rect: Rect, frame: &mut Frame) {
+    if !*SUPPORTED {
+        return;
+    }
+    if HIDDEN.load(Ordering::SeqCst) {
+        clear_album_art();
+        return;
+    }
+    let mut displayed = DISPLAYED.lock().unwrap();
+    if displayed
+        .as_ref()
+        .is_some_and(|(id, area)| id == video_id && *area == rect)
+    {
+        skip_cells(rect, frame);
+        return;
+    }
+    let Ok(data) = std::fs::read(thumbnail_path(video_id)) else {
+        drop(displayed);
+        clear_album_art();
+        return;
+    };
+    render_kitty_image(&data, rect, frame);
+    *displayed = Some((video_id.to_owned(), rect));
+}
+
+/// Removes the image from the terminal
+pub fn clear_album_art() {
+    if !*SUPPORTED || DISPLAYED.lock().unwrap().take().is_none() {
+        return;
+    }
+    if let Err(e) = execute!(
+        io::stdout(),
+        Print(format!("\x1b_Ga=d,d=I,i={IMAGE_ID},q=2\x1b\\"))
+    ) {
+        warn!("Can't remove the album art: {e}");
+    }
+}
+
+/// Draws an image resized to fit the area with the Kitty graphics protocol
+pub fn render_kitty_image(data: &[u8], rect: Rect, frame: &mut Frame) {
+    if !*SUPPORTED || rect.width == 0 || rect.height == 0 {
+        return;
+    }
+    let image = match image::load_from_memory(data) {
+        Ok(image) => image,
+        Err(e) => {
+            warn!("Can't decode the album art: {e}");
+            return;
+        }
+    };
+    let (cell_width, cell_height) = crossterm::terminal::window_size()
+        .ok()
+        .filter(|x| x.width > 0 && x.height > 0 && x.columns > 0 && x.rows > 0)
+        .map_or(DEFAULT_CELL_SIZE, |x| {
+            (x.width / x.columns, x.height / x.rows)
+        });
+    let image = image
+        .resize(
+            (rect.width * cell_width) as u32,
+            (rect.height * cell_height) as u32,
+            FilterType::Triangle,
+        )
+        .into_rgba8();
+    let payload = STANDARD.encode(image.as_raw());
+    let chunks = payload.as_bytes().chunks(CHUNK_SIZE).collect::<Vec<_>>();
+    let mut sequence = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        // The chunks are ASCII as they are base64 encoded
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            sequence.push_str(&format!(
+                "\x1b_Ga=T,f=32,s={},v={},i={IMAGE_ID},C=1,q=2,m={more};{chunk}\x1b\\",
+                image.width(),
+                image.height(),
+            ));
+        } else {
+            sequence.push_str(&format!("\x1b_Gm={more};{chunk}\x1b\\"));
+        }
+    }
+    let mut stdout = io::stdout();
+    let result = execute!(
+        stdout,
+        SavePosition,
+        MoveTo(rect.x, rect.y),
+        Print(sequence),
+        RestorePosition
+    )
+    .and_then(|_| stdout.flush());
+    if let Err(e) = result {
+        warn!("Can't draw the album art: {e}");
+    }
+    skip_cells(rect, frame);
+}
+
+/// Prevents the text of the frame from being drawn over the image
+fn skip_cells(rect: Rect, frame: &mut Frame) {
+    let buffer = frame.buffer_mut();
+    for y in rect.top()..rect.bottom() {
+        for x in rect.left()..rect.right() {
+            buffer.get_mut(x, y).set_skip(true);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent};
+use flume::Sender;
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use ytpapi2::AlbumDetails;
+
+use crate::{
+    consts::CONFIG,
+    structures::{
+        keybindings::{Action, BINDINGS},
+        sound_action::SoundAction,
+    },
+    tasks::api::spawn_album_task,
+    utils::{format_duration, invert},
+    DATABASE,
+};
+
+use super::{
+    album_art::{clear_album_art, render_album_art},
+    item_list::{ListItem, ListItemAction},
+    split_x, split_y_start, EventResponse, ManagerMessage, Screen, Screens, NOTIFICATION_DURATION,
+};
+
+/// Height of the header, with its borders
+const HEADER_HEIGHT: u16 = 6;
+
+/// The index of the track in the album
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackAction(usize);
+
+impl ListItemAction for TrackAction {
+    fn render_style(&self, _: &str, selected: bool) -> Style {
+        if selected {
+            invert(CONFIG.player.text_next_style)
+        } else {
+            CONFIG.player.text_next_style
+        }
+    }
+}
+
+/// The tracks of an album below a header with its metadata
+pub struct AlbumView {
+    pub action_sender: Sender<SoundAction>,
+    pub updater: Sender<ManagerMessage>,
+    browse_id: String,
+    /// `None` until the album is fetched
+    details: Option<AlbumDetails>,
+    items: ListItem<TrackAction>,
+}
+
+impl AlbumView {
+    pub fn new(action_sender: Sender<SoundAction>, updater: Sender<ManagerMessage>) -> Self {
+        Self {
+            action_sender,
+            updater,
+            browse_id: String::new(),
+            details: None,
+            items: ListItem::new(" Tracks ".to_owned()),
+        }
+    }
+
+    /// Clears the previous album and starts fetching the album with the browse id
+    pub fn load(&mut self, browse_id: String) {
+        self.items.clear();
+        self.details = None;
+        spawn_album_task(browse_id.clone(), self.updater.clone());
+        self.browse_id = browse_id;
+    }
+
+    fn set_details(&mut self, details: AlbumDetails) {
+        self.items.update(
+            details
+                .tracks
+                .iter()
+                .enumerate()
+                .map(|(i, track)| {
+                    (
+                        format!(" {:>2}. {}     {}", i + 1, track.title, track.duration),
+                        TrackAction(i),
+                    )
+                })
+                .collect(),
+            0,
+        );
+        self.details = Some(details);
+    }
+
+    /// Plays the album from the track, the tracks before it are kept in the queue
+    fn play_from(&self, TrackAction(i): &TrackAction) -> EventResponse {
+        let Some(details) = &self.details else {
+            return EventResponse::None;
+        };
+        self.action_sender
+            .send(SoundAction::ReplaceQueueFrom(details.tracks.clone(), *i))
+            .unwrap();
+        ManagerMessage::PlayerFrom(Screens::AlbumView).event()
+    }
+
+    fn add_to_queue(&self) -> EventResponse {
+        let Some(details) = &self.details else {
+            return EventResponse::None;
+        };
+        self.action_sender
+            .send(SoundAction::AddVideosToQueue(details.tracks.clone()))
+            .unwrap();
+        ManagerMessage::Notify(
+            format!("Added {} to the queue", details.title),
+            NOTIFICATION_DURATION,
+        )
+        .event()
+    }
+
+    /// The lines of the header, the details of the album are shown once fetched
+    fn header(&self) -> Vec<Line<'static>> {
+        let Some(details) = &self.details else {
+            return vec![Line::raw("Loading the album...")];
+        };
+        let seconds = details
+            .tracks
+            .iter()
+            .filter_map(|x| x.duration_seconds)
+            .sum::<u32>();
+        let duration = format_duration(seconds.into());
+        let mut summary = details
+            .year
+            .map(|year| vec![year.to_string()])
+            .unwrap_or_default();
+        summary.push(format!("{} tracks", details.tracks.len()));
+        summary.push(duration);
+        let mut lines = vec![
+            Line::styled(
+                details.title.clone(),
+                Style::new().add_modifier(Modifier::BOLD),
+            ),
+            Line::raw(details.artist.clone()),
+            Line::raw(summary.join(" • ")),
+        ];
+        lines.extend(details.label.clone().map(Line::raw));
+        lines
+    }
+}
+
+impl Screen for AlbumView {
+    fn on_mouse_press(&mut self, e: MouseEvent, r: &Rect) -> EventResponse {
+        let [_, list_rect] = split_y_start(*r, HEADER_HEIGHT);
+        match self.items.on_mouse_press(e, &list_rect) {
+            Some(action) => self.play_from(&action),
+            None => EventResponse::None,
+        }
+    }
+
+    fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
+        if let Some(action) = self.items.on_key_press(key).cloned() {
+            return self.play_from(&action);
+        }
+        match BINDINGS.action(&key) {
+            Some(Action::Search) => return ManagerMessage::SearchFrom(Screens::AlbumView).event(),
+            Some(Action::Settings) => {
+                return ManagerMessage::SettingsFrom(Screens::AlbumView).event()
+            }
+            Some(Action::Help) => return ManagerMessage::HelpFrom(Screens::AlbumView).event(),
+            _ => {}
+        }
+        match key.code {
+            KeyCode::Esc => ManagerMessage::Back.event(),
+            KeyCode::Char('a') => self.add_to_queue(),
+            _ => EventResponse::None,
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [header_rect, list_rect] = split_y_start(frame.size(), HEADER_HEIGHT);
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Album ")
+            .style(CONFIG.player.text_next_style);
+        let inner = block.inner(header_rect);
+        frame.render_widget(block, header_rect);
+        let mut text_rect = inner;
+        if CONFIG.ui.album_art {
+            // The cover is the thumbnail of a downloaded track, terminal cells are twice as high
+            let [text, art_rect] = split_x(inner, inner.height * 2);
+            text_rect = text;
+            let db = DATABASE.read().unwrap();
+            let cover = self.details.as_ref().and_then(|details| {
+                details
+                    .tracks
+                    .iter()
+                    .find(|x| db.iter().any(|y| y.video_id == x.video_id))
+            });
+            match cover {
+                Some(video) => render_album_art(&video.video_id, art_rect, frame),
+                None => clear_album_art(),
+            }
+        }
+        frame.render_widget(Paragraph::new(self.header()), text_rect);
+        frame.render_widget(&self.items, list_rect);
+    }
+
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
+        if let ManagerMessage::AlbumLoaded(details) = message {
+            // The previously opened album can still be fetched
+            if details.browse_id == self.browse_id {
+                self.set_details(details);
+            }
+        }
+        EventResponse::None
+    }
+
+    fn close(&mut self, _: Screens) -> EventResponse {
+        clear_album_art();
+        EventResponse::None
+    }
+
+    fn open(&mut self) -> EventResponse {
+        EventResponse::None
+    }
+}
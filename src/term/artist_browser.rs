@@ -0,0 +1,241 @@
+use std::cmp::Reverse;
+
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent};
+use flume::Sender;
+use ratatui::{layout::Rect, style::Style, Frame};
+use ytpapi2::{AlbumDetails, ArtistRef};
+
+use crate::{
+    consts::CONFIG,
+    structures::{
+        keybindings::{Action, BINDINGS},
+        sound_action::SoundAction,
+    },
+    tasks::api::{spawn_artist_radio_task, spawn_artist_task},
+    utils::invert,
+};
+
+use super::{
+    context_menu::{ContextMenu, MenuEvent},
+    item_list::{ListItem, ListItemAction},
+    EventResponse, ManagerMessage, Screen, Screens, NOTIFICATION_DURATION,
+};
+
+/// The shelves of an artist page, in the order they are listed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Release {
+    TopSongs,
+    Album,
+    Single,
+}
+
+/// The index of the release in `ArtistBrowser::releases`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReleaseAction(usize);
+
+impl ListItemAction for ReleaseAction {
+    fn render_style(&self, _: &str, selected: bool) -> Style {
+        if selected {
+            invert(CONFIG.player.text_next_style)
+        } else {
+            CONFIG.player.text_next_style
+        }
+    }
+}
+
+/// The options of the menu of the artist
+#[derive(Clone)]
+enum ArtistMenuAction {
+    Open(ReleaseAction),
+    PlayRadio,
+}
+
+/// The albums and singles of an artist opened from the search
+pub struct ArtistBrowser {
+    pub action_sender: Sender<SoundAction>,
+    pub updater: Sender<ManagerMessage>,
+    artist: Option<ArtistRef>,
+    /// The releases fetched so far, sorted by shelf then from the most recent
+    releases: Vec<(Release, AlbumDetails)>,
+    items: ListItem<ReleaseAction>,
+    /// The menu of the artist opened with `c`
+    menu: Option<ContextMenu<ArtistMenuAction>>,
+}
+
+impl ArtistBrowser {
+    pub fn new(action_sender: Sender<SoundAction>, updater: Sender<ManagerMessage>) -> Self {
+        Self {
+            action_sender,
+            updater,
+            artist: None,
+            releases: Vec::new(),
+            items: ListItem::new(" Artist ".to_owned()),
+            menu: None,
+        }
+    }
+
+    /// Clears the previous artist and starts fetching the releases of `artist`
+    pub fn load(&mut self, artist: ArtistRef) {
+        self.items
+            .set_title(format!(" {} (r to start the radio) ", artist.name));
+        self.items.clear();
+        self.releases.clear();
+        self.menu = None;
+        spawn_artist_task(artist.channel_id.clone(), self.updater.clone());
+        self.artist = Some(artist);
+    }
+
+    fn add_release(&mut self, release: Release, details: AlbumDetails) {
+        self.releases.push((release, details));
+        self.releases
+            .sort_by_key(|(release, details)| (*release, Reverse(details.year)));
+        self.items.update_contents(
+            self.releases
+                .iter()
+                .enumerate()
+                .map(|(i, (release, details))| {
+                    (format_release(*release, details), ReleaseAction(i))
+                })
+                .collect(),
+        );
+    }
+
+    /// Opens the album view of a release, the top songs are shown in the playlist viewer
+    fn inspect(&self, ReleaseAction(i): &ReleaseAction) -> EventResponse {
+        match self.releases.get(*i) {
+            Some((Release::TopSongs, details)) => ManagerMessage::Inspect(
+                details.title.clone(),
+                Screens::ArtistBrowser,
+                details.tracks.clone(),
+            )
+            .pass_to(Screens::PlaylistViewer)
+            .event(),
+            Some((_, details)) => ManagerMessage::BrowseAlbum(details.browse_id.clone()).event(),
+            None => EventResponse::None,
+        }
+    }
+
+    /// Opens the menu of the artist, it can open the hovered release
+    fn open_menu(&mut self) {
+        let Some(artist) = &self.artist else {
+            return;
+        };
+        let mut items = Vec::new();
+        if let Some(action) = self.items.select() {
+            items.push(("Open".to_owned(), ArtistMenuAction::Open(action.clone())));
+        }
+        items.push(("Play artist radio".to_owned(), ArtistMenuAction::PlayRadio));
+        self.menu = Some(ContextMenu::new(format!(" {} ", artist.name), items));
+    }
+
+    fn start_radio(&self) -> EventResponse {
+        let Some(artist) = self.artist.clone() else {
+            return EventResponse::None;
+        };
+        let message = format!("Loading the radio of {}", artist.name);
+        spawn_artist_radio_task(artist, self.action_sender.clone(), self.updater.clone());
+        ManagerMessage::Notify(message, NOTIFICATION_DURATION).event()
+    }
+}
+
+/// The line of a release with its year and its number of tracks
+fn format_release(release: Release, details: &AlbumDetails) -> String {
+    let tag = match release {
+        Release::TopSongs => "",
+        Release::Album => "[A] ",
+        Release::Single => "[S] ",
+    };
+    let year = details
+        .year
+        .map(|year| format!(" ({year})"))
+        .unwrap_or_default();
+    let count = details.tracks.len();
+    format!(
+        " {tag}{}{year}     {count} track{}",
+        details.title,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+impl Screen for ArtistBrowser {
+    fn on_mouse_press(&mut self, e: MouseEvent, r: &Rect) -> EventResponse {
+        if self.menu.is_some() {
+            return EventResponse::None;
+        }
+        match self.items.on_mouse_press(e, r) {
+            Some(action) => self.inspect(&action),
+            None => EventResponse::None,
+        }
+    }
+
+    fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
+        if let Some(menu) = &mut self.menu {
+            return match menu.on_key_press(key) {
+                MenuEvent::Chosen(action) => {
+                    self.menu = None;
+                    match action {
+                        ArtistMenuAction::Open(action) => self.inspect(&action),
+                        ArtistMenuAction::PlayRadio => self.start_radio(),
+                    }
+                }
+                MenuEvent::Closed => {
+                    self.menu = None;
+                    EventResponse::None
+                }
+                MenuEvent::None => EventResponse::None,
+            };
+        }
+        if let Some(action) = self.items.on_key_press(key).cloned() {
+            return self.inspect(&action);
+        }
+        match BINDINGS.action(&key) {
+            Some(Action::Search) => {
+                return ManagerMessage::SearchFrom(Screens::ArtistBrowser).event()
+            }
+            Some(Action::Settings) => {
+                return ManagerMessage::SettingsFrom(Screens::ArtistBrowser).event()
+            }
+            Some(Action::Help) => return ManagerMessage::HelpFrom(Screens::ArtistBrowser).event(),
+            Some(Action::ContextMenu) => {
+                self.open_menu();
+                return EventResponse::None;
+            }
+            _ => {}
+        }
+        match key.code {
+            KeyCode::Esc => ManagerMessage::Back.event(),
+            KeyCode::Char('r') => self.start_radio(),
+            _ => EventResponse::None,
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        frame.render_widget(&self.items, frame.size());
+        if let Some(menu) = &self.menu {
+            menu.render(frame, frame.size());
+        }
+    }
+
+    fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
+        if let ManagerMessage::AddArtistRelease(channel_id, release, details) = message {
+            // The releases of the previously opened artist still being fetched are dropped
+            if self
+                .artist
+                .as_ref()
+                .is_some_and(|x| x.channel_id == channel_id)
+            {
+                self.add_release(release, details);
+            }
+        }
+        EventResponse::None
+    }
+
+    fn close(&mut self, _: Screens) -> EventResponse {
+        self.menu = None;
+        EventResponse::None
+    }
+
+    fn open(&mut self) -> EventResponse {
+        EventResponse::None
+    }
+}
@@ -0,0 +1,88 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::{
+    layout::Rect,
+    widgets::{Block, Borders, Clear, List, ListState},
+    Frame,
+};
+
+use crate::{consts::CONFIG, utils::invert};
+
+/// What happened to a context menu after a key press
+pub enum MenuEvent<A> {
+    Chosen(A),
+    Closed,
+    None,
+}
+
+/// A small popup list of options shown above a screen
+pub struct ContextMenu<A> {
+    title: String,
+    items: Vec<(String, A)>,
+    selected: usize,
+}
+
+impl<A: Clone> ContextMenu<A> {
+    pub fn new(title: String, items: Vec<(String, A)>) -> Self {
+        Self {
+            title,
+            items,
+            selected: 0,
+        }
+    }
+
+    pub fn on_key_press(&mut self, key: KeyEvent) -> MenuEvent<A> {
+        match key.code {
+            KeyCode::Esc => return MenuEvent::Closed,
+            KeyCode::Enter => {
+                return match self.items.get(self.selected) {
+                    Some((_, action)) => MenuEvent::Chosen(action.clone()),
+                    None => MenuEvent::Closed,
+                }
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self
+                    .selected
+                    .checked_sub(1)
+                    .unwrap_or(self.items.len().saturating_sub(1));
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1) % self.items.len().max(1);
+            }
+            _ => {}
+        }
+        MenuEvent::None
+    }
+
+    /// Renders the menu centered in the area
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let width = self
+            .items
+            .iter()
+            .map(|(label, _)| label.chars().count())
+            .chain([self.title.chars().count()])
+            .max()
+            .unwrap_or_default() as u16
+            + 4;
+        let width = width.min(area.width);
+        let height = (self.items.len() as u16 + 2).min(area.height);
+        let area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(
+            List::new(self.items.iter().map(|(label, _)| format!(" {label}")))
+                .style(CONFIG.player.text_next_style)
+                .highlight_style(invert(CONFIG.player.text_next_style))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(self.title.as_str()),
+                ),
+            area,
+            &mut ListState::default().with_selected(Some(self.selected)),
+        );
+    }
+}
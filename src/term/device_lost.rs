@@ -5,7 +5,10 @@ use ratatui::{
     Frame,
 };
 
-use crate::consts::CONFIG;
+use crate::{
+    consts::CONFIG,
+    structures::keybindings::{Action, BINDINGS},
+};
 
 use super::{EventResponse, ManagerMessage, Screen, Screens};
 
@@ -18,6 +21,9 @@ impl Screen for DeviceLost {
     }
 
     fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
+        if BINDINGS.action(&key) == Some(Action::Help) {
+            return ManagerMessage::HelpFrom(Screens::DeviceLost).event();
+        }
         match key.code {
             KeyCode::Enter | KeyCode::Char(' ') => {
                 if let Some(m) = self.1.take() {
@@ -0,0 +1,293 @@
+use std::ops::Range;
+
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent, MouseEventKind};
+use flume::Sender;
+use ratatui::{
+    layout::Rect,
+    widgets::{Block, Borders, Gauge, Paragraph},
+    Frame,
+};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{
+    consts::CONFIG,
+    structures::{
+        download_progress::DownloadProgress,
+        keybindings::{Action, BINDINGS},
+        sound_action::SoundAction,
+    },
+    systems::download::{is_download_paused, DOWNLOAD_STATE},
+    tasks::download::IN_DOWNLOAD,
+    utils::invert,
+};
+
+use super::{rect_contains, split_y_start, EventResponse, ManagerMessage, Screen, Screens};
+
+/// Most lines of the failed downloads list, the other lists share the rest of the screen
+const MAX_FAILED_ROWS: u16 = 5;
+
+/// A line of the downloads screen
+enum Entry {
+    Active(YoutubeMusicVideoRef, DownloadProgress),
+    Queued(YoutubeMusicVideoRef),
+    Completed(YoutubeMusicVideoRef),
+    Failed(YoutubeMusicVideoRef),
+}
+
+impl Entry {
+    fn video(&self) -> &YoutubeMusicVideoRef {
+        match self {
+            Self::Active(v, _) | Self::Queued(v) | Self::Completed(v) | Self::Failed(v) => v,
+        }
+    }
+
+    /// The index of the list of the entry, from top to bottom
+    fn section(&self) -> usize {
+        match self {
+            Self::Active(..) => 0,
+            Self::Queued(_) => 1,
+            Self::Completed(_) => 2,
+            Self::Failed(_) => 3,
+        }
+    }
+}
+
+// The running, pending, completed and failed downloads of the session, each in its own list
+pub struct Downloads {
+    pub goto: Screens,
+    pub sender: Sender<SoundAction>,
+    /// Index of the selected line, the lists are numbered one after the other
+    selected: usize,
+}
+
+impl Downloads {
+    pub fn new(sender: Sender<SoundAction>) -> Self {
+        Self {
+            goto: Screens::MusicPlayer,
+            sender,
+            selected: 0,
+        }
+    }
+
+    /// The lines of the lists read from `DOWNLOAD_STATE`, sorted by list
+    fn entries(&self) -> Vec<Entry> {
+        let state = DOWNLOAD_STATE.read().unwrap();
+        let in_download = IN_DOWNLOAD.lock().unwrap();
+        let mut active = state
+            .active
+            .iter()
+            .filter_map(|(id, progress)| Some((in_download.get(id)?.clone(), *progress)))
+            .collect::<Vec<_>>();
+        active.sort_by(|(a, _), (b, _)| a.title.cmp(&b.title));
+        let queued = state
+            .queued
+            .iter()
+            .filter(|x| !state.active.contains_key(&x.video_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        active
+            .into_iter()
+            .map(|(video, progress)| Entry::Active(video, progress))
+            .chain(queued.into_iter().map(Entry::Queued))
+            .chain(state.completed.iter().cloned().map(Entry::Completed))
+            .chain(state.failed.iter().cloned().map(Entry::Failed))
+            .collect()
+    }
+
+    fn activate(&self, entry: &Entry) -> EventResponse {
+        match entry {
+            Entry::Completed(video) => {
+                self.sender
+                    .send(SoundAction::PlayVideoUnary(video.clone()))
+                    .unwrap();
+                ManagerMessage::PlayerFrom(Screens::Downloads).event()
+            }
+            Entry::Active(..) | Entry::Queued(_) | Entry::Failed(_) => EventResponse::None,
+        }
+    }
+}
+
+/// The rects of the lists, the active downloads take as many lines as they need
+fn layout(area: Rect, entries: &[Entry]) -> [Rect; 4] {
+    let count = |section: usize| entries.iter().filter(|x| x.section() == section).count() as u16;
+    let [active, rest] = split_y_start(area, (count(0).max(1) + 2).min(area.height / 3));
+    let failed_height = (count(3).clamp(1, MAX_FAILED_ROWS) + 2).min(rest.height / 3);
+    let [lists, failed] = split_y_start(rest, rest.height - failed_height);
+    let [queued, completed] = split_y_start(lists, lists.height / 2);
+    [active, queued, completed, failed]
+}
+
+/// The indexes of the entries of a list
+fn section_range(entries: &[Entry], section: usize) -> Range<usize> {
+    let start = entries.iter().take_while(|x| x.section() < section).count();
+    let len = entries[start..]
+        .iter()
+        .take_while(|x| x.section() == section)
+        .count();
+    start..start + len
+}
+
+/// The first entry shown in a list, the selected one is kept in the middle of its list
+fn list_offset(selected: Option<usize>, height: u16) -> usize {
+    selected
+        .map(|x| x.saturating_sub(height as usize / 2))
+        .unwrap_or_default()
+}
+
+impl Screen for Downloads {
+    fn on_mouse_press(&mut self, e: MouseEvent, frame_data: &Rect) -> EventResponse {
+        match e.kind {
+            MouseEventKind::ScrollUp => self.selected = self.selected.saturating_sub(1),
+            MouseEventKind::ScrollDown => self.selected = self.selected.saturating_add(1),
+            MouseEventKind::Down(_) => {
+                let entries = self.entries();
+                let rects = layout(*frame_data, &entries);
+                let Some(section) = rects
+                    .iter()
+                    .position(|x| rect_contains(x, e.column, e.row, 1))
+                else {
+                    return EventResponse::None;
+                };
+                let rect = rects[section];
+                let range = section_range(&entries, section);
+                let selected = range
+                    .contains(&self.selected)
+                    .then(|| self.selected - range.start);
+                let row = e.row.saturating_sub(rect.y + 1) as usize;
+                let index = range.start + list_offset(selected, rect.height - 2) + row;
+                if range.contains(&index) {
+                    self.selected = index;
+                    return self.activate(&entries[index]);
+                }
+            }
+            _ => {}
+        }
+        EventResponse::None
+    }
+
+    fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
+        match BINDINGS.action(&key) {
+            Some(Action::Settings) => {
+                return ManagerMessage::SettingsFrom(Screens::Downloads).event()
+            }
+            Some(Action::Help) => return ManagerMessage::HelpFrom(Screens::Downloads).event(),
+            _ => {}
+        }
+        match key.code {
+            KeyCode::Esc => ManagerMessage::ChangeState(self.goto).event(),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected = self.selected.saturating_sub(1);
+                EventResponse::None
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = self.selected.saturating_add(1);
+                EventResponse::None
+            }
+            KeyCode::Enter => match self.entries().get(self.selected) {
+                Some(entry) => self.activate(entry),
+                None => EventResponse::None,
+            },
+            KeyCode::Delete | KeyCode::Char('r') => {
+                if let Some(Entry::Active(video, _) | Entry::Queued(video)) =
+                    self.entries().get(self.selected)
+                {
+                    self.sender
+                        .send(SoundAction::CancelDownload(video.video_id.clone()))
+                        .unwrap();
+                }
+                EventResponse::None
+            }
+            _ => EventResponse::None,
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let entries = self.entries();
+        self.selected = self.selected.min(entries.len().saturating_sub(1));
+        let rects = layout(frame.size(), &entries);
+        for (section, area) in rects.into_iter().enumerate() {
+            let range = section_range(&entries, section);
+            let name = match section {
+                0 => "Downloading",
+                1 if is_download_paused() => "Queued, paused",
+                1 => "Queued",
+                2 => "Completed",
+                _ => "Failed",
+            };
+            let block = Block::default()
+                .borders(Borders::ALL)
+                .style(CONFIG.player.text_next_style)
+                .title(format!(" {name} ({}) ", range.len()));
+            let inner = block.inner(area);
+            frame.render_widget(block, area);
+
+            let selected = range
+                .contains(&self.selected)
+                .then(|| self.selected - range.start);
+            for (row, index) in range
+                .skip(list_offset(selected, inner.height))
+                .take(inner.height as usize)
+                .enumerate()
+            {
+                let line = Rect::new(inner.x, inner.y + row as u16, inner.width, 1);
+                render_entry(frame, &entries[index], index == self.selected, line);
+            }
+        }
+    }
+
+    fn handle_global_message(&mut self, _: ManagerMessage) -> EventResponse {
+        EventResponse::None
+    }
+
+    fn close(&mut self, _: Screens) -> EventResponse {
+        EventResponse::None
+    }
+
+    fn open(&mut self) -> EventResponse {
+        EventResponse::None
+    }
+}
+
+/// A running download is shown as a gauge with its speed, the other ones as a line
+fn render_entry(frame: &mut Frame, entry: &Entry, selected: bool, line: Rect) {
+    let video = entry.video();
+    let style = match entry {
+        Entry::Active(..) | Entry::Queued(_) => CONFIG.player.text_downloading_style,
+        Entry::Completed(_) => CONFIG.player.text_next_style,
+        Entry::Failed(_) => CONFIG.player.text_error_style,
+    };
+    let style = if selected { invert(style) } else { style };
+    let icon = match entry {
+        Entry::Active(_, progress) => {
+            let ratio = progress
+                .total_bytes
+                .filter(|x| *x > 0)
+                .map(|x| progress.downloaded_bytes as f64 / x as f64)
+                .unwrap_or_default();
+            frame.render_widget(
+                Gauge::default()
+                    .gauge_style(style)
+                    .ratio(ratio.clamp(0., 1.))
+                    .label(format!(
+                        "⭳ {} | {} ({}/{} KB, {} KB/s)",
+                        video.author,
+                        video.title,
+                        progress.downloaded_bytes / 1024,
+                        progress
+                            .total_bytes
+                            .map_or("?".to_owned(), |x| (x / 1024).to_string()),
+                        (progress.speed_bps / 1024.) as u64
+                    )),
+                line,
+            );
+            return;
+        }
+        Entry::Queued(_) => '…',
+        Entry::Completed(_) => '✓',
+        Entry::Failed(_) => '✗',
+    };
+    frame.render_widget(
+        Paragraph::new(format!(" {icon} {} | {}", video.author, video.title)).style(style),
+        line,
+    );
+}
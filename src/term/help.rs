@@ -0,0 +1,233 @@
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent, MouseEventKind};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use crate::{
+    consts::CONFIG,
+    structures::keybindings::{Action, BINDINGS},
+};
+
+use super::{EventResponse, ManagerMessage, Screen, Screens};
+
+/// Keys of a screen that aren't configurable
+const PLAYER_KEYS: &[(&str, &str)] = &[
+    ("up k", "Scroll up"),
+    ("down j", "Scroll down"),
+    ("enter", "Play the selected song"),
+    ("ctrl+up ctrl+down", "Move the song up or down the queue"),
+    ("ctrl+z", "Return to the skipped song and its position"),
+    ("i", "Show the statistics of the session"),
+    ("/", "Filter the queue"),
+    ("r", "Cycle the repeat mode"),
+    ("t", "Add 15 minutes to the sleep timer"),
+    ("T", "Cancel the sleep timer"),
+    ("] [", "Speed up or slow down the playback"),
+    ("a", "Add or remove the song from the favorites"),
+    ("x", "Cancel the download of the song"),
+    ("F5", "Retry the failed downloads"),
+    ("C", "Clear the queue"),
+    ("d", "Show the downloads"),
+    ("n", "Show the song in full screen"),
+    ("p", "Pause or resume the downloads"),
+];
+
+const PLAYLIST_KEYS: &[(&str, &str)] = &[
+    ("up k", "Scroll up"),
+    ("down j", "Scroll down"),
+    ("enter", "Play or inspect the playlist"),
+    (
+        "a-z 0-9",
+        "Jump to the playlists starting with the character",
+    ),
+    ("space", "Select the song of an inspected playlist"),
+    ("/", "Filter the songs of an inspected playlist"),
+    ("o", "Sort the songs of an inspected playlist"),
+    ("delete", "Delete the local playlist"),
+];
+
+const SEARCH_KEYS: &[(&str, &str)] = &[
+    ("enter", "Queue the result or download the selected ones"),
+    ("ctrl+enter", "Play the result after the current song"),
+    ("ctrl+space", "Select the result"),
+    ("tab", "Open the menu of the result"),
+];
+
+const ARTIST_KEYS: &[(&str, &str)] = &[
+    ("enter", "Show the tracks of the album"),
+    ("r", "Start the radio of the artist"),
+];
+
+const ALBUM_KEYS: &[(&str, &str)] = &[
+    ("enter", "Play the album from the track"),
+    ("a", "Add the album to the queue"),
+];
+
+const DOWNLOADS_KEYS: &[(&str, &str)] = &[
+    ("enter", "Play the download"),
+    ("delete r", "Cancel the download"),
+];
+
+const GLOBAL_KEYS: &[(&str, &str)] = &[("esc", "Go back"), ("ctrl+c ctrl+d", "Quit")];
+
+/// A popup listing the keys of every screen, shown above the screen it was opened from
+pub struct Help {
+    pub goto: Screens,
+    scroll: u16,
+}
+
+impl Default for Help {
+    fn default() -> Self {
+        Self {
+            goto: Screens::MusicPlayer,
+            scroll: 0,
+        }
+    }
+}
+
+impl Help {
+    /// The lines of the help, the configurable keys are read from the active bindings
+    fn lines() -> Vec<Line<'static>> {
+        let bindings = |actions: &[Action]| {
+            actions
+                .iter()
+                .map(|action| (BINDINGS.keys(*action).join(" "), action.description()))
+                .collect::<Vec<_>>()
+        };
+        let fixed = |keys: &[(&str, &'static str)]| {
+            keys.iter()
+                .map(|(keys, description)| ((*keys).to_owned(), *description))
+                .collect::<Vec<_>>()
+        };
+        let mut player = bindings(&[
+            Action::PlayPause,
+            Action::Next,
+            Action::Previous,
+            Action::SeekForward,
+            Action::SeekBackward,
+            Action::VolumeUp,
+            Action::VolumeDown,
+            Action::Shuffle,
+            Action::ContextMenu,
+        ]);
+        player.extend(fixed(PLAYER_KEYS));
+        let mut playlists = bindings(&[Action::ContextMenu]);
+        playlists.extend(fixed(PLAYLIST_KEYS));
+        let mut artist = bindings(&[Action::ContextMenu]);
+        artist.extend(fixed(ARTIST_KEYS));
+        let mut global = bindings(&[Action::Help, Action::Search, Action::Settings]);
+        global.extend(fixed(GLOBAL_KEYS));
+        let sections = [
+            ("Everywhere", global),
+            ("Player", player),
+            ("Playlists", playlists),
+            ("Search", fixed(SEARCH_KEYS)),
+            ("Artist", artist),
+            ("Album", fixed(ALBUM_KEYS)),
+            ("Downloads", fixed(DOWNLOADS_KEYS)),
+        ];
+        let width = sections
+            .iter()
+            .flat_map(|(_, keys)| keys.iter().map(|(keys, _)| keys.chars().count()))
+            .max()
+            .unwrap_or_default();
+        let mut lines = Vec::new();
+        for (title, keys) in sections {
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            lines.push(Line::styled(
+                title,
+                Style::new().add_modifier(Modifier::BOLD),
+            ));
+            for (keys, description) in keys {
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        format!("  {keys:width$}  "),
+                        CONFIG.player.text_searching_style,
+                    ),
+                    Span::raw(description),
+                ]));
+            }
+        }
+        lines
+    }
+
+    fn scroll_by(&mut self, offset: i32, frame_data: &Rect) {
+        let max = (Self::lines().len() as u16)
+            .saturating_sub(popup_area(*frame_data).height.saturating_sub(2));
+        self.scroll = (i32::from(self.scroll) + offset).clamp(0, i32::from(max)) as u16;
+    }
+}
+
+/// A centered rect taking most of the screen
+fn popup_area(area: Rect) -> Rect {
+    let width = area.width.min(70);
+    let height = area.height.saturating_sub(4).max(area.height.min(3));
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+impl Screen for Help {
+    fn on_mouse_press(&mut self, e: MouseEvent, frame_data: &Rect) -> EventResponse {
+        match e.kind {
+            MouseEventKind::ScrollUp => self.scroll_by(-1, frame_data),
+            MouseEventKind::ScrollDown => self.scroll_by(1, frame_data),
+            _ => {}
+        }
+        EventResponse::None
+    }
+
+    fn on_key_press(&mut self, key: KeyEvent, frame_data: &Rect) -> EventResponse {
+        if BINDINGS.action(&key) == Some(Action::Help) {
+            return ManagerMessage::ChangeState(self.goto).event();
+        }
+        let page = i32::from(popup_area(*frame_data).height.saturating_sub(2));
+        match key.code {
+            KeyCode::Esc => return ManagerMessage::ChangeState(self.goto).event(),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1, frame_data),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1, frame_data),
+            KeyCode::PageUp => self.scroll_by(-page, frame_data),
+            KeyCode::PageDown => self.scroll_by(page, frame_data),
+            _ => {}
+        }
+        EventResponse::None
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let area = popup_area(frame.size());
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(Self::lines())
+                .style(CONFIG.player.text_next_style)
+                .scroll((self.scroll, 0))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(" Keys (Esc to close) "),
+                ),
+            area,
+        );
+    }
+
+    fn handle_global_message(&mut self, _: ManagerMessage) -> EventResponse {
+        EventResponse::None
+    }
+
+    fn close(&mut self, _: Screens) -> EventResponse {
+        EventResponse::None
+    }
+
+    fn open(&mut self) -> EventResponse {
+        self.scroll = 0;
+        EventResponse::None
+    }
+}
@@ -1,22 +1,45 @@
-use crossterm::event::{KeyCode, KeyEvent, MouseEventKind};
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEventKind};
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::Style,
-    text::Text,
-    widgets::{Block, Borders, List, ListState, StatefulWidget, Widget},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListState, Paragraph, StatefulWidget, Widget},
 };
 
-use super::{rect_contains, relative_pos};
+use crate::{consts::CONFIG, structures::keybindings::BINDINGS};
+
+/// Pressing the same jump key again within this delay moves to the next matching item
+const JUMP_CYCLE_DELAY: Duration = Duration::from_millis(500);
+
+use super::{rect_contains, relative_pos, split_y};
 
 pub trait ListItemAction {
     fn render_style(&self, string: &str, selected: bool) -> Style;
+
+    /// Text shown dimly after the item
+    fn render_suffix(&self) -> Option<String> {
+        None
+    }
 }
 
 pub struct ListItem<Action> {
     list: Vec<(String, Action)>,
     current_position: usize,
     title: String,
+    /// Indices of the items selected with `Space` or `Shift+Up`/`Shift+Down`
+    selected: HashSet<usize>,
+    /// Query typed after `/`, only the items containing it are shown while it is set
+    filter: Option<String>,
+    /// The character an item is jumped to with, lists without it don't handle jumps
+    key_from_item: Option<fn(&Action) -> Option<char>>,
+    /// The last jump key, the index it moved to and when, to cycle through the matches
+    last_jump: Option<(char, usize, Instant)>,
 }
 
 impl<Action> Default for ListItem<Action> {
@@ -25,6 +48,10 @@ impl<Action> Default for ListItem<Action> {
             list: Default::default(),
             current_position: Default::default(),
             title: Default::default(),
+            selected: Default::default(),
+            filter: Default::default(),
+            key_from_item: None,
+            last_jump: None,
         }
     }
 }
@@ -35,6 +62,18 @@ impl<Action: Clone> ListItem<Action> {
             list: Default::default(),
             current_position: Default::default(),
             title,
+            selected: Default::default(),
+            filter: Default::default(),
+            key_from_item: None,
+            last_jump: None,
+        }
+    }
+
+    /// A list where typing a character jumps to the items starting with it
+    pub fn with_jump_keys(title: String, key_from_item: fn(&Action) -> Option<char>) -> Self {
+        Self {
+            key_from_item: Some(key_from_item),
+            ..Self::new(title)
         }
     }
 
@@ -66,12 +105,48 @@ impl<Action: Clone> ListItem<Action> {
     }
 
     pub fn on_key_press(&mut self, key: KeyEvent) -> Option<&Action> {
+        if let Some(filter) = &mut self.filter {
+            match key.code {
+                KeyCode::Esc => self.set_filter(None),
+                KeyCode::Enter => return self.select(),
+                KeyCode::Up => self.select_up(),
+                KeyCode::Down => self.select_down(),
+                KeyCode::Backspace | KeyCode::Delete => {
+                    filter.pop();
+                    let filter = filter.clone();
+                    self.set_filter(Some(filter));
+                }
+                KeyCode::Char(c) => {
+                    filter.push(c);
+                    let filter = filter.clone();
+                    self.set_filter(Some(filter));
+                }
+                _ => {}
+            }
+            return None;
+        }
         match key.code {
             KeyCode::Enter => {
                 if let Some(a) = self.select() {
                     return Some(a);
                 }
             }
+            KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.extend_selection(Self::select_up)
+            }
+            KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+                self.extend_selection(Self::select_down)
+            }
+            KeyCode::Char(' ') => self.toggle_selection(),
+            // `j` and `k` are kept to move, like the characters bound to actions
+            KeyCode::Char(c)
+                if self.key_from_item.is_some()
+                    && c.is_alphanumeric()
+                    && !matches!(c, 'j' | 'k')
+                    && BINDINGS.action(&key).is_none() =>
+            {
+                self.jump_to(c)
+            }
             KeyCode::Char('+') | KeyCode::Up | KeyCode::Char('k') => self.select_up(),
             KeyCode::Char('-') | KeyCode::Down | KeyCode::Char('j') => self.select_down(),
             _ => {}
@@ -80,19 +155,72 @@ impl<Action: Clone> ListItem<Action> {
     }
 
     pub fn get_item_frame(&self, height: usize) -> Vec<(usize, &(String, Action))> {
+        let items = self.filtered_items();
+        let position = items
+            .iter()
+            .position(|(i, _)| *i == self.current_position)
+            .unwrap_or_default();
         let height = height.saturating_sub(2); // Remove the borders
                                                // Add a little offset when the list is full
-        let start = self.current_position.saturating_sub(3);
-        let length = self.list.len();
+        let start = position.saturating_sub(3);
+        let length = items.len();
         let length_after_start = length.saturating_sub(start);
         // Tries to take all the space left if length_after_start is smaller than height
         let start = start.saturating_sub(height.saturating_sub(length_after_start));
+        items.into_iter().skip(start).take(height).collect()
+    }
+
+    /// The items matching the filter with their index, all of them without filter
+    pub fn filtered_items(&self) -> Vec<(usize, &(String, Action))> {
+        let filter = self.filter.as_ref().map(|x| x.to_lowercase());
         self.list
             .iter()
             .enumerate()
-            .skip(start)
-            .take(height)
-            .collect::<Vec<_>>()
+            .filter(|(_, (string, _))| {
+                filter
+                    .as_ref()
+                    .is_none_or(|filter| string.to_lowercase().contains(filter))
+            })
+            .collect()
+    }
+
+    pub fn filter(&self) -> Option<&str> {
+        self.filter.as_deref()
+    }
+
+    /// Moves to the next item starting with the character, cycling through them when the
+    /// same key is pressed again quickly
+    fn jump_to(&mut self, c: char) {
+        let Some(key_from_item) = self.key_from_item else {
+            return;
+        };
+        let c = c.to_lowercase().next().unwrap_or(c);
+        let matches = self
+            .filtered_items()
+            .into_iter()
+            .filter(|(_, (_, action))| {
+                key_from_item(action).and_then(|x| x.to_lowercase().next()) == Some(c)
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let cycled = match self.last_jump {
+            Some((last, index, time)) if last == c && time.elapsed() < JUMP_CYCLE_DELAY => {
+                matches.iter().find(|i| **i > index).or(matches.first())
+            }
+            _ => matches.first(),
+        };
+        if let Some(index) = cycled.copied() {
+            self.select_to(index);
+            self.last_jump = Some((c, index, Instant::now()));
+        }
+    }
+
+    /// Changes the filter and moves to the first matching item
+    pub fn set_filter(&mut self, filter: Option<String>) {
+        self.filter = filter;
+        if let Some((first, _)) = self.filtered_items().first() {
+            self.current_position = *first;
+        }
     }
 
     pub fn click_on(&mut self, y_position: usize, height: usize) -> Option<(usize, &Action)> {
@@ -104,24 +232,72 @@ impl<Action: Clone> ListItem<Action> {
     }
 
     pub fn select(&self) -> Option<&Action> {
+        self.filtered_items()
+            .into_iter()
+            .find(|(i, _)| *i == self.current_position)
+            .map(|(_, (_, action))| action)
+    }
+
+    /// Selects the current item and the one it moves to
+    fn extend_selection(&mut self, move_cursor: fn(&mut Self)) {
+        if self.list.is_empty() {
+            return;
+        }
+        self.selected.insert(self.current_position);
+        move_cursor(self);
+        self.selected.insert(self.current_position);
+    }
+
+    /// Adds or removes the current item from the selection
+    pub fn toggle_selection(&mut self) {
+        if self.current_position >= self.list.len() {
+            return;
+        }
+        if !self.selected.remove(&self.current_position) {
+            self.selected.insert(self.current_position);
+        }
+    }
+
+    pub fn selected_indices(&self) -> &HashSet<usize> {
+        &self.selected
+    }
+
+    /// The selected items in the order of the list
+    pub fn selected_actions(&self) -> Vec<&Action> {
         self.list
-            .get(self.current_position)
-            .map(|(_, action)| action)
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.selected.contains(i))
+            .map(|(_, (_, action))| action)
+            .collect()
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selected.clear();
     }
 
     pub fn select_down(&mut self) {
-        if self.current_position == self.list.len() - 1 {
-            self.select_to(0);
-        } else {
-            self.select_to(self.current_position.saturating_add(1));
+        let items = self.filtered_items();
+        let next = items
+            .iter()
+            .find(|(i, _)| *i > self.current_position)
+            .or(items.first())
+            .map(|(i, _)| *i);
+        if let Some(next) = next {
+            self.select_to(next);
         }
     }
 
     pub fn select_up(&mut self) {
-        if self.current_position == 0 {
-            self.select_to(self.list.len() - 1);
-        } else {
-            self.select_to(self.current_position.saturating_sub(1));
+        let items = self.filtered_items();
+        let previous = items
+            .iter()
+            .rev()
+            .find(|(i, _)| *i < self.current_position)
+            .or(items.last())
+            .map(|(i, _)| *i);
+        if let Some(previous) = previous {
+            self.select_to(previous);
         }
     }
 
@@ -131,18 +307,27 @@ impl<Action: Clone> ListItem<Action> {
 
     pub fn update(&mut self, list: Vec<(String, Action)>, current: usize) {
         self.list = list;
+        self.selected.clear();
         self.current_position = current.min(self.list.len().saturating_sub(1));
     }
 
     pub fn update_contents(&mut self, list: Vec<(String, Action)>) {
         self.list = list;
+        self.selected.clear();
         self.current_position = self.current_position.min(self.list.len().saturating_sub(1));
     }
     pub fn clear(&mut self) {
         self.list.clear();
+        self.selected.clear();
         self.current_position = 0;
     }
 
+    pub fn retain(&mut self, f: impl FnMut(&(String, Action)) -> bool) {
+        self.list.retain(f);
+        self.selected.clear();
+        self.current_position = self.current_position.min(self.list.len().saturating_sub(1));
+    }
+
     pub fn add_element(&mut self, element: (String, Action)) {
         self.list.push(element);
     }
@@ -158,13 +343,31 @@ impl<Action: Clone> ListItem<Action> {
 
 impl<Action: ListItemAction + Clone> Widget for &ListItem<Action> {
     fn render(self, area: Rect, buf: &mut Buffer) {
+        let area = match &self.filter {
+            Some(filter) => {
+                let [list_area, filter_area] = split_y(area, 3);
+                render_filter(filter, filter_area, buf);
+                list_area
+            }
+            None => area,
+        };
         StatefulWidget::render(
             List::new(
                 self.get_item_frame(area.height as usize)
                     .iter()
                     .map(|(i, (string, action))| {
-                        let style = action.render_style(string, self.current_position == *i);
-                        ratatui::widgets::ListItem::new(Text::from(string.as_str())).style(style)
+                        let mut style = action.render_style(string, self.current_position == *i);
+                        if self.selected.contains(i) {
+                            style = style.bg(Color::DarkGray);
+                        }
+                        let mut spans = vec![Span::raw(string.as_str())];
+                        if let Some(suffix) = action.render_suffix() {
+                            spans.push(Span::styled(
+                                suffix,
+                                Style::new().add_modifier(Modifier::DIM),
+                            ));
+                        }
+                        ratatui::widgets::ListItem::new(Line::from(spans)).style(style)
                     })
                     .collect::<Vec<_>>(),
             )
@@ -179,3 +382,11 @@ impl<Action: ListItemAction + Clone> Widget for &ListItem<Action> {
         );
     }
 }
+
+/// Renders the `/` search bar of a filtered list
+pub fn render_filter(filter: &str, area: Rect, buf: &mut Buffer) {
+    Paragraph::new(format!("/{filter}"))
+        .style(CONFIG.player.text_searching_style)
+        .block(Block::default().borders(Borders::ALL).title(" Filter "))
+        .render(area, buf);
+}
@@ -11,21 +11,56 @@ pub struct ListSelector {
     pub list_size: usize,
     current_position: usize,
     scroll_position: usize,
+    /// Query typed after `/`
+    filter: Option<String>,
+    /// Indices of the items matching the filter, all the items are shown when it is `None`
+    visible: Option<Vec<usize>>,
 }
 
 impl ListSelector {
-    pub fn get_item_frame(&self, height: usize) -> (usize, usize) {
+    /// The indices of the shown items
+    fn visible(&self) -> Vec<usize> {
+        self.visible
+            .clone()
+            .unwrap_or_else(|| (0..self.list_size).collect())
+    }
+
+    pub fn get_item_frame(&self, height: usize) -> Vec<usize> {
+        let visible = self.visible();
+        let position = visible
+            .iter()
+            .position(|i| *i == self.scroll_position)
+            .unwrap_or_default();
         let height = height.saturating_sub(2); // Remove the borders
                                                // Add a little offset when the list is full
-        let start = self.scroll_position.saturating_sub(3);
-        let length = self.list_size;
+        let start = position.saturating_sub(3);
+        let length = visible.len();
         let length_after_start = length.saturating_sub(start);
         // Tries to take all the space left if length_after_start is smaller than height
         let start = start.saturating_sub(height.saturating_sub(length_after_start));
-        (
-            start.min(self.list_size),
-            (start + height).min(self.list_size),
-        )
+        visible.into_iter().skip(start).take(height).collect()
+    }
+
+    pub fn filter(&self) -> Option<&str> {
+        self.filter.as_deref()
+    }
+
+    /// Changes the filter, the matching items must then be given to `set_visible`
+    pub fn set_filter(&mut self, filter: Option<String>) {
+        if filter.is_none() {
+            self.visible = None;
+        }
+        self.filter = filter;
+    }
+
+    /// Shows only the given items, scrolling to the first one if `reset` is set
+    pub fn set_visible(&mut self, visible: Vec<usize>, reset: bool) {
+        if reset {
+            if let Some(first) = visible.first() {
+                self.scroll_position = *first;
+            }
+        }
+        self.visible = Some(visible);
     }
 
     pub fn is_scrolling(&self) -> bool {
@@ -33,24 +68,36 @@ impl ListSelector {
     }
 
     pub fn click_on(&mut self, y_position: usize, height: usize) -> Option<usize> {
-        let (a, b) = self.get_item_frame(height);
-        (a..b)
-            .enumerate()
-            .find(|(i, _)| *i == y_position)
-            .map(|(_, w)| w)
+        self.get_item_frame(height).get(y_position).copied()
     }
 
     pub fn play(&mut self) -> Option<usize> {
+        if !self.visible().contains(&self.scroll_position) {
+            return None;
+        }
         self.current_position = self.scroll_position;
         self.select()
     }
 
     pub fn scroll_down(&mut self) {
-        self.scroll_to(self.scroll_position.saturating_add(1));
+        if let Some(next) = self
+            .visible()
+            .into_iter()
+            .find(|i| *i > self.scroll_position)
+        {
+            self.scroll_to(next);
+        }
     }
 
     pub fn scroll_up(&mut self) {
-        self.scroll_to(self.scroll_position.saturating_sub(1));
+        if let Some(previous) = self
+            .visible()
+            .into_iter()
+            .rev()
+            .find(|i| *i < self.scroll_position)
+        {
+            self.scroll_to(previous);
+        }
     }
 
     pub fn scroll_to(&mut self, position: usize) {
@@ -65,6 +112,15 @@ impl ListSelector {
         }
     }
 
+    /// The index of the item under the cursor
+    pub fn hovered(&self) -> Option<usize> {
+        if self.scroll_position < self.list_size {
+            Some(self.scroll_position)
+        } else {
+            None
+        }
+    }
+
     pub fn update(&mut self, list_size: usize, current: usize) {
         if !self.is_scrolling() {
             self.scroll_position = current;
@@ -82,10 +138,10 @@ impl ListSelector {
         style_fn: impl Fn(usize, bool, bool) -> (Style, String),
         render_title: &str,
     ) {
-        let (a, b) = self.get_item_frame(area.height as usize);
         StatefulWidget::render(
             List::new(
-                (a..b)
+                self.get_item_frame(area.height as usize)
+                    .into_iter()
                     .map(|i| {
                         let (style, text) =
                             style_fn(i, self.current_position == i, self.scroll_position == i);
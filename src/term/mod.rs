@@ -1,10 +1,19 @@
+pub mod album_art;
+pub mod album_view;
+pub mod artist_browser;
+pub mod context_menu;
 pub mod device_lost;
+pub mod downloads;
+pub mod help;
 pub mod item_list;
 pub mod list_selector;
 pub mod music_player;
+pub mod now_playing;
 pub mod playlist;
 pub mod playlist_view;
 pub mod search;
+pub mod settings;
+pub mod track_menu;
 pub mod vertical_gauge;
 
 use std::{
@@ -21,14 +30,34 @@ use crossterm::{
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use flume::{Receiver, Sender};
-use ratatui::{backend::CrosstermBackend, layout::Rect, Frame, Terminal};
-use ytpapi2::YoutubeMusicVideoRef;
+use ratatui::{
+    backend::CrosstermBackend,
+    layout::Rect,
+    widgets::{Clear, Paragraph},
+    Frame, Terminal,
+};
+use ytpapi2::{AlbumDetails, ArtistRef, YoutubeMusicVideoRef};
 
-use crate::{structures::sound_action::SoundAction, systems::player::PlayerState, SIGNALING_STOP};
+use crate::{
+    consts::CONFIG, get_header_file, structures::sound_action::SoundAction,
+    systems::player::PlayerState, utils::invert, SIGNALING_STOP,
+};
 
-use self::{device_lost::DeviceLost, item_list::ListItem, playlist::Chooser, search::Search};
+use self::{
+    album_art::set_album_art_hidden,
+    album_view::AlbumView,
+    artist_browser::{ArtistBrowser, Release},
+    device_lost::DeviceLost,
+    downloads::Downloads,
+    help::Help,
+    item_list::ListItem,
+    now_playing::NowPlaying,
+    playlist::{Chooser, ChooserAction},
+    search::Search,
+    settings::Settings,
+};
 
-use crate::term::playlist_view::PlaylistView;
+use crate::term::playlist_view::{PlayListAction, PlaylistView, SortMode};
 
 // A trait to handle the different screens
 pub trait Screen {
@@ -57,9 +86,28 @@ pub enum ManagerMessage {
     PlayerFrom(Screens),
     #[allow(dead_code)]
     PlaylistFrom(Screens),
+    DownloadsFrom(Screens),
+    SettingsFrom(Screens),
+    HelpFrom(Screens),
+    /// Opens the releases of an artist, `Back` returns to the current screen
+    BrowseArtist(ArtistRef),
+    /// Returns to the screen the last screen of the navigation stack was opened from
+    Back,
+    /// A release fetched for the artist browser, with the channel id of its artist
+    AddArtistRelease(String, Release, AlbumDetails),
+    /// Opens the tracks of an album from its browse id, `Back` returns to the current screen
+    BrowseAlbum(String),
+    /// The album fetched for the album view
+    AlbumLoaded(AlbumDetails),
     RestartPlayer,
     Quit,
     AddElementToChooser((String, Vec<YoutubeMusicVideoRef>)),
+    /// Shows a toast at the bottom of the screen for some time
+    Notify(String, Duration),
+    /// The header file changed and YouTube Music was reconnected to
+    AuthRefreshed,
+    /// The cookies expired and couldn't be read again from the browser
+    AuthExpired,
 }
 
 impl ManagerMessage {
@@ -80,8 +128,17 @@ pub enum Screens {
     Search = 0x2,
     DeviceLost = 0x3,
     PlaylistViewer = 0x4,
+    Downloads = 0x5,
+    Settings = 0x6,
+    Help = 0x7,
+    NowPlaying = 0x8,
+    ArtistBrowser = 0x9,
+    AlbumView = 0xA,
 }
 
+/// How long a notification stays on screen
+pub const NOTIFICATION_DURATION: Duration = Duration::from_secs(4);
+
 // The screen manager that handles the different screens
 pub struct Manager {
     music_player: PlayerState,
@@ -90,26 +147,60 @@ pub struct Manager {
     device_lost: DeviceLost,
     current_screen: Screens,
     playlist_viewer: PlaylistView,
+    downloads: Downloads,
+    settings: Settings,
+    help: Help,
+    now_playing: NowPlaying,
+    artist_browser: ArtistBrowser,
+    album_view: AlbumView,
+    /// The screens to return to with `Back`, the last one first
+    navigation: Vec<Screens>,
+    /// The toasts shown with the time they expire at, the oldest first
+    notifications: Vec<(String, Instant)>,
+    /// Shown at the top of the screen until the authentication is reloaded
+    auth_banner: Option<String>,
 }
 
 impl Manager {
     pub async fn new(action_sender: Sender<SoundAction>, music_player: PlayerState) -> Self {
+        let mut chooser = Chooser {
+            action_sender: action_sender.clone(),
+            updater: music_player.updater.clone(),
+            goto: Screens::MusicPlayer,
+            item_list: ListItem::with_jump_keys(
+                " Choose a playlist ".to_owned(),
+                ChooserAction::jump_key,
+            ),
+        };
+        chooser.add_discover_pages();
+        let artist_browser =
+            ArtistBrowser::new(action_sender.clone(), music_player.updater.clone());
+        let album_view = AlbumView::new(action_sender.clone(), music_player.updater.clone());
         Self {
             music_player,
-            chooser: Chooser {
-                action_sender: action_sender.clone(),
-                goto: Screens::MusicPlayer,
-                item_list: ListItem::new(" Choose a playlist ".to_owned()),
-            },
+            chooser,
             playlist_viewer: PlaylistView {
                 sender: action_sender.clone(),
-                items: ListItem::new(" Playlist ".to_owned()),
+                items: ListItem::with_jump_keys(" Playlist ".to_owned(), PlayListAction::jump_key),
                 goto: Screens::Playlist,
                 videos: Vec::new(),
+                inspected: Vec::new(),
+                name: String::new(),
+                sort_mode: SortMode::default(),
+                track_menu: None,
             },
-            search: Search::new(action_sender).await,
+            downloads: Downloads::new(action_sender.clone()),
+            settings: Settings::new(action_sender.clone()),
+            help: Help::default(),
+            now_playing: NowPlaying::new(action_sender.clone()),
+            artist_browser,
+            album_view,
+            navigation: Vec::new(),
+            search: Search::new(action_sender),
             current_screen: Screens::Playlist,
             device_lost: DeviceLost(Vec::new(), None),
+            notifications: Vec::new(),
+            auth_banner: None,
         }
     }
     pub fn current_screen(&mut self) -> &mut dyn Screen {
@@ -122,6 +213,12 @@ impl Manager {
             Screens::Search => &mut self.search,
             Screens::DeviceLost => &mut self.device_lost,
             Screens::PlaylistViewer => &mut self.playlist_viewer,
+            Screens::Downloads => &mut self.downloads,
+            Screens::Settings => &mut self.settings,
+            Screens::Help => &mut self.help,
+            Screens::NowPlaying => &mut self.now_playing,
+            Screens::ArtistBrowser => &mut self.artist_browser,
+            Screens::AlbumView => &mut self.album_view,
         }
     }
     pub fn set_current_screen(&mut self, screen: Screens) {
@@ -129,6 +226,12 @@ impl Manager {
         let k = self.current_screen().open();
         self.handle_event(k);
     }
+    /// Opens the screen, the current one is pushed onto the navigation stack
+    fn push_screen(&mut self, screen: Screens) {
+        self.current_screen().close(screen);
+        self.navigation.push(self.current_screen);
+        self.set_current_screen(screen);
+    }
     pub fn handle_event(&mut self, event: EventResponse) -> bool {
         match event {
             EventResponse::Message(messages) => {
@@ -172,6 +275,52 @@ impl Manager {
                 self.chooser.goto = e;
                 self.set_current_screen(Screens::Playlist);
             }
+            ManagerMessage::DownloadsFrom(e) => {
+                self.current_screen().close(Screens::Downloads);
+                self.downloads.goto = e;
+                self.set_current_screen(Screens::Downloads);
+            }
+            ManagerMessage::SettingsFrom(e) => {
+                self.current_screen().close(Screens::Settings);
+                self.settings.goto = e;
+                self.settings.refresh(&self.music_player);
+                self.set_current_screen(Screens::Settings);
+            }
+            ManagerMessage::HelpFrom(e) => {
+                self.current_screen().close(Screens::Help);
+                self.help.goto = e;
+                self.set_current_screen(Screens::Help);
+            }
+            ManagerMessage::BrowseArtist(artist) => {
+                self.artist_browser.load(artist);
+                self.push_screen(Screens::ArtistBrowser);
+            }
+            ManagerMessage::BrowseAlbum(browse_id) => {
+                self.album_view.load(browse_id);
+                self.push_screen(Screens::AlbumView);
+            }
+            ManagerMessage::Back => {
+                let screen = self.navigation.pop().unwrap_or(Screens::Playlist);
+                self.current_screen().close(screen);
+                self.set_current_screen(screen);
+            }
+            ManagerMessage::Notify(text, duration) => {
+                self.notifications.push((text, Instant::now() + duration));
+            }
+            ManagerMessage::AuthRefreshed => {
+                self.auth_banner = None;
+                self.notifications.push((
+                    "Authentication reloaded".to_owned(),
+                    Instant::now() + NOTIFICATION_DURATION,
+                ));
+            }
+            ManagerMessage::AuthExpired => {
+                let (Ok((_, path)) | Err((_, path))) = get_header_file();
+                self.auth_banner = Some(format!(
+                    "The cookies expired, refresh `{}` to reconnect",
+                    path.display()
+                ));
+            }
             e => {
                 return self.handle_manager_message(ManagerMessage::PassTo(
                     Screens::DeviceLost,
@@ -187,6 +336,36 @@ impl Manager {
         false
     }
 
+    /// Renders the toasts stacked above the bottom row, the newest one at the bottom
+    fn render_notifications(&self, f: &mut Frame) {
+        let size = f.size();
+        let toasts = self.notifications.iter().rev().take(size.height as usize);
+        for (row, (text, _)) in toasts.enumerate() {
+            let y = size.y + size.height - row as u16 - 1;
+            let width = (text.chars().count() as u16 + 2).min(size.width);
+            let area = Rect::new(size.x + size.width - width, y, width, 1);
+            f.render_widget(Clear, area);
+            f.render_widget(
+                Paragraph::new(format!(" {text} ")).style(invert(CONFIG.player.text_next_style)),
+                area,
+            );
+        }
+    }
+
+    /// Renders the banner of the expired authentication on the top row
+    fn render_auth_banner(&self, f: &mut Frame) {
+        let Some(text) = &self.auth_banner else {
+            return;
+        };
+        let size = f.size();
+        let area = Rect::new(size.x, size.y, size.width, 1.min(size.height));
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(format!(" {text} ")).style(invert(CONFIG.player.text_error_style)),
+            area,
+        );
+    }
+
     /// The main loop of the manager
     pub fn run(&mut self, updater: &Receiver<ManagerMessage>) -> Result<(), io::Error> {
         // setup terminal
@@ -204,6 +383,8 @@ impl Manager {
             if matches!(SIGNALING_STOP.1.try_recv(), Ok(())) {
                 break;
             }
+            let now = Instant::now();
+            self.notifications.retain(|(_, expiry)| *expiry > now);
             while let Ok(e) = updater.try_recv() {
                 if self.handle_manager_message(e) {
                     break 'a;
@@ -212,7 +393,21 @@ impl Manager {
             let rectsize = terminal.size()?;
             terminal.draw(|f| {
                 self.music_player.update();
+                if self.current_screen == Screens::NowPlaying
+                    || (self.current_screen == Screens::Help
+                        && self.help.goto == Screens::NowPlaying)
+                {
+                    self.now_playing.update(&self.music_player);
+                }
+                // The help is drawn above the screen it was opened from
+                set_album_art_hidden(self.current_screen == Screens::Help);
+                if self.current_screen == Screens::Help {
+                    let below = self.help.goto;
+                    self.get_screen(below).render(f);
+                }
                 self.current_screen().render(f);
+                self.render_notifications(f);
+                self.render_auth_banner(f);
             })?;
 
             let timeout = tick_rate
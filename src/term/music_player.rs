@@ -1,25 +1,158 @@
+use std::time::{Duration, Instant};
+
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseEventKind};
 
 use rand::seq::SliceRandom;
-use ratatui::widgets::{Block, Borders, Gauge};
+use ratatui::{
+    layout::Rect,
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Gauge, Paragraph},
+    Frame,
+};
+use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::{
     consts::CONFIG,
-    errors::handle_error,
+    database::{
+        add_to_local_playlist, create_local_playlist, list_local_playlists,
+        remove_from_local_playlist,
+    },
+    errors::handle_play_error,
     structures::{
         app_status::{AppStatus, MusicDownloadStatus},
+        keybindings::{Action, BINDINGS},
+        session_stats::SessionStats,
         sound_action::SoundAction,
     },
-    systems::{download::DOWNLOAD_LIST, player::PlayerState},
-    utils::invert,
+    systems::{
+        download::{self, DOWNLOAD_STATE},
+        player::PlayerState,
+    },
+    tasks::last_playlist,
+    utils::{format_duration, invert, truncate_display},
 };
 
 use super::{
-    rect_contains, relative_pos, split_x, split_y, vertical_gauge::VerticalGauge, EventResponse,
-    ManagerMessage, Screen, Screens,
+    album_art::{clear_album_art, render_album_art},
+    context_menu::MenuEvent,
+    item_list::render_filter,
+    rect_contains, relative_pos, split_x, split_y, split_y_start,
+    track_menu::{apply_track_action, TrackAction, TrackMenu, FAVORITES_PLAYLIST},
+    vertical_gauge::VerticalGauge,
+    EventResponse, ManagerMessage, Screen, Screens, NOTIFICATION_DURATION,
 };
 
+/// Change of the playback rate on each key press
+const SPEED_STEP: f32 = 0.25;
+/// Time added to the sleep timer on each key press
+const SLEEP_TIMER_STEP: Duration = Duration::from_secs(15 * 60);
+/// Number of artists listed in the session statistics
+const TOP_ARTISTS: usize = 5;
+
 impl PlayerState {
+    /// Playing a song of the queue jumps to it instead of adding it again, and playing it next
+    /// moves it after the current one
+    fn on_track_action(
+        &mut self,
+        action: TrackAction,
+        video: YoutubeMusicVideoRef,
+    ) -> EventResponse {
+        let index = self.list.iter().position(|x| x.video_id == video.video_id);
+        match (&action, index) {
+            (TrackAction::PlayNow, Some(index)) => {
+                if index != self.current {
+                    self.activate(index);
+                }
+                return EventResponse::None;
+            }
+            (TrackAction::PlayNext, Some(index)) => {
+                if index != self.current {
+                    // The songs after the removed one shift back
+                    let to = if index < self.current {
+                        self.current
+                    } else {
+                        self.current + 1
+                    };
+                    SoundAction::MoveTrack { from: index, to }.apply_sound_action(self);
+                }
+                return EventResponse::None;
+            }
+            _ => {}
+        }
+        apply_track_action(action, video, &self.soundaction_sender)
+    }
+
+    /// Swaps the song under the cursor with the previous or the next one, the cursor follows it
+    fn move_hovered(&mut self, up: bool) {
+        let Some(from) = self.list_selector.hovered() else {
+            return;
+        };
+        let to = if up {
+            from.checked_sub(1)
+        } else {
+            Some(from + 1).filter(|x| *x < self.list.len())
+        };
+        let Some(to) = to else {
+            return;
+        };
+        let scrolling = self.list_selector.is_scrolling();
+        SoundAction::MoveTrack { from, to }.apply_sound_action(self);
+        // Without scrolling, the cursor is on the playing song and follows it with `current`
+        if scrolling {
+            self.list_selector.scroll_to(to);
+        }
+    }
+
+    /// Shows only the songs whose `author | title` contains the `/` filter
+    fn refresh_filter(&mut self, reset: bool) {
+        let Some(filter) = self.list_selector.filter().map(|x| x.to_lowercase()) else {
+            return;
+        };
+        let visible = self
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, video)| {
+                format!("{} | {}", video.author, video.title)
+                    .to_lowercase()
+                    .contains(&filter)
+            })
+            .map(|(i, _)| i)
+            .collect();
+        self.list_selector.set_visible(visible, reset);
+    }
+
+    /// Handles the keys while the `/` filter is typed
+    fn on_filter_key(&mut self, key: KeyEvent, mut filter: String) -> EventResponse {
+        match key.code {
+            KeyCode::Esc => {
+                self.list_selector.set_filter(None);
+                return EventResponse::None;
+            }
+            KeyCode::Enter => {
+                if let Some(e) = self.list_selector.play() {
+                    self.activate(e);
+                }
+                return EventResponse::None;
+            }
+            KeyCode::Up => self.list_selector.scroll_up(),
+            KeyCode::Down => self.list_selector.scroll_down(),
+            KeyCode::Backspace | KeyCode::Delete => {
+                filter.pop();
+                self.list_selector.set_filter(Some(filter));
+                self.refresh_filter(true);
+            }
+            KeyCode::Char(c) => {
+                filter.push(c);
+                self.list_selector.set_filter(Some(filter));
+                self.refresh_filter(true);
+            }
+            _ => {}
+        }
+        EventResponse::None
+    }
+
     pub fn activate(&mut self, index: usize) {
         match index.cmp(&self.current) {
             std::cmp::Ordering::Less => {
@@ -40,6 +173,9 @@ impl Screen for PlayerState {
         mouse_event: crossterm::event::MouseEvent,
         frame_data: &ratatui::layout::Rect,
     ) -> EventResponse {
+        if self.track_menu.is_some() {
+            return EventResponse::None;
+        }
         let x = mouse_event.column;
         let y = mouse_event.row;
         let [top_rect, bottom] = split_y(*frame_data, 3);
@@ -68,7 +204,12 @@ impl Screen for PlayerState {
                 let (_, y) = relative_pos(&volume_rect, x, y, 1);
                 let size = volume_rect.height as usize - 2;
                 let percent = 100. - y as f64 / size as f64 * 100.;
-                self.sink.set_volume(percent as i32)
+                // Fading avoids a jarring jump when clicking far from the current volume
+                SoundAction::FadeVolumeTo(
+                    percent.clamp(0., 100.) as u8,
+                    Duration::from_millis(200),
+                )
+                .apply_sound_action(self);
             }
         } else if let MouseEventKind::ScrollUp = &mouse_event.kind {
             if rect_contains(&volume_rect, x, y, 1) {
@@ -91,8 +232,145 @@ impl Screen for PlayerState {
     }
 
     fn on_key_press(&mut self, key: KeyEvent, _: &ratatui::layout::Rect) -> EventResponse {
+        if let Some(menu) = &mut self.track_menu {
+            return match menu.on_key_press(key) {
+                MenuEvent::Chosen(action) => {
+                    let video = menu.video().clone();
+                    self.track_menu = None;
+                    self.on_track_action(action, video)
+                }
+                MenuEvent::Closed => {
+                    self.track_menu = None;
+                    EventResponse::None
+                }
+                MenuEvent::None => EventResponse::None,
+            };
+        }
+        if self.show_stats {
+            if matches!(key.code, KeyCode::Char('i') | KeyCode::Esc) {
+                self.show_stats = false;
+            }
+            return EventResponse::None;
+        }
+        if let Some(filter) = self.list_selector.filter() {
+            return self.on_filter_key(key, filter.to_owned());
+        }
+        if key.code == KeyCode::Char('/') {
+            self.list_selector.set_filter(Some(String::new()));
+            self.refresh_filter(true);
+            return EventResponse::None;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('z') {
+            SoundAction::UndoSkip.apply_sound_action(self);
+            return EventResponse::None;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL)
+            && matches!(key.code, KeyCode::Up | KeyCode::Down)
+        {
+            self.move_hovered(key.code == KeyCode::Up);
+            return EventResponse::None;
+        }
+        if let Some(action) = BINDINGS.action(&key) {
+            match action {
+                Action::PlayPause => SoundAction::PlayPause.apply_sound_action(self),
+                Action::Next => SoundAction::Next(1).apply_sound_action(self),
+                Action::Previous => SoundAction::Previous(1).apply_sound_action(self),
+                Action::SeekForward => SoundAction::Forward.apply_sound_action(self),
+                Action::SeekBackward => SoundAction::Backward.apply_sound_action(self),
+                Action::VolumeUp => SoundAction::Plus.apply_sound_action(self),
+                Action::VolumeDown => SoundAction::Minus.apply_sound_action(self),
+                Action::Search => return ManagerMessage::SearchFrom(Screens::MusicPlayer).event(),
+                Action::Settings => {
+                    return ManagerMessage::SettingsFrom(Screens::MusicPlayer).event()
+                }
+                Action::Help => return ManagerMessage::HelpFrom(Screens::MusicPlayer).event(),
+                Action::ContextMenu => {
+                    self.track_menu = self
+                        .list_selector
+                        .hovered()
+                        .and_then(|x| self.list.get(x))
+                        .map(|video| TrackMenu::new(video.clone()));
+                }
+                Action::Shuffle => {
+                    self.list.shuffle(&mut rand::thread_rng());
+                    self.current = 0;
+                    last_playlist::remember_queue(&self.list, self.current);
+                    handle_play_error(&self.updater, "sink stop", self.sink.stop(&self.guard));
+                }
+            }
+            return EventResponse::None;
+        }
         match key.code {
             KeyCode::Esc => ManagerMessage::ChangeState(self.goto).event(),
+            KeyCode::Char('t') => {
+                let remaining = self
+                    .sleep_until
+                    .map(|x| x.saturating_duration_since(Instant::now()))
+                    .unwrap_or_default();
+                let duration = remaining + SLEEP_TIMER_STEP;
+                SoundAction::SetSleepTimer(duration).apply_sound_action(self);
+                ManagerMessage::Notify(
+                    format!("Sleep timer: {} minutes", duration.as_secs().div_ceil(60)),
+                    NOTIFICATION_DURATION,
+                )
+                .event()
+            }
+            KeyCode::Char('i') => {
+                self.show_stats = true;
+                EventResponse::None
+            }
+            KeyCode::Char('x') => {
+                if let Some(video) = self.list_selector.hovered().and_then(|x| self.list.get(x)) {
+                    SoundAction::CancelDownload(video.video_id.clone()).apply_sound_action(self);
+                }
+                EventResponse::None
+            }
+            KeyCode::Char('a') => {
+                let Some(video) = self.list_selector.hovered().and_then(|x| self.list.get(x))
+                else {
+                    return EventResponse::None;
+                };
+                let playlist = list_local_playlists()
+                    .into_iter()
+                    .find(|x| x.name == FAVORITES_PLAYLIST);
+                let message = match playlist {
+                    Some(playlist) if playlist.video_ids.contains(&video.video_id) => {
+                        remove_from_local_playlist(playlist.id, &video.video_id);
+                        format!("Removed from {FAVORITES_PLAYLIST}")
+                    }
+                    playlist => {
+                        let id = playlist
+                            .map(|x| x.id)
+                            .unwrap_or_else(|| create_local_playlist(FAVORITES_PLAYLIST));
+                        add_to_local_playlist(id, &video.video_id);
+                        format!("Added to {FAVORITES_PLAYLIST}")
+                    }
+                };
+                ManagerMessage::Notify(message, NOTIFICATION_DURATION).event()
+            }
+            KeyCode::Char('d') => ManagerMessage::DownloadsFrom(Screens::MusicPlayer).event(),
+            KeyCode::Char('n') => ManagerMessage::ChangeState(Screens::NowPlaying).event(),
+            KeyCode::Char('p') => {
+                SoundAction::ToggleDownloadPause.apply_sound_action(self);
+                EventResponse::None
+            }
+            KeyCode::Char('r') => {
+                SoundAction::CycleRepeat.apply_sound_action(self);
+                EventResponse::None
+            }
+            KeyCode::Char('T') => {
+                SoundAction::ClearSleepTimer.apply_sound_action(self);
+                ManagerMessage::Notify("Sleep timer cancelled".to_owned(), NOTIFICATION_DURATION)
+                    .event()
+            }
+            KeyCode::Char(']') => {
+                SoundAction::SetSpeed(self.sink.speed() + SPEED_STEP).apply_sound_action(self);
+                EventResponse::None
+            }
+            KeyCode::Char('[') => {
+                SoundAction::SetSpeed(self.sink.speed() - SPEED_STEP).apply_sound_action(self);
+                EventResponse::None
+            }
             KeyCode::F(5) => {
                 // Get all musics that have failled to download
                 let mut musics = Vec::new();
@@ -108,24 +386,13 @@ impl Screen for PlayerState {
                         }
                     });
                 // Download them
-                DOWNLOAD_LIST.lock().unwrap().extend(musics);
-                EventResponse::None
-            }
-            KeyCode::Char('f') => ManagerMessage::SearchFrom(Screens::MusicPlayer).event(),
-            KeyCode::Char('s') => {
-                self.list.shuffle(&mut rand::thread_rng());
-                self.current = 0;
-                handle_error(&self.updater, "sink stop", self.sink.stop(&self.guard));
+                download::add_to_download_list(musics);
                 EventResponse::None
             }
             KeyCode::Char('C') => {
                 SoundAction::Cleanup.apply_sound_action(self);
                 EventResponse::None
             }
-            KeyCode::Char(' ') => {
-                SoundAction::PlayPause.apply_sound_action(self);
-                EventResponse::None
-            }
             KeyCode::Up | KeyCode::Char('k') => {
                 self.list_selector.scroll_up();
                 EventResponse::None
@@ -140,38 +407,28 @@ impl Screen for PlayerState {
                 }
                 EventResponse::None
             }
-            KeyCode::Char('+') | KeyCode::Char('=') => {
-                SoundAction::Plus.apply_sound_action(self);
-                EventResponse::None
-            }
-            KeyCode::Char('-') => {
-                SoundAction::Minus.apply_sound_action(self);
-                EventResponse::None
-            }
-            KeyCode::Char('<') | KeyCode::Left | KeyCode::Char('h') => {
-                if key.modifiers.contains(KeyModifiers::CONTROL) {
-                    SoundAction::Previous(1).apply_sound_action(self);
-                } else {
-                    SoundAction::Backward.apply_sound_action(self);
-                }
-                EventResponse::None
-            }
-            KeyCode::Char('>') | KeyCode::Right | KeyCode::Char('l') => {
-                if key.modifiers.contains(KeyModifiers::CONTROL) {
-                    SoundAction::Next(1).apply_sound_action(self);
-                } else {
-                    SoundAction::Forward.apply_sound_action(self);
-                }
-                EventResponse::None
-            }
             _ => EventResponse::None,
         }
     }
 
     fn render(&mut self, f: &mut ratatui::Frame) {
         let render_volume_slider = CONFIG.player.volume_slider;
+        let show_album_art = CONFIG.ui.album_art;
         let [top_rect, progress_rect] = split_y(f.size(), 3);
-        let [list_rect, volume_rect] = split_x(top_rect, if render_volume_slider { 10 } else { 0 });
+        let [list_rect, volume_rect] = split_x(
+            top_rect,
+            if render_volume_slider || show_album_art {
+                10
+            } else {
+                0
+            },
+        );
+        // The album art takes the top of the column, cells being about twice as high as wide
+        let [art_rect, volume_rect] = match (show_album_art, render_volume_slider) {
+            (true, true) => split_y_start(volume_rect, volume_rect.width / 2),
+            (true, false) => [volume_rect, Rect::default()],
+            (false, _) => [Rect::default(), volume_rect],
+        };
         let colors = if self.sink.is_paused() {
             AppStatus::Paused
         } else if self.sink.is_finished() {
@@ -189,6 +446,15 @@ impl Screen for PlayerState {
                 volume_rect,
             );
         }
+        if show_album_art {
+            // The image would be drawn above the menu
+            match self.current() {
+                Some(video) if self.track_menu.is_none() && !self.show_stats => {
+                    render_album_art(&video.video_id, art_rect, f)
+                }
+                _ => clear_album_art(),
+            }
+        }
         let current_time = self.sink.elapsed();
         let total_time = self.sink.duration().map(|x| x as u32).unwrap_or(0);
         f.render_widget(
@@ -198,7 +464,11 @@ impl Screen for PlayerState {
                         .title(
                             self.current()
                                 .map(|x| format!(" {x} "))
-                                .unwrap_or_else(|| " No music playing ".to_owned()),
+                                .unwrap_or_else(|| " No music playing ".to_owned())
+                                + &match self.repeat_mode.icon() {
+                                    "" => String::new(),
+                                    icon => format!("{icon} "),
+                                },
                         )
                         .borders(Borders::ALL),
                 )
@@ -212,16 +482,39 @@ impl Screen for PlayerState {
                     .clamp(0.0, 1.0),
                 )
                 .label(format!(
-                    "{}:{:02} / {}:{:02}",
-                    current_time / 60,
-                    current_time % 60,
-                    total_time / 60,
-                    total_time % 60
+                    "{} / {}{}{}{}",
+                    format_duration(current_time.into()),
+                    format_duration(total_time.into()),
+                    if self.sink.speed() == 1.0 {
+                        String::new()
+                    } else {
+                        format!(" (x{})", self.sink.speed())
+                    },
+                    self.sleep_until
+                        .map(|x| {
+                            let remaining = x.saturating_duration_since(Instant::now()).as_secs();
+                            format!(" | Sleep in {}", format_duration(remaining))
+                        })
+                        .unwrap_or_default(),
+                    if download::is_download_paused() {
+                        " | Downloads paused"
+                    } else {
+                        ""
+                    }
                 )),
             progress_rect,
         );
+        let list_rect = match self.list_selector.filter() {
+            Some(filter) => {
+                let [list_rect, filter_rect] = split_y(list_rect, 3);
+                render_filter(filter, filter_rect, f.buffer_mut());
+                list_rect
+            }
+            None => list_rect,
+        };
         // Create a List from all list items and highlight the currently selected one
         self.list_selector.update(self.list.len(), self.current);
+        self.refresh_filter(false);
         self.list_selector.render(
             list_rect,
             f.buffer_mut(),
@@ -242,14 +535,34 @@ impl Screen for PlayerState {
                         music_state.style(None)
                     },
                     if let Some(e) = self.list.get(index) {
-                        format!(" {music_state_c} {} | {}", e.author, e.title)
+                        let eta = DOWNLOAD_STATE
+                            .read()
+                            .unwrap()
+                            .active
+                            .get(&e.video_id)
+                            .and_then(|x| x.eta())
+                            .map(|x| format!(" {}s", x.as_secs()))
+                            .unwrap_or_default();
+                        let line = format!(
+                            " {music_state_c}{eta} {} | {}{}",
+                            e.author,
+                            e.title,
+                            if e.explicit { " [E]" } else { "" }
+                        );
+                        truncate_display(&line, list_rect.width.saturating_sub(2)).into_owned()
                     } else {
                         String::new()
                     },
                 )
             },
             " Playlist ",
-        )
+        );
+        if let Some(menu) = &self.track_menu {
+            menu.render(f, list_rect);
+        }
+        if self.show_stats {
+            render_session_stats(&self.session_stats, f, list_rect);
+        }
     }
 
     fn handle_global_message(&mut self, message: ManagerMessage) -> EventResponse {
@@ -263,6 +576,9 @@ impl Screen for PlayerState {
     }
 
     fn close(&mut self, _: Screens) -> EventResponse {
+        clear_album_art();
+        self.track_menu = None;
+        self.show_stats = false;
         EventResponse::None
     }
 
@@ -270,3 +586,47 @@ impl Screen for PlayerState {
         EventResponse::None
     }
 }
+
+/// The statistics of the session in a popup centered in `area`
+fn render_session_stats(stats: &SessionStats, frame: &mut Frame, area: Rect) {
+    let mut lines = vec![
+        Line::raw(format!(" Tracks played: {}", stats.tracks_played)),
+        Line::raw(format!(
+            " Time played: {}",
+            format_duration(stats.total_seconds_played)
+        )),
+        Line::default(),
+        Line::styled(
+            " Most played artists",
+            Style::new().add_modifier(Modifier::BOLD),
+        ),
+    ];
+    let artists = stats.top_artists(TOP_ARTISTS);
+    if artists.is_empty() {
+        lines.push(Line::raw("   None yet"));
+    }
+    lines.extend(
+        artists
+            .into_iter()
+            .map(|(artist, count)| Line::raw(format!(" {count:>3}  {artist}"))),
+    );
+    let width = area.width.min(50);
+    let height = (lines.len() as u16 + 2).min(area.height);
+    let area = Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    );
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .style(CONFIG.player.text_next_style)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(" Session (i to close) "),
+            ),
+        area,
+    );
+}
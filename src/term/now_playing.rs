@@ -0,0 +1,263 @@
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent};
+use flume::Sender;
+use ratatui::{
+    layout::{Alignment, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Gauge, Paragraph},
+    Frame,
+};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{
+    consts::CONFIG,
+    structures::{
+        app_status::AppStatus,
+        keybindings::{Action, BINDINGS},
+        repeat_mode::RepeatMode,
+        sound_action::SoundAction,
+    },
+    systems::player::PlayerState,
+    utils::{format_duration, truncate_display},
+};
+
+use super::{
+    album_art::{clear_album_art, render_album_art},
+    playlist::SHUFFLE,
+    split_y_start, EventResponse, ManagerMessage, Screen, Screens,
+};
+
+/// What is shown of the player, copied from it before each frame
+#[derive(Default)]
+struct Snapshot {
+    current: Option<YoutubeMusicVideoRef>,
+    previous: Option<YoutubeMusicVideoRef>,
+    next: Option<YoutubeMusicVideoRef>,
+    paused: bool,
+    /// Seconds played, the player only reports whole seconds
+    elapsed: u32,
+    duration: f64,
+    speed: f32,
+    volume: u8,
+    repeat_mode: RepeatMode,
+}
+
+/// The current song in full screen, toggled with `n` from the player
+pub struct NowPlaying {
+    sender: Sender<SoundAction>,
+    snapshot: Snapshot,
+    /// When the elapsed seconds last changed, to move the progress between two seconds
+    elapsed_since: Instant,
+}
+
+impl NowPlaying {
+    pub fn new(sender: Sender<SoundAction>) -> Self {
+        Self {
+            sender,
+            snapshot: Snapshot::default(),
+            elapsed_since: Instant::now(),
+        }
+    }
+
+    /// Reads the state of the player, called before each frame while the screen is shown
+    pub fn update(&mut self, player: &PlayerState) {
+        let elapsed = player.sink.elapsed();
+        if elapsed != self.snapshot.elapsed {
+            self.elapsed_since = Instant::now();
+        }
+        self.snapshot = Snapshot {
+            current: player.current().cloned(),
+            previous: player
+                .current
+                .checked_sub(1)
+                .and_then(|x| player.list.get(x))
+                .cloned(),
+            next: player.relative_current(1).cloned(),
+            paused: player.sink.is_paused(),
+            elapsed,
+            duration: player.sink.duration().unwrap_or_default(),
+            speed: player.sink.speed(),
+            volume: player.sink.volume_percent(),
+            repeat_mode: player.repeat_mode,
+        };
+    }
+
+    /// The played time with the part of the current second guessed from the last update
+    fn position(&self) -> f64 {
+        let snapshot = &self.snapshot;
+        let part = if snapshot.paused {
+            0.0
+        } else {
+            self.elapsed_since
+                .elapsed()
+                .min(Duration::from_secs(1))
+                .as_secs_f64()
+                * f64::from(snapshot.speed)
+        };
+        (f64::from(snapshot.elapsed) + part).min(snapshot.duration)
+    }
+}
+
+impl Screen for NowPlaying {
+    fn on_mouse_press(&mut self, _: MouseEvent, _: &Rect) -> EventResponse {
+        EventResponse::None
+    }
+
+    fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
+        let action = match BINDINGS.action(&key) {
+            Some(Action::PlayPause) => SoundAction::PlayPause,
+            Some(Action::Next) => SoundAction::Next(1),
+            Some(Action::Previous) => SoundAction::Previous(1),
+            Some(Action::SeekForward) => SoundAction::Forward,
+            Some(Action::SeekBackward) => SoundAction::Backward,
+            Some(Action::VolumeUp) => SoundAction::Plus,
+            Some(Action::VolumeDown) => SoundAction::Minus,
+            Some(Action::Help) => return ManagerMessage::HelpFrom(Screens::NowPlaying).event(),
+            _ => {
+                return match key.code {
+                    KeyCode::Esc | KeyCode::Char('n') => {
+                        ManagerMessage::ChangeState(Screens::MusicPlayer).event()
+                    }
+                    _ => EventResponse::None,
+                }
+            }
+        };
+        self.sender.send(action).unwrap();
+        EventResponse::None
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .style(CONFIG.player.text_next_style)
+            .title(" Now playing ");
+        let inner = block.inner(frame.size());
+        frame.render_widget(block, frame.size());
+        let snapshot = &self.snapshot;
+
+        // The art takes what the text doesn't need, cells being about twice as high as wide
+        let text_height = 11;
+        let [art_area, text_area] = split_y_start(inner, inner.height.saturating_sub(text_height));
+        if CONFIG.ui.album_art && art_area.height > 0 {
+            let width = (art_area.height * 2).min(art_area.width);
+            let art_area = Rect::new(
+                art_area.x + (art_area.width - width) / 2,
+                art_area.y,
+                width,
+                art_area.height,
+            );
+            match &snapshot.current {
+                Some(video) => render_album_art(&video.video_id, art_area, frame),
+                None => clear_album_art(),
+            }
+        }
+
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        let dim = Style::new().add_modifier(Modifier::DIM);
+        let (title, author) = match &snapshot.current {
+            Some(video) => (video.title.as_str(), video.author.as_str()),
+            None => ("No music playing", ""),
+        };
+        let album = snapshot
+            .current
+            .as_ref()
+            .map(|x| x.album.as_str())
+            .unwrap_or_default();
+        let header = vec![
+            Line::default(),
+            Line::styled(
+                truncate_display(&title.to_uppercase(), text_area.width).into_owned(),
+                bold,
+            ),
+            Line::default(),
+            Line::styled(author, bold),
+            Line::styled(album, dim),
+        ];
+        let [header_area, rest] = split_y_start(text_area, header.len() as u16);
+        frame.render_widget(
+            Paragraph::new(header).alignment(Alignment::Center),
+            header_area,
+        );
+
+        let position = self.position();
+        let total = snapshot.duration as u32;
+        let [gauge_area, rest] = split_y_start(rest, 1);
+        let margin = gauge_area.width / 10;
+        let status = match (&snapshot.current, snapshot.paused) {
+            (None, _) => AppStatus::NoMusic,
+            (Some(_), true) => AppStatus::Paused,
+            (Some(_), false) => AppStatus::Playing,
+        };
+        frame.render_widget(
+            Gauge::default()
+                .gauge_style(status.style())
+                .ratio(if snapshot.duration > 0.0 {
+                    (position / snapshot.duration).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                })
+                .label(format!(
+                    "{} / {}",
+                    format_duration(position as u64),
+                    format_duration(total.into())
+                )),
+            Rect::new(
+                gauge_area.x + margin,
+                gauge_area.y,
+                gauge_area.width.saturating_sub(margin * 2),
+                gauge_area.height,
+            ),
+        );
+
+        let status = format!(
+            "{}   Volume {}%   Repeat {} {}   Shuffle {}",
+            if snapshot.paused { "⏸" } else { "▶" },
+            snapshot.volume,
+            snapshot.repeat_mode.name(),
+            snapshot.repeat_mode.icon(),
+            if SHUFFLE.load(Ordering::SeqCst) {
+                "on"
+            } else {
+                "off"
+            },
+        );
+        let around = |label: &str, video: &Option<YoutubeMusicVideoRef>| {
+            Line::styled(
+                video
+                    .as_ref()
+                    .map(|x| format!("{label}: {} | {}", x.author, x.title))
+                    .unwrap_or_default(),
+                dim,
+            )
+        };
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::default(),
+                Line::from(status),
+                Line::default(),
+                around("Previous", &snapshot.previous),
+                around("Next", &snapshot.next),
+            ])
+            .alignment(Alignment::Center),
+            rest,
+        );
+    }
+
+    fn handle_global_message(&mut self, _: ManagerMessage) -> EventResponse {
+        EventResponse::None
+    }
+
+    fn close(&mut self, _: Screens) -> EventResponse {
+        clear_album_art();
+        EventResponse::None
+    }
+
+    fn open(&mut self) -> EventResponse {
+        EventResponse::None
+    }
+}
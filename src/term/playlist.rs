@@ -1,26 +1,47 @@
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use crossterm::event::{KeyCode, KeyEvent};
 use flume::Sender;
+use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
 use ratatui::{layout::Rect, style::Style, Frame};
 use ytpapi2::YoutubeMusicVideoRef;
 
 use crate::{
     consts::{CACHE_DIR, CONFIG},
-    structures::sound_action::SoundAction,
+    database::{delete_local_playlist, list_local_playlists, LocalPlaylist, LocalPlaylistId},
+    structures::{
+        keybindings::{Action, BINDINGS},
+        sound_action::SoundAction,
+    },
     systems::download,
+    tasks::{
+        api::{spawn_discover_task, DiscoverPage},
+        last_playlist,
+    },
     utils::invert,
     DATABASE,
 };
 
 use super::{
     item_list::{ListItem, ListItemAction},
-    EventResponse, ManagerMessage, Screen, Screens,
+    EventResponse, ManagerMessage, Screen, Screens, NOTIFICATION_DURATION,
 };
 
 #[derive(Clone)]
 pub enum ChooserAction {
     Play(PlayListEntry),
+    /// Adds the playlists of a page of YouTube Music to the list
+    Discover(DiscoverPage),
+}
+
+impl ChooserAction {
+    pub fn jump_key(&self) -> Option<char> {
+        match self {
+            Self::Play(entry) => entry.name.chars().next(),
+            Self::Discover(page) => page.name().chars().next(),
+        }
+    }
 }
 
 impl ListItemAction for ChooserAction {
@@ -37,6 +58,7 @@ pub struct Chooser {
     pub item_list: ListItem<ChooserAction>,
     pub goto: Screens,
     pub action_sender: Sender<SoundAction>,
+    pub updater: Sender<ManagerMessage>,
 }
 
 #[derive(Clone)]
@@ -44,6 +66,8 @@ pub struct PlayListEntry {
     pub name: String,
     pub videos: Vec<YoutubeMusicVideoRef>,
     pub text_to_show: String,
+    /// Set for the playlists created by the user
+    pub local: Option<LocalPlaylistId>,
 }
 
 impl PlayListEntry {
@@ -52,6 +76,17 @@ impl PlayListEntry {
             text_to_show: format_playlist(&name, &videos),
             name,
             videos,
+            local: None,
+        }
+    }
+
+    pub fn new_local(playlist: &LocalPlaylist) -> Self {
+        let videos = playlist.videos();
+        Self {
+            text_to_show: format!("★ {}", format_playlist(&playlist.name, &videos)),
+            name: playlist.name.clone(),
+            videos,
+            local: Some(playlist.id),
         }
     }
 
@@ -79,39 +114,62 @@ impl Screen for Chooser {
         mouse_event: crossterm::event::MouseEvent,
         frame_data: &Rect,
     ) -> EventResponse {
-        if let Some(ChooserAction::Play(a)) = self.item_list.on_mouse_press(mouse_event, frame_data)
-        {
-            if PLAYER_RUNNING.load(std::sync::atomic::Ordering::SeqCst) {
-                return EventResponse::Message(vec![ManagerMessage::Inspect(
-                    a.name,
-                    Screens::Playlist,
-                    a.videos,
-                )
-                .pass_to(Screens::PlaylistViewer)]);
+        match self.item_list.on_mouse_press(mouse_event, frame_data) {
+            Some(ChooserAction::Play(a)) => {
+                if PLAYER_RUNNING.load(std::sync::atomic::Ordering::SeqCst) {
+                    return EventResponse::Message(vec![ManagerMessage::Inspect(
+                        a.name,
+                        Screens::Playlist,
+                        a.videos,
+                    )
+                    .pass_to(Screens::PlaylistViewer)]);
+                }
+                self.play(&a);
+                EventResponse::Message(vec![ManagerMessage::PlayerFrom(Screens::Playlist)])
             }
-            self.play(&a);
-            EventResponse::Message(vec![ManagerMessage::PlayerFrom(Screens::Playlist)])
-        } else {
-            EventResponse::None
+            Some(ChooserAction::Discover(page)) => self.discover(page),
+            None => EventResponse::None,
         }
     }
 
     fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
-        if let Some(ChooserAction::Play(a)) = self.item_list.on_key_press(key).cloned() {
-            if PLAYER_RUNNING.load(std::sync::atomic::Ordering::SeqCst) {
-                return EventResponse::Message(vec![ManagerMessage::Inspect(
-                    a.name,
-                    Screens::Playlist,
-                    a.videos,
-                )
-                .pass_to(Screens::PlaylistViewer)]);
+        match self.item_list.on_key_press(key).cloned() {
+            Some(ChooserAction::Play(a)) => {
+                if PLAYER_RUNNING.load(std::sync::atomic::Ordering::SeqCst) {
+                    return EventResponse::Message(vec![ManagerMessage::Inspect(
+                        a.name,
+                        Screens::Playlist,
+                        a.videos,
+                    )
+                    .pass_to(Screens::PlaylistViewer)]);
+                }
+                self.play(&a);
+                return EventResponse::Message(vec![ManagerMessage::ChangeState(
+                    Screens::MusicPlayer,
+                )]);
             }
-            self.play(&a);
-            return EventResponse::Message(vec![ManagerMessage::ChangeState(Screens::MusicPlayer)]);
+            Some(ChooserAction::Discover(page)) => return self.discover(page),
+            None => {}
+        }
+        match BINDINGS.action(&key) {
+            Some(Action::Search) => return ManagerMessage::SearchFrom(Screens::Playlist).event(),
+            Some(Action::Settings) => {
+                return ManagerMessage::SettingsFrom(Screens::Playlist).event()
+            }
+            Some(Action::Help) => return ManagerMessage::HelpFrom(Screens::Playlist).event(),
+            _ => {}
         }
         match key.code {
+            KeyCode::Delete => {
+                if let Some(ChooserAction::Play(PlayListEntry {
+                    local: Some(id), ..
+                })) = self.item_list.select()
+                {
+                    delete_local_playlist(*id);
+                    self.refresh_local_playlists();
+                }
+            }
             KeyCode::Esc => return ManagerMessage::ChangeState(Screens::MusicPlayer).event(),
-            KeyCode::Char('f') => return ManagerMessage::SearchFrom(Screens::Playlist).event(),
             _ => {}
         }
         EventResponse::None
@@ -124,6 +182,8 @@ impl Screen for Chooser {
     fn handle_global_message(&mut self, message: super::ManagerMessage) -> EventResponse {
         if let ManagerMessage::AddElementToChooser(a) = message {
             self.add_element(a);
+            // The database may have been loaded with this element
+            self.refresh_local_playlists();
         }
         EventResponse::None
     }
@@ -133,13 +193,36 @@ impl Screen for Chooser {
     }
 
     fn open(&mut self) -> EventResponse {
+        self.refresh_local_playlists();
         EventResponse::None
     }
 }
 pub static PLAYER_RUNNING: AtomicBool = AtomicBool::new(false);
+/// Whether `Local musics` is shuffled when played, can be changed in the settings
+pub static SHUFFLE: Lazy<AtomicBool> = Lazy::new(|| AtomicBool::new(CONFIG.player.shuffle));
 
 impl Chooser {
+    /// Adds the entries of the charts, the new releases and the radios at the top of the list
+    pub fn add_discover_pages(&mut self) {
+        for page in [
+            DiscoverPage::Charts,
+            DiscoverPage::NewReleases,
+            DiscoverPage::Radio,
+        ] {
+            self.item_list
+                .add_element((format!("» {}", page.name()), ChooserAction::Discover(page)));
+        }
+    }
+    fn discover(&mut self, page: DiscoverPage) -> EventResponse {
+        spawn_discover_task(page, self.updater.clone());
+        ManagerMessage::Notify(
+            format!("Loading the {}...", page.name()),
+            NOTIFICATION_DURATION,
+        )
+        .event()
+    }
     fn play(&mut self, a: &PlayListEntry) {
+        last_playlist::remember_playlist(&a.name);
         if a.name != "Local musics" {
             std::fs::write(
                 CACHE_DIR.join("last-playlist.json"),
@@ -149,11 +232,33 @@ impl Chooser {
         }
         self.action_sender.send(SoundAction::Cleanup).unwrap();
         download::clean(&self.action_sender);
+        let mut videos = a.videos.clone();
+        if a.name == "Local musics" && SHUFFLE.load(Ordering::SeqCst) {
+            videos.shuffle(&mut rand::thread_rng());
+        }
         self.action_sender
-            .send(SoundAction::AddVideosToQueue(a.videos.clone()))
+            .send(SoundAction::AddVideosToQueue(videos))
             .unwrap();
     }
+    /// Replaces the local playlists of the list with their current content
+    fn refresh_local_playlists(&mut self) {
+        self.item_list.retain(|(_, action)| {
+            !matches!(
+                action,
+                ChooserAction::Play(PlayListEntry { local: Some(_), .. })
+            )
+        });
+        for playlist in list_local_playlists() {
+            let entry = PlayListEntry::new_local(&playlist);
+            self.item_list
+                .add_element((entry.text_to_show.clone(), ChooserAction::Play(entry)));
+        }
+    }
+    /// Adds a playlist, replacing the one with the same name if any
     fn add_element(&mut self, element: (String, Vec<YoutubeMusicVideoRef>)) {
+        self.item_list.retain(
+            |(_, action)| !matches!(action, ChooserAction::Play(entry) if entry.name == element.0),
+        );
         let entry = PlayListEntry::new(element.0, element.1);
         self.item_list
             .add_element((entry.text_to_show.clone(), ChooserAction::Play(entry)));
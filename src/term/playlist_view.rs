@@ -3,15 +3,32 @@ use flume::Sender;
 use ratatui::{layout::Rect, style::Style, Frame};
 use ytpapi2::YoutubeMusicVideoRef;
 
-use crate::{consts::CONFIG, structures::sound_action::SoundAction, utils::invert, DATABASE};
+use crate::{
+    consts::CONFIG,
+    structures::{
+        keybindings::{Action, BINDINGS},
+        sound_action::SoundAction,
+    },
+    utils::invert,
+    DATABASE,
+};
 
 use super::{
+    context_menu::MenuEvent,
     item_list::{ListItem, ListItemAction},
-    EventResponse, ManagerMessage, Screen, Screens,
+    track_menu::{apply_track_action, TrackMenu},
+    EventResponse, ManagerMessage, Screen, Screens, NOTIFICATION_DURATION,
 };
 
 #[derive(Clone)]
-pub struct PlayListAction(usize, bool);
+/// The index of the song, whether it isn't downloaded and the first character of its title
+pub struct PlayListAction(usize, bool, Option<char>);
+
+impl PlayListAction {
+    pub fn jump_key(&self) -> Option<char> {
+        self.2
+    }
+}
 
 impl ListItemAction for PlayListAction {
     fn render_style(&self, _: &str, selected: bool) -> Style {
@@ -29,17 +46,121 @@ impl ListItemAction for PlayListAction {
     }
 }
 
+/// The order of the songs of the inspected playlist, cycled with `o`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortMode {
+    /// The order of the playlist
+    #[default]
+    Default,
+    TitleAsc,
+    TitleDesc,
+    ArtistAsc,
+    DurationAsc,
+    DurationDesc,
+}
+
+impl SortMode {
+    fn next(self) -> Self {
+        match self {
+            Self::Default => Self::TitleAsc,
+            Self::TitleAsc => Self::TitleDesc,
+            Self::TitleDesc => Self::ArtistAsc,
+            Self::ArtistAsc => Self::DurationAsc,
+            Self::DurationAsc => Self::DurationDesc,
+            Self::DurationDesc => Self::Default,
+        }
+    }
+
+    /// Shown in the title of the list, `None` for the order of the playlist
+    fn label(self) -> Option<&'static str> {
+        match self {
+            Self::Default => None,
+            Self::TitleAsc => Some("↑ Title"),
+            Self::TitleDesc => Some("↓ Title"),
+            Self::ArtistAsc => Some("↑ Artist"),
+            Self::DurationAsc => Some("↑ Duration"),
+            Self::DurationDesc => Some("↓ Duration"),
+        }
+    }
+
+    /// Sorts the songs, keeping the order of the playlist between equal ones. The songs without
+    /// a known duration are sorted last.
+    fn sort(self, videos: &mut [YoutubeMusicVideoRef]) {
+        match self {
+            Self::Default => {}
+            Self::TitleAsc => videos.sort_by_cached_key(|x| x.title.to_lowercase()),
+            Self::TitleDesc => {
+                videos.sort_by_cached_key(|x| std::cmp::Reverse(x.title.to_lowercase()))
+            }
+            Self::ArtistAsc => videos.sort_by_cached_key(|x| x.author.to_lowercase()),
+            Self::DurationAsc => {
+                videos.sort_by_key(|x| (x.duration_seconds.is_none(), x.duration_seconds))
+            }
+            Self::DurationDesc => videos.sort_by_key(|x| {
+                (
+                    x.duration_seconds.is_none(),
+                    std::cmp::Reverse(x.duration_seconds),
+                )
+            }),
+        }
+    }
+}
+
 // Audio device not connected!
 pub struct PlaylistView {
     pub items: ListItem<PlayListAction>,
+    /// The songs of the inspected playlist in the order of `sort_mode`
     pub videos: Vec<YoutubeMusicVideoRef>,
+    /// The songs in the order of the playlist
+    pub inspected: Vec<YoutubeMusicVideoRef>,
+    /// The name of the inspected playlist
+    pub name: String,
+    pub sort_mode: SortMode,
     pub goto: Screens,
     pub sender: Sender<SoundAction>,
+    /// The menu of the selected song opened with `c`
+    pub track_menu: Option<TrackMenu>,
+}
+
+impl PlaylistView {
+    /// Sorts the songs of the inspected playlist again and updates the list
+    fn refresh(&mut self) {
+        self.items.set_title(match self.sort_mode.label() {
+            Some(label) => format!(" Inspecting {} [{label}] ", self.name),
+            None => format!(" Inspecting {} ", self.name),
+        });
+        self.videos = self.inspected.clone();
+        self.sort_mode.sort(&mut self.videos);
+        let db = DATABASE.read().unwrap();
+        self.items.update_contents(
+            self.videos
+                .iter()
+                .enumerate()
+                .map(|(i, m)| {
+                    (
+                        format!(
+                            "  {m}{}{}",
+                            if m.explicit { " [E]" } else { "" },
+                            m.year.map(|x| format!(" ({x})")).unwrap_or_default()
+                        ),
+                        PlayListAction(
+                            i,
+                            !db.iter().any(|x| x.video_id == m.video_id),
+                            m.title.chars().next(),
+                        ),
+                    )
+                })
+                .collect(),
+        );
+    }
 }
 
 impl Screen for PlaylistView {
     fn on_mouse_press(&mut self, e: crossterm::event::MouseEvent, r: &Rect) -> EventResponse {
-        if let Some(PlayListAction(v, _)) = self.items.on_mouse_press(e, r) {
+        if self.track_menu.is_some() {
+            return EventResponse::None;
+        }
+        if let Some(PlayListAction(v, _, _)) = self.items.on_mouse_press(e, r) {
             self.sender
                 .send(SoundAction::ReplaceQueue(
                     self.videos.iter().skip(v).cloned().collect(),
@@ -52,7 +173,45 @@ impl Screen for PlaylistView {
     }
 
     fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
-        if let Some(PlayListAction(v, _)) = self.items.on_key_press(key) {
+        if let Some(menu) = &mut self.track_menu {
+            return match menu.on_key_press(key) {
+                MenuEvent::Chosen(action) => {
+                    let video = menu.video().clone();
+                    self.track_menu = None;
+                    apply_track_action(action, video, &self.sender)
+                }
+                MenuEvent::Closed => {
+                    self.track_menu = None;
+                    EventResponse::None
+                }
+                MenuEvent::None => EventResponse::None,
+            };
+        }
+        let filtering = self.items.filter().is_some();
+        if key.code == KeyCode::Char('/') && !filtering {
+            self.items.set_filter(Some(String::new()));
+            return EventResponse::None;
+        }
+        if key.code == KeyCode::Char('o') && !filtering {
+            self.sort_mode = self.sort_mode.next();
+            self.refresh();
+            return EventResponse::None;
+        }
+        if key.code == KeyCode::Enter && !filtering && !self.items.selected_indices().is_empty() {
+            let videos = self
+                .items
+                .selected_actions()
+                .into_iter()
+                .filter_map(|PlayListAction(i, _, _)| self.videos.get(*i).cloned())
+                .collect::<Vec<_>>();
+            self.items.clear_selection();
+            let message = format!("Added {} songs to the queue", videos.len());
+            self.sender
+                .send(SoundAction::AddVideosToQueue(videos))
+                .unwrap();
+            return ManagerMessage::Notify(message, NOTIFICATION_DURATION).event();
+        }
+        if let Some(PlayListAction(v, _, _)) = self.items.on_key_press(key) {
             self.sender
                 .send(SoundAction::ReplaceQueue(
                     self.videos.iter().skip(*v).cloned().collect(),
@@ -60,36 +219,49 @@ impl Screen for PlaylistView {
                 .unwrap();
             return EventResponse::Message(vec![ManagerMessage::PlayerFrom(Screens::Playlist)]);
         }
+        if filtering {
+            return EventResponse::None;
+        }
+        match BINDINGS.action(&key) {
+            Some(Action::Search) => {
+                return ManagerMessage::SearchFrom(Screens::PlaylistViewer).event()
+            }
+            Some(Action::Settings) => {
+                return ManagerMessage::SettingsFrom(Screens::PlaylistViewer).event()
+            }
+            Some(Action::Help) => return ManagerMessage::HelpFrom(Screens::PlaylistViewer).event(),
+            Some(Action::ContextMenu) => {
+                self.track_menu = self
+                    .items
+                    .select()
+                    .and_then(|PlayListAction(i, _, _)| self.videos.get(*i))
+                    .map(|video| TrackMenu::new(video.clone()));
+                return EventResponse::None;
+            }
+            _ => {}
+        }
         match key.code {
             KeyCode::Esc => ManagerMessage::ChangeState(self.goto).event(),
-            KeyCode::Char('f') => ManagerMessage::SearchFrom(Screens::PlaylistViewer).event(),
             _ => EventResponse::None,
         }
     }
 
     fn render(&mut self, frame: &mut Frame) {
         frame.render_widget(&self.items, frame.size());
+        if let Some(menu) = &self.track_menu {
+            menu.render(frame, frame.size());
+        }
     }
 
     fn handle_global_message(&mut self, m: ManagerMessage) -> EventResponse {
         match m {
             ManagerMessage::Inspect(a, screen, m) => {
-                self.items.set_title(format!(" Inspecting {a} "));
+                self.name = a;
+                self.items.set_filter(None);
                 self.goto = screen;
-                let db = DATABASE.read().unwrap();
-                self.items.update(
-                    m.iter()
-                        .enumerate()
-                        .map(|(i, m)| {
-                            (
-                                format!("  {m}"),
-                                PlayListAction(i, !db.iter().any(|x| x.video_id == m.video_id)),
-                            )
-                        })
-                        .collect(),
-                    0,
-                );
-                self.videos = m;
+                self.inspected = m;
+                self.refresh();
+                self.items.select_to(0);
 
                 EventResponse::Message(vec![ManagerMessage::ChangeState(Screens::PlaylistViewer)])
             }
@@ -98,6 +270,7 @@ impl Screen for PlaylistView {
     }
 
     fn close(&mut self, _: Screens) -> EventResponse {
+        self.track_menu = None;
         EventResponse::None
     }
 
@@ -10,16 +10,24 @@ use ratatui::{
     Frame,
 };
 use tokio::task::JoinHandle;
-use ytpapi2::{HeaderMap, HeaderValue, SearchResults, YoutubeMusicInstance, YoutubeMusicPlaylistRef, YoutubeMusicVideoRef};
+use ytpapi2::{ArtistRef, SearchResults, YoutubeMusicPlaylistRef, YoutubeMusicVideoRef};
 
 use crate::{
-    consts::CONFIG, get_header_file, run_service, structures::sound_action::SoundAction, tasks, try_get_cookies, utils::invert, DATABASE
+    consts::CONFIG,
+    fuzzy_search, run_service,
+    structures::sound_action::SoundAction,
+    tasks::{self, api::API},
+    utils::invert,
+    DATABASE,
 };
 
 use super::{
+    context_menu::MenuEvent,
     item_list::{ListItem, ListItemAction},
     playlist::format_playlist,
-    split_y_start, EventResponse, ManagerMessage, Screen, Screens,
+    split_y_start,
+    track_menu::{apply_track_action, TrackMenu},
+    EventResponse, ManagerMessage, Screen, Screens, NOTIFICATION_DURATION,
 };
 
 pub struct Search {
@@ -27,21 +35,30 @@ pub struct Search {
     pub goto: Screens,
     pub list: Arc<RwLock<ListItem<Status>>>,
     pub search_handle: Option<JoinHandle<()>>,
-    pub api: Option<Arc<YoutubeMusicInstance>>,
     pub action_sender: Sender<SoundAction>,
+    /// The menu of the selected result opened with `Tab`, `c` being part of the search
+    pub track_menu: Option<TrackMenu>,
 }
 #[derive(Clone, Debug, PartialEq)]
 pub enum Status {
     Local(YoutubeMusicVideoRef),
+    /// A downloaded song matching the search with its score
+    LocalMatch(YoutubeMusicVideoRef, f32),
     Unknown(YoutubeMusicVideoRef),
     PlayList(YoutubeMusicPlaylistRef, Vec<YoutubeMusicVideoRef>),
+    /// Opens the albums and singles of the artist
+    Artist(ArtistRef),
+    /// Opens the album view, its tracks are used for the download ratio
+    Album(YoutubeMusicPlaylistRef, Vec<YoutubeMusicVideoRef>),
 }
 impl ListItemAction for Status {
     fn render_style(&self, _: &str, selected: bool) -> Style {
         let k = match self {
-            Self::Local(_) => CONFIG.player.text_next_style,
+            Self::Local(_) | Self::LocalMatch(_, _) => CONFIG.player.text_next_style,
             Self::Unknown(_) => CONFIG.player.text_downloading_style,
-            Self::PlayList(_, _) => CONFIG.player.text_next_style,
+            Self::PlayList(_, _) | Self::Artist(_) | Self::Album(_, _) => {
+                CONFIG.player.text_next_style
+            }
         };
         if selected {
             invert(k)
@@ -49,6 +66,13 @@ impl ListItemAction for Status {
             k
         }
     }
+
+    fn render_suffix(&self) -> Option<String> {
+        match self {
+            Self::LocalMatch(_, score) => Some(format!("{score:.2}")),
+            _ => None,
+        }
+    }
 }
 
 impl Screen for Search {
@@ -57,6 +81,9 @@ impl Screen for Search {
         mouse_event: crossterm::event::MouseEvent,
         frame_data: &Rect,
     ) -> EventResponse {
+        if self.track_menu.is_some() {
+            return EventResponse::None;
+        }
         let splitted = split_y_start(*frame_data, 3);
         if let Some(e) = self
             .list
@@ -71,11 +98,43 @@ impl Screen for Search {
     }
 
     fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
+        if let Some(menu) = &mut self.track_menu {
+            return match menu.on_key_press(key) {
+                MenuEvent::Chosen(action) => {
+                    let video = menu.video().clone();
+                    self.track_menu = None;
+                    apply_track_action(action, video, &self.action_sender)
+                }
+                MenuEvent::Closed => {
+                    self.track_menu = None;
+                    EventResponse::None
+                }
+                MenuEvent::None => EventResponse::None,
+            };
+        }
+        if key.code == KeyCode::Tab {
+            self.track_menu = match self.list.read().unwrap().select() {
+                Some(Status::Local(e) | Status::LocalMatch(e, _) | Status::Unknown(e)) => {
+                    Some(TrackMenu::new(e.clone()))
+                }
+                _ => None,
+            };
+            return EventResponse::None;
+        }
         if KeyCode::Esc == key.code {
             return ManagerMessage::ChangeState(self.goto).event();
         }
-        if let Some(e) = self.list.write().unwrap().on_key_press(key) {
-            return self.execute_status(e.clone(), key.modifiers);
+        if key.code == KeyCode::Enter && !self.list.read().unwrap().selected_indices().is_empty() {
+            return self.download_selection();
+        }
+        // Space toggles the selection only with Ctrl as it is part of the search
+        if key.code != KeyCode::Char(' ') || key.modifiers.contains(KeyModifiers::CONTROL) {
+            if let Some(e) = self.list.write().unwrap().on_key_press(key) {
+                return self.execute_status(e.clone(), key.modifiers);
+            }
+        }
+        if key.code == KeyCode::Char(' ') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return EventResponse::None;
         }
         let textbefore = self.text.trim().to_owned();
         match key.code {
@@ -95,83 +154,122 @@ impl Screen for Search {
             handle.abort();
         }
 
-        let text = self.text.to_lowercase();
-
-        let local = DATABASE
-            .read()
-            .unwrap()
-            .iter()
-            .filter(|x| {
-                x.title.to_lowercase().contains(&text) || x.author.to_lowercase().contains(&text)
-            })
-            .cloned()
-            .map(|video| (format!(" {video} "), Status::Local(video)))
-            .take(100)
-            .collect::<Vec<_>>();
-        self.list.write().unwrap().update_contents(local.clone());
-
-        if let Some(api) = self.api.clone() {
-            let text = self.text.clone();
-            let items = self.list.clone();
-            self.search_handle = Some(run_service(async move {
+        let text = self.text.clone();
+        let items = self.list.clone();
+        let api = API.load_full();
+        self.search_handle = Some(run_service(async move {
+            // The downloaded songs are shown right away while YouTube Music is searched
+            let local = async {
+                let text = text.clone();
+                let local = tokio::task::spawn_blocking(move || local_results(&text))
+                    .await
+                    .unwrap_or_default();
+                items.write().unwrap().update_contents(local.clone());
+                local
+            };
+            let online = async {
+                let api = api?;
                 // Sleep to prevent spamming the api
                 tokio::time::sleep(std::time::Duration::from_millis(300)).await;
-                let mut item = Vec::new();
                 match api
                     .search(&text.replace('\\', "\\\\").replace('\"', "\\\""), 0)
                     .await
                 {
-                    Ok(SearchResults {
-                        videos: e,
-                        playlists: p,
-                    }) => {
-                        for video in e.into_iter() {
-                            let id = video.video_id.clone();
-                            item.push((
-                                format!(" {video} "),
-                                if DATABASE.read().unwrap().iter().any(|x| x.video_id == id) {
-                                    Status::Local(video)
-                                } else {
-                                    Status::Unknown(video)
-                                },
-                            ));
-                        }
-                        for playlist in p.into_iter() {
-                            let api = api.clone();
-                            let items = items.clone();
-                            run_service(async move {
-                                match api.get_playlist(&playlist, 0).await {
-                                    Ok(e) => {
-                                        if e.is_empty() {
-                                            return;
-                                        }
-                                        items.write().unwrap().add_element((
-                                            format_playlist(
-                                                &format!(
-                                                    " [P] {} ({})",
-                                                    playlist.name, playlist.subtitle
-                                                ),
-                                                &e,
-                                            ),
-                                            Status::PlayList(playlist, e),
-                                        ));
-                                    }
-                                    Err(e) => {
-                                        error!("{e:?}");
-                                    }
-                                };
-                            });
-                        }
-                    }
+                    Ok(results) => Some((api, results)),
                     Err(e) => {
                         error!("{e:?}");
+                        None
                     }
                 }
-                let mut local = local;
-                local.append(&mut item);
-                items.write().unwrap().update_contents(local);
-            }));
-        }
+            };
+            let (mut results, online) = tokio::join!(local, online);
+            let Some((
+                api,
+                SearchResults {
+                    videos,
+                    playlists,
+                    artists,
+                    albums,
+                },
+            )) = online
+            else {
+                return;
+            };
+            for video in videos {
+                // A result already found locally is kept once, as downloaded
+                if let Some((_, status)) = results.iter_mut().find(|(_, status)| {
+                    matches!(status, Status::LocalMatch(e, _) if e.video_id == video.video_id)
+                }) {
+                    *status = Status::Local(video);
+                    continue;
+                }
+                let id = video.video_id.clone();
+                results.push((
+                    format!(" {video} "),
+                    if DATABASE.read().unwrap().iter().any(|x| x.video_id == id) {
+                        Status::Local(video)
+                    } else {
+                        Status::Unknown(video)
+                    },
+                ));
+            }
+            items.write().unwrap().update_contents(results);
+            for playlist in playlists.into_iter() {
+                let api = api.clone();
+                let items = items.clone();
+                run_service(async move {
+                    match api.get_playlist(&playlist, 0).await {
+                        Ok(e) => {
+                            add_collection(
+                                &items,
+                                format!(" [P] {} ({})", playlist.name, playlist.subtitle),
+                                playlist,
+                                e,
+                                Status::PlayList,
+                            );
+                        }
+                        Err(e) => {
+                            error!("{e:?}");
+                        }
+                    };
+                });
+            }
+            for artist in artists {
+                let name = match &artist.subscriber_count {
+                    Some(count) => format!(" [@] {} ({count})", artist.name),
+                    None => format!(" [@] {}", artist.name),
+                };
+                items
+                    .write()
+                    .unwrap()
+                    .add_element((name, Status::Artist(artist)));
+            }
+            for album in albums {
+                let api = api.clone();
+                let items = items.clone();
+                run_service(async move {
+                    match api.get_album(&album.browse_id, 0).await {
+                        Ok(details) => {
+                            let name = match album.year {
+                                Some(year) => {
+                                    format!(" [A] {} - {} ({year})", album.title, album.artist)
+                                }
+                                None => format!(" [A] {} - {}", album.title, album.artist),
+                            };
+                            let playlist = YoutubeMusicPlaylistRef {
+                                name: album.title,
+                                subtitle: album.artist,
+                                browse_id: album.browse_id,
+                            };
+                            add_collection(&items, name, playlist, details.tracks, Status::Album);
+                        }
+                        Err(e) => {
+                            error!("{e:?}");
+                        }
+                    };
+                });
+            }
+        }));
 
         EventResponse::None
     }
@@ -194,6 +292,9 @@ impl Screen for Search {
         //  Select the playlist to play
         let items = self.list.read().unwrap();
         frame.render_widget(&*items, splitted[1]);
+        if let Some(menu) = &self.track_menu {
+            menu.render(frame, splitted[1]);
+        }
     }
 
     fn handle_global_message(&mut self, _: super::ManagerMessage) -> EventResponse {
@@ -201,6 +302,7 @@ impl Screen for Search {
     }
 
     fn close(&mut self, _: Screens) -> EventResponse {
+        self.track_menu = None;
         EventResponse::None
     }
 
@@ -208,8 +310,33 @@ impl Screen for Search {
         EventResponse::None
     }
 }
+/// Adds a playlist, an artist or an album of the results, the empty ones are skipped
+fn add_collection(
+    items: &RwLock<ListItem<Status>>,
+    name: String,
+    playlist: YoutubeMusicPlaylistRef,
+    videos: Vec<YoutubeMusicVideoRef>,
+    status: fn(YoutubeMusicPlaylistRef, Vec<YoutubeMusicVideoRef>) -> Status,
+) {
+    if videos.is_empty() {
+        return;
+    }
+    items
+        .write()
+        .unwrap()
+        .add_element((format_playlist(&name, &videos), status(playlist, videos)));
+}
+/// The downloaded songs matching the search
+fn local_results(text: &str) -> Vec<(String, Status)> {
+    fuzzy_search(text, 100)
+        .into_iter()
+        .filter(|(_, score)| *score >= CONFIG.search.min_score)
+        .map(|(video, score)| (format!(" {video} "), Status::LocalMatch(video, score)))
+        .collect()
+}
+
 impl Search {
-    pub async fn new(action_sender: Sender<SoundAction>) -> Self {
+    pub fn new(action_sender: Sender<SoundAction>) -> Self {
         Self {
             text: String::new(),
             list: Arc::new(RwLock::new(ListItem::new(
@@ -217,42 +344,53 @@ impl Search {
             ))),
             goto: Screens::MusicPlayer,
             search_handle: None,
-            api: if let Some(cookies) = try_get_cookies() {
-                let mut headermap = HeaderMap::new();
-                headermap.insert(
-                    "cookie",
-                    HeaderValue::from_str(&cookies).unwrap(),
-                );
-                headermap.insert(
-                    "user-agent",
-                    HeaderValue::from_static("Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0"),
-                );
-                YoutubeMusicInstance::new(headermap).await
-            } else {
-                YoutubeMusicInstance::from_header_file(get_header_file().unwrap().1.as_path()).await
-            }
-                .ok()
-                .map(Arc::new),
+            track_menu: None,
             action_sender,
         }
     }
 
+    /// Downloads all the selected songs
+    fn download_selection(&self) -> EventResponse {
+        let mut list = self.list.write().unwrap();
+        let videos = list
+            .selected_actions()
+            .into_iter()
+            .filter_map(|status| match status {
+                Status::Local(e) | Status::LocalMatch(e, _) | Status::Unknown(e) => Some(e.clone()),
+                Status::PlayList(_, _) | Status::Artist(_) | Status::Album(_, _) => None,
+            })
+            .collect::<Vec<_>>();
+        list.clear_selection();
+        let message = format!("Downloading {} songs", videos.len());
+        for video in videos {
+            tasks::download::start_task_unary(self.action_sender.clone(), video);
+        }
+        ManagerMessage::Notify(message, NOTIFICATION_DURATION).event()
+    }
+
     pub fn execute_status(&self, e: Status, modifiers: KeyModifiers) -> EventResponse {
         match e {
-            Status::Local(e) | Status::Unknown(e) => {
+            Status::Local(e) | Status::LocalMatch(e, _) | Status::Unknown(e) => {
+                // With Ctrl the song is played after the current one and the search stays open
+                if modifiers.contains(KeyModifiers::CONTROL) {
+                    self.action_sender
+                        .send(SoundAction::QueueNext(e.clone()))
+                        .unwrap();
+                    let message = format!("Playing {} next", e.title);
+                    tasks::download::start_task_unary(self.action_sender.clone(), e);
+                    return ManagerMessage::Notify(message, NOTIFICATION_DURATION).event();
+                }
                 self.action_sender
                     .send(SoundAction::AddVideoUnary(e.clone()))
                     .unwrap();
                 tasks::download::start_task_unary(self.action_sender.clone(), e);
-                if modifiers.contains(KeyModifiers::CONTROL) {
-                    EventResponse::None
-                } else {
-                    ManagerMessage::PlayerFrom(Screens::Playlist).event()
-                }
+                ManagerMessage::PlayerFrom(Screens::Playlist).event()
             }
             Status::PlayList(e, v) => ManagerMessage::Inspect(e.name, Screens::Search, v)
                 .pass_to(Screens::PlaylistViewer)
                 .event(),
+            Status::Artist(artist) => ManagerMessage::BrowseArtist(artist).event(),
+            Status::Album(album, _) => ManagerMessage::BrowseAlbum(album.browse_id).event(),
         }
     }
 }
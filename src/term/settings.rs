@@ -0,0 +1,444 @@
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{KeyCode, KeyEvent, MouseEvent, MouseEventKind};
+use flume::Sender;
+use player::source::EQ_MAX_GAIN;
+use ratatui::{
+    layout::Rect,
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+use crate::{
+    config::{Config, CROSSFADE_RANGE, DOWNLOAD_RETRIES_RANGE, SEEK_STEP_RANGE, VOLUME_RANGE},
+    consts::CONFIG,
+    structures::{
+        keybindings::{Action, BINDINGS},
+        persistence::load_player_state,
+        repeat_mode::RepeatMode,
+        sound_action::SoundAction,
+    },
+    systems::player::PlayerState,
+    utils::invert,
+};
+
+use super::{playlist::SHUFFLE, EventResponse, ManagerMessage, Screen, Screens};
+
+/// What a setting changes when it is edited
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Volume,
+    RepeatMode,
+    Shuffle,
+    SeekStep,
+    SleepTimer,
+    Crossfade,
+    Gapless,
+    Normalize,
+    Autoplay,
+    VolumeSlider,
+    AlbumArt,
+    DownloadRetries,
+    /// A band of the equalizer, from `EQ_FREQUENCIES`
+    EqBand(usize),
+}
+
+/// The labels of the bands of the equalizer
+const EQ_LABELS: [&str; 10] = [
+    "Equalizer 31 Hz (dB)",
+    "Equalizer 62 Hz (dB)",
+    "Equalizer 125 Hz (dB)",
+    "Equalizer 250 Hz (dB)",
+    "Equalizer 500 Hz (dB)",
+    "Equalizer 1 kHz (dB)",
+    "Equalizer 2 kHz (dB)",
+    "Equalizer 4 kHz (dB)",
+    "Equalizer 8 kHz (dB)",
+    "Equalizer 16 kHz (dB)",
+];
+
+impl Field {
+    const ALL: [Self; 22] = [
+        Self::Volume,
+        Self::RepeatMode,
+        Self::Shuffle,
+        Self::SeekStep,
+        Self::SleepTimer,
+        Self::Crossfade,
+        Self::Gapless,
+        Self::Normalize,
+        Self::Autoplay,
+        Self::VolumeSlider,
+        Self::AlbumArt,
+        Self::DownloadRetries,
+        Self::EqBand(0),
+        Self::EqBand(1),
+        Self::EqBand(2),
+        Self::EqBand(3),
+        Self::EqBand(4),
+        Self::EqBand(5),
+        Self::EqBand(6),
+        Self::EqBand(7),
+        Self::EqBand(8),
+        Self::EqBand(9),
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Volume => "Volume",
+            Self::RepeatMode => "Repeat mode (none, all, one)",
+            Self::Shuffle => "Shuffle local musics",
+            Self::SeekStep => "Seek step (seconds)",
+            Self::SleepTimer => "Sleep timer (minutes, 0 to disable)",
+            Self::Crossfade => "Crossfade (seconds)",
+            Self::Gapless => "Gapless playback",
+            Self::Normalize => "Loudness normalization",
+            Self::Autoplay => "Autoplay when the queue ends",
+            Self::VolumeSlider => "Volume slider",
+            Self::AlbumArt => "Album art",
+            Self::DownloadRetries => "Download retries",
+            Self::EqBand(band) => EQ_LABELS[band],
+        }
+    }
+
+    /// Settings only read on startup
+    fn needs_restart(self) -> bool {
+        !matches!(
+            self,
+            Self::Volume
+                | Self::RepeatMode
+                | Self::Shuffle
+                | Self::SeekStep
+                | Self::SleepTimer
+                | Self::EqBand(_)
+        )
+    }
+
+    /// The section and key of the setting in the config file
+    fn config_key(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Self::Volume => Some(("player", "initial_volume")),
+            Self::Shuffle => Some(("player", "shuffle")),
+            Self::SeekStep => Some(("playback", "seek_step_secs")),
+            Self::Crossfade => Some(("player", "crossfade_secs")),
+            Self::Gapless => Some(("playback", "gapless")),
+            Self::Normalize => Some(("playback", "normalize")),
+            Self::Autoplay => Some(("playback", "autoplay")),
+            Self::VolumeSlider => Some(("player", "volume_slider")),
+            Self::AlbumArt => Some(("ui", "album_art")),
+            Self::DownloadRetries => Some(("network", "download_retries")),
+            // Saved by the player
+            Self::RepeatMode | Self::SleepTimer => None,
+            // Saved with the other bands
+            Self::EqBand(_) => None,
+        }
+    }
+
+    fn is_bool(self) -> bool {
+        matches!(
+            self,
+            Self::Shuffle
+                | Self::Gapless
+                | Self::Normalize
+                | Self::Autoplay
+                | Self::VolumeSlider
+                | Self::AlbumArt
+        )
+    }
+}
+
+/// A parsed value of a setting
+enum Value {
+    Bool(bool),
+    Number(u8),
+    Repeat(RepeatMode),
+    /// A gain in dB, read again from the text of all the bands
+    Gain,
+}
+
+impl Value {
+    fn parse(field: Field, text: &str) -> Option<Self> {
+        let text = text.trim();
+        if field == Field::RepeatMode {
+            return RepeatMode::from_name(text).map(Self::Repeat);
+        }
+        if field.is_bool() {
+            return text.parse().ok().map(Self::Bool);
+        }
+        if let Field::EqBand(_) = field {
+            return text
+                .parse::<f32>()
+                .ok()
+                .filter(|x| (-EQ_MAX_GAIN..=EQ_MAX_GAIN).contains(x))
+                .map(|_| Self::Gain);
+        }
+        let number = text.parse::<u8>().ok()?;
+        // The same ranges as the config so the saved values don't stop the next start
+        let range = match field {
+            Field::Volume => VOLUME_RANGE,
+            Field::Crossfade => CROSSFADE_RANGE,
+            Field::SeekStep => SEEK_STEP_RANGE,
+            Field::DownloadRetries => DOWNLOAD_RETRIES_RANGE,
+            _ => 0..=u8::MAX,
+        };
+        range.contains(&number).then_some(Self::Number(number))
+    }
+}
+
+/// Edits the settings, the live ones are applied right away and all are saved to the config
+pub struct Settings {
+    pub goto: Screens,
+    sender: Sender<SoundAction>,
+    values: Vec<(Field, String)>,
+    selected: usize,
+    /// The text of the value being edited
+    editing: Option<String>,
+    error: Option<String>,
+}
+
+impl Settings {
+    pub fn new(sender: Sender<SoundAction>) -> Self {
+        let values = Field::ALL
+            .into_iter()
+            .map(|field| {
+                let value = match field {
+                    Field::Volume => CONFIG.player.initial_volume.to_string(),
+                    Field::RepeatMode => load_player_state().repeat_mode.name().to_owned(),
+                    Field::Shuffle => SHUFFLE.load(Ordering::SeqCst).to_string(),
+                    Field::SeekStep => CONFIG.playback.seek_step_secs.to_string(),
+                    Field::SleepTimer => "0".to_owned(),
+                    Field::Crossfade => CONFIG.player.crossfade_secs.to_string(),
+                    Field::Gapless => CONFIG.playback.gapless.to_string(),
+                    Field::Normalize => CONFIG.playback.normalize.to_string(),
+                    Field::Autoplay => CONFIG.playback.autoplay.to_string(),
+                    Field::VolumeSlider => CONFIG.player.volume_slider.to_string(),
+                    Field::AlbumArt => CONFIG.ui.album_art.to_string(),
+                    Field::DownloadRetries => CONFIG.network.download_retries.to_string(),
+                    Field::EqBand(band) => CONFIG.eq.bands[band].to_string(),
+                };
+                (field, value)
+            })
+            .collect();
+        Self {
+            goto: Screens::MusicPlayer,
+            sender,
+            values,
+            selected: 0,
+            editing: None,
+            error: None,
+        }
+    }
+
+    /// Reads the current values of the live settings from the player
+    pub fn refresh(&mut self, player: &PlayerState) {
+        for (field, value) in &mut self.values {
+            match field {
+                Field::Volume => *value = player.sink.volume_percent().to_string(),
+                Field::RepeatMode => *value = player.repeat_mode.name().to_owned(),
+                Field::SeekStep => *value = player.sink.seek_step_secs().to_string(),
+                Field::SleepTimer => {
+                    let remaining = player
+                        .sleep_until
+                        .map(|x| x.saturating_duration_since(Instant::now()))
+                        .unwrap_or_default();
+                    *value = remaining.as_secs().div_ceil(60).to_string();
+                }
+                Field::EqBand(band) => *value = player.sink.eq_bands()[*band].to_string(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Applies the edited value of the selected setting and saves it
+    fn confirm(&mut self, text: String) {
+        let Some((field, value)) = self.values.get_mut(self.selected) else {
+            return;
+        };
+        let field = *field;
+        let Some(parsed) = Value::parse(field, &text) else {
+            self.error = Some(format!(
+                "Invalid value `{}` for {}",
+                text.trim(),
+                field.label()
+            ));
+            return;
+        };
+        text.trim().clone_into(value);
+        let action = match (field, &parsed) {
+            (Field::Volume, Value::Number(x)) => {
+                Some(SoundAction::FadeVolumeTo(*x, Duration::from_millis(200)))
+            }
+            (Field::RepeatMode, Value::Repeat(mode)) => Some(SoundAction::SetRepeat(*mode)),
+            (Field::SeekStep, Value::Number(x)) => Some(SoundAction::SetSeekStep(*x)),
+            (Field::SleepTimer, Value::Number(0)) => Some(SoundAction::ClearSleepTimer),
+            (Field::SleepTimer, Value::Number(x)) => Some(SoundAction::SetSleepTimer(
+                Duration::from_secs(u64::from(*x) * 60),
+            )),
+            (Field::Shuffle, Value::Bool(x)) => {
+                SHUFFLE.store(*x, Ordering::SeqCst);
+                None
+            }
+            (Field::EqBand(_), Value::Gain) => {
+                self.apply_eq_bands();
+                None
+            }
+            _ => None,
+        };
+        if let Some(action) = action {
+            self.sender.send(action).unwrap();
+        }
+        if let Some((section, key)) = field.config_key() {
+            let result = match parsed {
+                Value::Bool(x) => Config::save_value(section, key, x),
+                Value::Number(x) => Config::save_value(section, key, i64::from(x)),
+                Value::Repeat(_) | Value::Gain => Ok(()),
+            };
+            self.error = result.err();
+        }
+    }
+
+    /// Sends the gains of the equalizer to the player and saves them
+    fn apply_eq_bands(&mut self) {
+        let mut bands = [0.0; 10];
+        for (field, value) in &self.values {
+            if let Field::EqBand(band) = field {
+                bands[*band] = value.parse().unwrap_or_default();
+            }
+        }
+        self.sender.send(SoundAction::SetEqBands(bands)).unwrap();
+        let array = bands
+            .iter()
+            .map(|x| f64::from(*x))
+            .collect::<toml_edit::Array>();
+        self.error = Config::save_value("eq", "bands", array).err();
+    }
+
+    /// The number of rows hidden above, so the selected one stays visible in `height` rows
+    fn scroll(&self, height: u16) -> usize {
+        self.selected
+            .saturating_sub(usize::from(height.saturating_sub(1)))
+    }
+
+    /// Changes the selected band of the equalizer by `step` dB, heard right away
+    fn adjust_eq_band(&mut self, step: f32) {
+        let Some((Field::EqBand(_), value)) = self.values.get_mut(self.selected) else {
+            return;
+        };
+        let gain = value.parse::<f32>().unwrap_or_default() + step;
+        *value = gain.clamp(-EQ_MAX_GAIN, EQ_MAX_GAIN).to_string();
+        self.error = None;
+        self.apply_eq_bands();
+    }
+}
+
+impl Screen for Settings {
+    fn on_mouse_press(&mut self, e: MouseEvent, frame_data: &Rect) -> EventResponse {
+        match e.kind {
+            MouseEventKind::ScrollUp => self.selected = self.selected.saturating_sub(1),
+            MouseEventKind::ScrollDown => {
+                self.selected = (self.selected + 1).min(self.values.len() - 1)
+            }
+            MouseEventKind::Down(_) if self.editing.is_none() => {
+                let row = e.row.saturating_sub(frame_data.y + 1) as usize
+                    + self.scroll(frame_data.height.saturating_sub(2));
+                if row < self.values.len() {
+                    self.selected = row;
+                }
+            }
+            _ => {}
+        }
+        EventResponse::None
+    }
+
+    fn on_key_press(&mut self, key: KeyEvent, _: &Rect) -> EventResponse {
+        if let Some(text) = &mut self.editing {
+            match key.code {
+                KeyCode::Esc => self.editing = None,
+                KeyCode::Enter => {
+                    let text = std::mem::take(text);
+                    self.editing = None;
+                    self.confirm(text);
+                }
+                KeyCode::Backspace | KeyCode::Delete => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                _ => {}
+            }
+            return EventResponse::None;
+        }
+        if BINDINGS.action(&key) == Some(Action::Help) {
+            return ManagerMessage::HelpFrom(Screens::Settings).event();
+        }
+        match key.code {
+            KeyCode::Esc => return ManagerMessage::ChangeState(self.goto).event(),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.values.len() - 1)
+            }
+            KeyCode::Enter => {
+                self.error = None;
+                self.editing = self.values.get(self.selected).map(|(_, x)| x.clone());
+            }
+            KeyCode::Left | KeyCode::Char('h') => self.adjust_eq_band(-1.0),
+            KeyCode::Right | KeyCode::Char('l') => self.adjust_eq_band(1.0),
+            _ => {}
+        }
+        EventResponse::None
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let area = frame.size();
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .style(CONFIG.player.text_next_style)
+            .title(" Settings (Enter to edit, Left/Right for the equalizer, * needs a restart) ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let scroll = self.scroll(inner.height);
+        for (row, (field, value)) in self
+            .values
+            .iter()
+            .enumerate()
+            .skip(scroll)
+            .take(inner.height as usize)
+        {
+            let line = Rect::new(inner.x, inner.y + (row - scroll) as u16, inner.width, 1);
+            let marker = if field.needs_restart() { '*' } else { ' ' };
+            let (value, style) = match &self.editing {
+                Some(text) if row == self.selected => {
+                    (format!("{text}▏"), CONFIG.player.text_searching_style)
+                }
+                _ if row == self.selected => (value.clone(), invert(CONFIG.player.text_next_style)),
+                _ => (value.clone(), CONFIG.player.text_next_style),
+            };
+            frame.render_widget(
+                Paragraph::new(format!("{marker} {}: {value}", field.label())).style(style),
+                line,
+            );
+        }
+        if let Some(error) = &self.error {
+            let row = (self.values.len() as u16 + 1).min(inner.height.saturating_sub(1));
+            frame.render_widget(
+                Paragraph::new(error.as_str()).style(CONFIG.player.text_error_style),
+                Rect::new(inner.x, inner.y + row, inner.width, 1),
+            );
+        }
+    }
+
+    fn handle_global_message(&mut self, _: ManagerMessage) -> EventResponse {
+        EventResponse::None
+    }
+
+    fn close(&mut self, _: Screens) -> EventResponse {
+        self.editing = None;
+        EventResponse::None
+    }
+
+    fn open(&mut self) -> EventResponse {
+        EventResponse::None
+    }
+}
@@ -0,0 +1,220 @@
+use crossterm::event::KeyEvent;
+use flume::Sender;
+use ratatui::{layout::Rect, Frame};
+use ytpapi2::{LikeStatus, YoutubeMusicPlaylistRef, YoutubeMusicVideoRef};
+
+use crate::{
+    database::{
+        add_to_local_playlist, create_local_playlist, list_local_playlists, LocalPlaylistId,
+    },
+    structures::sound_action::SoundAction,
+    tasks::{
+        api::{LIBRARY_PLAYLISTS, LIKE_STATUSES},
+        download::start_task_unary,
+    },
+};
+
+use super::{
+    context_menu::{ContextMenu, MenuEvent},
+    EventResponse, ManagerMessage, NOTIFICATION_DURATION,
+};
+
+/// The local playlist toggled with the `a` key, offered by the menu when there is no playlist
+pub const FAVORITES_PLAYLIST: &str = "Favorites";
+
+/// The options of the context menu of a track
+#[derive(Clone)]
+pub enum TrackAction {
+    PlayNow,
+    /// Adds the track right after the playing one
+    PlayNext,
+    AddToQueue,
+    /// Opens the list of the local playlists
+    ChoosePlaylist,
+    /// Adds the track to a playlist, it is created with the name if there is no id
+    AddToPlaylist(Option<LocalPlaylistId>, String),
+    /// Opens the list of the playlists of the YouTube Music library
+    ChooseYouTubePlaylist,
+    /// Adds the track to a playlist of the YouTube Music library
+    SaveToYouTubePlaylist(YoutubeMusicPlaylistRef),
+    Like,
+    Unlike,
+    Download,
+    RemoveFromDatabase,
+    CopyUrl,
+}
+
+/// The context menu of a track opened with `c`
+pub struct TrackMenu {
+    menu: ContextMenu<TrackAction>,
+    video: YoutubeMusicVideoRef,
+}
+
+impl TrackMenu {
+    pub fn new(video: YoutubeMusicVideoRef) -> Self {
+        let like_status = LIKE_STATUSES
+            .lock()
+            .unwrap()
+            .get(&video.video_id)
+            .copied()
+            .or(video.like_status);
+        let like = if like_status == Some(LikeStatus::Like) {
+            ("Unlike".to_owned(), TrackAction::Unlike)
+        } else {
+            ("Like".to_owned(), TrackAction::Like)
+        };
+        Self {
+            menu: ContextMenu::new(
+                format!(" {} ", video.title),
+                vec![
+                    ("Play now".to_owned(), TrackAction::PlayNow),
+                    ("Play next".to_owned(), TrackAction::PlayNext),
+                    ("Add to queue".to_owned(), TrackAction::AddToQueue),
+                    ("Add to playlist…".to_owned(), TrackAction::ChoosePlaylist),
+                    (
+                        "Save to YouTube Music playlist…".to_owned(),
+                        TrackAction::ChooseYouTubePlaylist,
+                    ),
+                    like,
+                    ("Download".to_owned(), TrackAction::Download),
+                    (
+                        "Remove from database".to_owned(),
+                        TrackAction::RemoveFromDatabase,
+                    ),
+                    ("Copy video URL".to_owned(), TrackAction::CopyUrl),
+                ],
+            ),
+            video,
+        }
+    }
+
+    pub fn video(&self) -> &YoutubeMusicVideoRef {
+        &self.video
+    }
+
+    /// Handles a key, choosing a playlist replaces the menu by the list of the playlists
+    pub fn on_key_press(&mut self, key: KeyEvent) -> MenuEvent<TrackAction> {
+        match self.menu.on_key_press(key) {
+            MenuEvent::Chosen(TrackAction::ChoosePlaylist) => {
+                let mut playlists = list_local_playlists()
+                    .into_iter()
+                    .map(|x| {
+                        (
+                            x.name.clone(),
+                            TrackAction::AddToPlaylist(Some(x.id), x.name),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if playlists.is_empty() {
+                    playlists.push((
+                        format!("{FAVORITES_PLAYLIST} (new)"),
+                        TrackAction::AddToPlaylist(None, FAVORITES_PLAYLIST.to_owned()),
+                    ));
+                }
+                self.menu = ContextMenu::new(" Add to playlist ".to_owned(), playlists);
+                MenuEvent::None
+            }
+            MenuEvent::Chosen(TrackAction::ChooseYouTubePlaylist) => {
+                let playlists = LIBRARY_PLAYLISTS
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|x| {
+                        (
+                            x.name.clone(),
+                            TrackAction::SaveToYouTubePlaylist(x.clone()),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if playlists.is_empty() {
+                    // Closes the menu with a notification
+                    return MenuEvent::Chosen(TrackAction::ChooseYouTubePlaylist);
+                }
+                self.menu = ContextMenu::new(" Save to YouTube Music ".to_owned(), playlists);
+                MenuEvent::None
+            }
+            event => event,
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        self.menu.render(frame, area);
+    }
+}
+
+/// Applies an option of the menu to its track
+pub fn apply_track_action(
+    action: TrackAction,
+    video: YoutubeMusicVideoRef,
+    sender: &Sender<SoundAction>,
+) -> EventResponse {
+    let message = match action {
+        TrackAction::PlayNow => {
+            let message = format!("Playing {}", video.title);
+            sender
+                .send(SoundAction::PlayVideoUnary(video.clone()))
+                .unwrap();
+            start_task_unary(sender.clone(), video);
+            message
+        }
+        TrackAction::PlayNext => {
+            let message = format!("Playing {} next", video.title);
+            sender.send(SoundAction::QueueNext(video.clone())).unwrap();
+            start_task_unary(sender.clone(), video);
+            message
+        }
+        TrackAction::AddToQueue => {
+            let message = format!("Added {} to the queue", video.title);
+            sender
+                .send(SoundAction::AddVideosToQueue(vec![video.clone()]))
+                .unwrap();
+            start_task_unary(sender.clone(), video);
+            message
+        }
+        TrackAction::ChoosePlaylist => return EventResponse::None,
+        TrackAction::ChooseYouTubePlaylist => {
+            "No playlist in your YouTube Music library".to_owned()
+        }
+        TrackAction::SaveToYouTubePlaylist(playlist) => {
+            sender
+                .send(SoundAction::SaveToYouTubePlaylist(
+                    playlist.browse_id,
+                    video.video_id,
+                ))
+                .unwrap();
+            format!("Saving to {}…", playlist.name)
+        }
+        TrackAction::Like | TrackAction::Unlike => {
+            let like = matches!(action, TrackAction::Like);
+            sender
+                .send(SoundAction::SetLike(video.video_id, like))
+                .unwrap();
+            return EventResponse::None;
+        }
+        TrackAction::AddToPlaylist(id, name) => {
+            let id = id.unwrap_or_else(|| create_local_playlist(&name));
+            add_to_local_playlist(id, &video.video_id);
+            // Only the downloaded tracks of a local playlist are played
+            start_task_unary(sender.clone(), video);
+            format!("Added to {name}")
+        }
+        TrackAction::Download => {
+            let message = format!("Downloading {}", video.title);
+            start_task_unary(sender.clone(), video);
+            message
+        }
+        TrackAction::RemoveFromDatabase => {
+            sender
+                .send(SoundAction::DeleteVideoUnary(video.clone()))
+                .unwrap();
+            format!("Removed {} from the database", video.title)
+        }
+        TrackAction::CopyUrl => {
+            sender
+                .send(SoundAction::CopyVideoUrl(video.watch_url()))
+                .unwrap();
+            return EventResponse::None;
+        }
+    };
+    ManagerMessage::Notify(message, NOTIFICATION_DURATION).event()
+}
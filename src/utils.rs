@@ -1,5 +1,14 @@
+use std::{borrow::Cow, collections::HashSet, fmt, io::Write, path::Path};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{execute, style::Print};
 use directories::ProjectDirs;
+use log::warn;
 use ratatui::style::{Color, Style};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+use ytpapi2::YoutubeMusicVideoRef;
+
+use crate::{consts::CACHE_DIR, DATABASE};
 
 /// Get directories for the project for config, cache, etc.
 pub fn get_project_dirs() -> Option<ProjectDirs> {
@@ -61,3 +70,408 @@ pub fn color_contrast(color: Color) -> Color {
         Color::Reset => Color::Black,
     }
 }
+
+/// Copies the text with the OSC 52 escape sequence, supported by most terminals even over ssh.
+/// Returns false when the terminal has no clipboard, like the Linux console
+pub fn copy_to_clipboard(text: &str) -> bool {
+    if matches!(std::env::var("TERM").as_deref(), Ok("linux" | "dumb")) {
+        return false;
+    }
+    if let Err(e) = execute!(
+        std::io::stdout(),
+        Print(format!("\x1b]52;c;{}\x07", STANDARD.encode(text)))
+    ) {
+        warn!("Can't copy to the clipboard: {e}");
+        return false;
+    }
+    true
+}
+
+/// Writes the downloaded tracks to an extended M3U playlist, the other ones are skipped
+pub fn export_m3u(tracks: &[YoutubeMusicVideoRef], out: &Path) -> std::io::Result<()> {
+    let db = DATABASE.read().unwrap();
+    let mut file = std::io::BufWriter::new(std::fs::File::create(out)?);
+    writeln!(file, "#EXTM3U")?;
+    for track in tracks {
+        if !db.iter().any(|x| x.video_id == track.video_id) {
+            // Kept as a comment so the track can still be found
+            writeln!(
+                file,
+                "# Not downloaded: {} - {} {}",
+                track.author,
+                track.title,
+                track.watch_url()
+            )?;
+            continue;
+        }
+        let path = CACHE_DIR.join(format!("downloads/{}.mp4", track.video_id));
+        let path = path.canonicalize().unwrap_or(path);
+        writeln!(
+            file,
+            "#EXTINF:{},{} - {}",
+            track.duration_seconds.map_or(-1, |x| x as i64),
+            track.author,
+            track.title
+        )?;
+        writeln!(file, "{}", path.display())?;
+    }
+    file.flush()
+}
+
+/// Reads the track names of the `#EXTINF` lines of an M3U playlist
+pub fn parse_m3u(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("#EXTINF:"))
+        .filter_map(|info| info.split_once(',').map(|(_, name)| name.trim().to_owned()))
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// Whether the root element of the document is an XSPF `<playlist>`
+pub fn is_xspf(content: &str) -> bool {
+    let mut rest = content.trim_start_matches('\u{feff}').trim_start();
+    // Skips the XML declaration, the comments and the doctype
+    while rest.starts_with("<?") || rest.starts_with("<!") {
+        let end = if rest.starts_with("<!--") { "-->" } else { ">" };
+        let Some(position) = rest.find(end) else {
+            return false;
+        };
+        rest = rest[position + end.len()..].trim_start();
+    }
+    rest.strip_prefix("<playlist")
+        .is_some_and(|x| x.starts_with(|c: char| c.is_whitespace() || c == '>'))
+}
+
+/// Reads the tracks of an XSPF playlist as `creator - title`, or `title` without creator
+pub fn parse_xspf(content: &str) -> Vec<String> {
+    let mut tracks = Vec::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("<track>") {
+        let track = &rest[start + "<track>".len()..];
+        let end = track.find("</track>").unwrap_or(track.len());
+        let (track, next) = track.split_at(end);
+        rest = next;
+        let Some(title) = xml_element_text(track, "title").filter(|x| !x.is_empty()) else {
+            continue;
+        };
+        tracks.push(
+            match xml_element_text(track, "creator").filter(|x| !x.is_empty()) {
+                Some(creator) => format!("{creator} - {title}"),
+                None => title,
+            },
+        );
+    }
+    tracks
+}
+
+/// The unescaped text of the first `<name>` element of an XML fragment
+fn xml_element_text(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{name}>"))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{name}>"))?;
+    let text = xml[start..end].trim();
+    let text = match text
+        .strip_prefix("<![CDATA[")
+        .and_then(|x| x.strip_suffix("]]>"))
+    {
+        Some(data) => return Some(data.to_owned()),
+        None => text,
+    };
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(position) = rest.find('&') {
+        unescaped.push_str(&rest[..position]);
+        rest = &rest[position..];
+        let entity = rest.find(';').map(|x| &rest[1..x]);
+        let character = match entity {
+            Some("amp") => Some('&'),
+            Some("lt") => Some('<'),
+            Some("gt") => Some('>'),
+            Some("quot") => Some('"'),
+            Some("apos") => Some('\''),
+            Some(code) => code
+                .strip_prefix("#x")
+                .map(|x| u32::from_str_radix(x, 16))
+                .or_else(|| code.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+            None => None,
+        };
+        match (character, entity) {
+            (Some(character), Some(entity)) => {
+                unescaped.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    Some(unescaped)
+}
+
+/// Number of single character edits needed to turn `a` into `b`
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Case-insensitive similarity between 0 (nothing in common) and 1 (same strings)
+pub fn similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (a.to_lowercase(), b.to_lowercase());
+    let max_len = a.chars().count().max(b.chars().count());
+    if max_len == 0 {
+        return 1.;
+    }
+    1. - levenshtein(&a, &b) as f32 / max_len as f32
+}
+
+/// The trigrams of the words of a string, padded like PostgreSQL's `pg_trgm`
+pub fn trigrams(value: &str) -> HashSet<[char; 3]> {
+    value
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .flat_map(|word| {
+            let padded = format!("  {word} ").chars().collect::<Vec<_>>();
+            padded
+                .windows(3)
+                .map(|x| [x[0], x[1], x[2]])
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Trigram similarity between 0 and 1: the shared trigrams divided by all the trigrams
+pub fn trigram_similarity(a: &str, b: &str) -> f32 {
+    let (a, b) = (trigrams(a), trigrams(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// Free space always kept on the disk, even when the size of the download is unknown
+pub const MIN_FREE_SPACE: u64 = 50 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum DiskSpaceError {
+    /// The free space couldn't be read
+    Io(std::io::Error),
+    Insufficient {
+        available: u64,
+        required: u64,
+    },
+}
+
+impl fmt::Display for DiskSpaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Can't read the free disk space: {e}"),
+            Self::Insufficient {
+                available,
+                required,
+            } => write!(
+                f,
+                "Not enough disk space ({}MB available, {}MB required)",
+                available / 1024 / 1024,
+                required / 1024 / 1024
+            ),
+        }
+    }
+}
+
+/// Checks that the disk containing `dir` has at least `required_bytes` free, and never less
+/// than `MIN_FREE_SPACE`
+pub fn check_disk_space(dir: &Path, required_bytes: u64) -> Result<(), DiskSpaceError> {
+    let available = free_space(dir).map_err(DiskSpaceError::Io)?;
+    let required = required_bytes.max(MIN_FREE_SPACE);
+    if available < required {
+        return Err(DiskSpaceError::Insufficient {
+            available,
+            required,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn free_space(dir: &Path) -> std::io::Result<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read if the call succeeded
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(windows)]
+fn free_space(dir: &Path) -> std::io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let path = dir
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect::<Vec<_>>();
+    let mut available = 0;
+    // SAFETY: `path` is null terminated and the other pointers are either valid or null
+    if unsafe {
+        GetDiskFreeSpaceExW(
+            path.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    } == 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(available)
+}
+
+/// Writes to a temporary file renamed over `path` so a crash can't leave a truncated file
+pub fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, data)?;
+    std::fs::rename(&tmp_path, path)
+}
+
+/// Formats a duration as `M:SS`, or `H:MM:SS` from an hour
+pub fn format_duration(secs: u64) -> String {
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// Shortens the text to the number of terminal columns with an ellipsis, the wide characters
+/// like the CJK ones taking two columns
+pub fn truncate_display(s: &str, max_cols: u16) -> Cow<'_, str> {
+    let max_cols = usize::from(max_cols);
+    if s.width() <= max_cols {
+        return Cow::Borrowed(s);
+    }
+    let mut width = 0;
+    let mut truncated = String::new();
+    for c in s.chars() {
+        width += c.width().unwrap_or(0);
+        // One column is kept for the ellipsis
+        if width >= max_cols {
+            break;
+        }
+        truncated.push(c);
+    }
+    if max_cols > 0 {
+        truncated.push('…');
+    }
+    Cow::Owned(truncated)
+}
+
+#[test]
+fn format_duration_test() {
+    assert_eq!(format_duration(0), "0:00");
+    assert_eq!(format_duration(59), "0:59");
+    assert_eq!(format_duration(61), "1:01");
+    assert_eq!(format_duration(3599), "59:59");
+    assert_eq!(format_duration(3600), "1:00:00");
+    assert_eq!(format_duration(36061), "10:01:01");
+}
+
+#[test]
+fn truncate_display_test() {
+    assert_eq!(truncate_display("One More Time", 20), "One More Time");
+    assert_eq!(truncate_display("One More Time", 8), "One Mor…");
+    // Each character takes two columns
+    assert_eq!(truncate_display("千と千尋の神隠し", 7), "千と千…");
+    assert_eq!(truncate_display("千と千尋の神隠し", 8), "千と千…");
+    assert_eq!(truncate_display("abc", 0), "");
+}
+
+#[test]
+fn similarity_test() {
+    assert_eq!(levenshtein("kitten", "sitting"), 3);
+    assert_eq!(levenshtein("", "abc"), 3);
+    assert_eq!(
+        similarity("Daft Punk | One More Time", "daft punk | one more time"),
+        1.
+    );
+    assert!(similarity("Daft Punk | One More Time", "Daft Punk | One More Tme") > 0.9);
+    assert!(similarity("Daft Punk | One More Time", "Queen | Bohemian Rhapsody") < 0.5);
+    assert_eq!(
+        parse_m3u("#EXTM3U\n#EXTINF:320,Daft Punk - One More Time\n/music/one.mp3\n"),
+        vec!["Daft Punk - One More Time".to_owned()]
+    );
+}
+
+#[test]
+fn xspf_test() {
+    let playlist = r#"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <trackList>
+    <track>
+      <title>One More Time</title>
+      <creator>Daft Punk</creator>
+    </track>
+    <track><title>Rock &amp; Roll &#x2014; Live</title></track>
+    <track><creator>Nobody</creator></track>
+  </trackList>
+</playlist>"#;
+    assert!(is_xspf(playlist));
+    assert!(!is_xspf("#EXTM3U\n"));
+    assert_eq!(
+        parse_xspf(playlist),
+        vec![
+            "Daft Punk - One More Time".to_owned(),
+            "Rock & Roll — Live".to_owned()
+        ]
+    );
+}
+
+#[test]
+fn trigram_similarity_test() {
+    assert_eq!(
+        trigram_similarity("Bohemian Rhapsody", "bohemian rhapsody"),
+        1.
+    );
+    assert!(trigram_similarity("bohemian rapsody", "Bohemian Rhapsody") > 0.5);
+    assert!(trigram_similarity("bohemian", "One More Time") < 0.1);
+    assert_eq!(trigram_similarity("", ""), 0.);
+}
+
+#[test]
+fn check_disk_space_test() {
+    let dir = std::env::temp_dir();
+    if free_space(&dir).unwrap() >= MIN_FREE_SPACE {
+        assert!(check_disk_space(&dir, 0).is_ok());
+    }
+    assert!(matches!(
+        check_disk_space(&dir, u64::MAX),
+        Err(DiskSpaceError::Insufficient { .. })
+    ));
+    assert!(matches!(
+        check_disk_space(Path::new("/this/path/does/not/exist"), 0),
+        Err(DiskSpaceError::Io(_))
+    ));
+}
@@ -0,0 +1,72 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use log::trace;
+use lru::LruCache;
+
+/// Options of the in-memory cache of browse responses
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheOptions {
+    /// Maximum number of responses kept in memory.
+    /// A capacity of `0` disables the cache.
+    pub capacity: usize,
+    /// How long a cached response stays valid.
+    pub ttl: Duration,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            ttl: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// A LRU cache of raw responses keyed by endpoint and parameter
+#[derive(Clone)]
+pub(crate) struct ResponseCache {
+    entries: Arc<Mutex<LruCache<String, (String, Instant)>>>,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(options: &CacheOptions) -> Option<Self> {
+        Some(Self {
+            entries: Arc::new(Mutex::new(LruCache::new(NonZeroUsize::new(
+                options.capacity,
+            )?))),
+            ttl: options.ttl,
+        })
+    }
+
+    pub(crate) fn key(endpoint_route: &str, endpoint_key: &str, endpoint_param: &str) -> String {
+        format!("{endpoint_route}/{endpoint_key}={endpoint_param}")
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        let (response, captured) = entries.get(key)?;
+        if captured.elapsed() > self.ttl {
+            entries.pop(key);
+            return None;
+        }
+        trace!("Cache hit for {key}");
+        Some(response.clone())
+    }
+
+    pub(crate) fn insert(&self, key: String, response: String) {
+        self.entries
+            .lock()
+            .unwrap()
+            .put(key, (response, Instant::now()));
+    }
+
+    /// Removes all the responses, which may be outdated after an edit of the library
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt::Display;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::YoutubeMusicPlaylistRef;
+use crate::{AlbumRef, ArtistRef, LyricsResult, YoutubeMusicPlaylistRef};
 
 /// Applies recursively the `transformer` function to the given json value
 /// and returns the transformed values.
@@ -47,6 +47,32 @@ pub struct YoutubeMusicVideoRef {
     pub album: String,
     pub video_id: String,
     pub duration: String,
+    #[serde(default)]
+    pub duration_seconds: Option<u32>,
+    #[serde(default)]
+    pub thumbnail_url: Option<String>,
+    #[serde(default)]
+    pub like_status: Option<LikeStatus>,
+    #[serde(default)]
+    pub explicit: bool,
+    /// The release year, when it is in the subtitle of the track
+    #[serde(default)]
+    pub year: Option<u16>,
+}
+
+/// The rating given to a video by the signed in user
+#[derive(Debug, Clone, Copy, PartialOrd, Eq, Ord, PartialEq, Hash, Serialize, Deserialize)]
+pub enum LikeStatus {
+    Like,
+    Indifferent,
+    Dislike,
+}
+
+impl YoutubeMusicVideoRef {
+    /// The page of the video on YouTube Music
+    pub fn watch_url(&self) -> String {
+        format!("https://music.youtube.com/watch?v={}", self.video_id)
+    }
 }
 
 impl Display for YoutubeMusicVideoRef {
@@ -119,6 +145,64 @@ pub fn get_playlist_search(value: &Value) -> Option<YoutubeMusicPlaylistRef> {
     })
 }
 
+/// The `browseId` of a search result and the texts of its columns, split on the dots
+fn get_search_item(value: &Value) -> Option<(&str, Vec<String>)> {
+    let browse_id = value
+        .get("navigationEndpoint")?
+        .get("browseEndpoint")?
+        .get("browseId")?
+        .as_str()?;
+    let texts = value
+        .get("flexColumns")?
+        .as_array()?
+        .iter()
+        .flat_map(|x| x.pointer("/musicResponsiveListItemFlexColumnRenderer/text/runs"))
+        .flat_map(Value::as_array)
+        .flatten()
+        .flat_map(|x| x.get("text").and_then(Value::as_str))
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && *x != "•")
+        .map(str::to_owned)
+        .collect();
+    Some((browse_id, texts))
+}
+
+/// Tries to extract an artist from a search result, its columns are `name`, `"Artist"`
+/// and the number of subscribers
+pub(crate) fn get_artist_ref(value: &Value) -> Option<ArtistRef> {
+    let (channel_id, texts) = get_search_item(value)?;
+    if !channel_id.starts_with("UC") {
+        return None;
+    }
+    Some(ArtistRef {
+        name: texts.first()?.clone(),
+        channel_id: channel_id.to_owned(),
+        thumbnail_url: get_thumbnail_url(value),
+        subscriber_count: texts.iter().find(|x| x.contains("subscriber")).map(|x| {
+            x.replace("subscribers", "")
+                .replace("subscriber", "")
+                .trim()
+                .to_owned()
+        }),
+    })
+}
+
+/// Tries to extract an album from a search result, its columns are `title`, the type of the
+/// album, the artist and the year
+pub(crate) fn get_album_ref(value: &Value) -> Option<AlbumRef> {
+    let (browse_id, texts) = get_search_item(value)?;
+    if !browse_id.starts_with("MPREb_") {
+        return None;
+    }
+    Some(AlbumRef {
+        title: texts.first()?.clone(),
+        artist: texts.get(2).cloned().unwrap_or_default(),
+        year: get_year(value),
+        browse_id: browse_id.to_owned(),
+        thumbnail_url: get_thumbnail_url(value),
+    })
+}
+
 pub fn extract_playlist_info(value: &Value) -> Option<(String, String)> {
     let header = value.get("header")?.get("musicDetailHeaderRenderer")?;
     let title = get_text(header.get("title")?, false, false)?;
@@ -133,6 +217,36 @@ pub fn extract_playlist_info(value: &Value) -> Option<(String, String)> {
     Some((title, subtitles.get(1)?.clone()))
 }
 
+/// Tries to find the copyright line of an album, like `℗ 2001 Daft Life Ltd.`
+pub fn extract_album_label(value: &Value) -> Option<String> {
+    value
+        .get("text")?
+        .as_str()
+        .map(str::trim)
+        .filter(|x| x.starts_with('℗') || x.starts_with('©'))
+        .map(|x| x.to_string())
+}
+
+/// Tries to find the release year in the subtitle of a playlist header.
+pub fn extract_playlist_year(value: &Value) -> Option<u16> {
+    value
+        .get("header")?
+        .get("musicDetailHeaderRenderer")?
+        .get("subtitle")?
+        .get("runs")?
+        .as_array()?
+        .iter()
+        .flat_map(|x| get_text(x, false, false))
+        .find_map(|x| {
+            let x = x.trim();
+            if x.len() == 4 {
+                x.parse().ok()
+            } else {
+                None
+            }
+        })
+}
+
 pub fn get_video_from_album(value: &Value) -> Option<YoutubeMusicVideoRef> {
     let video_id = value
         .get("playlistItemData")
@@ -154,6 +268,127 @@ pub fn get_video_from_album(value: &Value) -> Option<YoutubeMusicVideoRef> {
         album: String::new(),
         video_id: video_id.to_string(),
         duration: String::new(),
+        duration_seconds: get_duration_seconds(value),
+        thumbnail_url: get_thumbnail_url(value),
+        like_status: get_like_status(value),
+        explicit: is_explicit(value),
+        year: None,
+    })
+}
+
+/// A titled row of items on a browse page (`musicShelfRenderer` or `musicCarouselShelfRenderer`)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Shelf {
+    pub(crate) title: String,
+    pub(crate) contents: Value,
+}
+
+/// Tries to extract a shelf and its title from a json value.
+pub(crate) fn get_shelf(value: &Value) -> Option<Shelf> {
+    let (title, contents) = if let Some(shelf) = value.get("musicShelfRenderer") {
+        (shelf.get("title"), shelf.get("contents")?)
+    } else {
+        let shelf = value.get("musicCarouselShelfRenderer")?;
+        (
+            shelf
+                .get("header")
+                .and_then(|x| x.get("musicCarouselShelfBasicHeaderRenderer"))
+                .and_then(|x| x.get("title")),
+            shelf.get("contents")?,
+        )
+    };
+    Some(Shelf {
+        title: title
+            .and_then(|x| get_text(x, false, false))
+            .unwrap_or_default(),
+        contents: contents.clone(),
+    })
+}
+
+/// Tries to extract the artist name from the header of an artist page.
+pub(crate) fn get_artist_name(value: &Value) -> Option<String> {
+    let header = value
+        .get("musicImmersiveHeaderRenderer")
+        .or_else(|| value.get("musicVisualHeaderRenderer"))?;
+    get_text(header.get("title")?, false, false)
+}
+
+/// Tries to extract the id of the radio started by the button of an artist page.
+pub(crate) fn get_artist_radio_id(value: &Value) -> Option<String> {
+    value
+        .get("startRadioButton")?
+        .get("buttonRenderer")?
+        .get("navigationEndpoint")?
+        .get("watchPlaylistEndpoint")?
+        .get("playlistId")?
+        .as_str()
+        .map(|x| x.to_string())
+}
+
+/// Tries to extract a video of the queue of a watch next response (`playlistPanelVideoRenderer`).
+pub(crate) fn get_watch_next_video(value: &Value) -> Option<YoutubeMusicVideoRef> {
+    let renderer = value.get("playlistPanelVideoRenderer")?;
+    // The byline is `artist • album • year` for a song and `artist • views • likes` for a video
+    let byline = get_text(renderer.get("longBylineText")?, false, false)?;
+    let mut byline = byline.split(" • ").map(str::trim);
+    let author = byline.next()?.to_string();
+    let album = byline.next().unwrap_or_default().to_string();
+    let duration = renderer
+        .get("lengthText")
+        .and_then(|x| get_text(x, false, false))
+        .unwrap_or_default();
+    Some(YoutubeMusicVideoRef {
+        title: get_text(renderer.get("title")?, false, false)?,
+        author,
+        album,
+        video_id: renderer.get("videoId")?.as_str()?.to_string(),
+        duration_seconds: parse_duration_text(&duration),
+        duration,
+        thumbnail_url: get_thumbnail_url(renderer),
+        like_status: get_like_status(renderer),
+        explicit: is_explicit(renderer),
+        year: byline.find_map(|x| {
+            (x.len() == 4)
+                .then(|| x.parse().ok())
+                .flatten()
+                .filter(|year| (1900..2100).contains(year))
+        }),
+    })
+}
+
+/// Tries to find the browse id of the lyrics tab in a watch next response.
+pub(crate) fn get_lyrics_browse_id(value: &Value) -> Option<String> {
+    value
+        .get("browseEndpoint")
+        .and_then(|x| x.get("browseId"))
+        .and_then(Value::as_str)
+        .filter(|x| x.starts_with("MPLY"))
+        .map(|x| x.to_string())
+}
+
+/// Tries to extract the lyrics from a lyrics browse response.
+pub(crate) fn get_lyrics(value: &Value) -> Option<LyricsResult> {
+    let renderer = value
+        .get("musicDescriptionShelfRenderer")
+        .or_else(|| value.get("lyricsRenderer"))?;
+    // Lyrics are not joined with `get_text` as it would trim the line breaks
+    let text = renderer
+        .get("description")?
+        .get("runs")?
+        .as_array()?
+        .iter()
+        .flat_map(|x| x.get("text").and_then(Value::as_str))
+        .collect::<String>();
+    if text.trim().is_empty() {
+        return None;
+    }
+    Some(LyricsResult {
+        text,
+        source: renderer
+            .get("footer")
+            .and_then(|x| get_text(x, false, false))
+            .unwrap_or_default(),
+        is_synced: false,
     })
 }
 
@@ -230,5 +465,128 @@ pub(crate) fn get_video(value: &Value) -> Option<YoutubeMusicVideoRef> {
         author: texts.next()?,
         album: texts.next().unwrap_or_default(),
         duration: String::new(),
+        duration_seconds: get_duration_seconds(value),
+        thumbnail_url: get_thumbnail_url(value),
+        like_status: get_like_status(value),
+        explicit: is_explicit(value),
+        year: get_year(value),
     })
 }
+
+/// Whether the item has the explicit badge
+pub(crate) fn is_explicit(value: &Value) -> bool {
+    match value {
+        Value::Array(e) => e.iter().any(is_explicit),
+        Value::Object(e) => match e.get("musicInlineBadgeRenderer") {
+            Some(badge) => {
+                badge["icon"]["iconType"] == "MUSIC_EXPLICIT_BADGE"
+                    || badge["badgeStyle"] == "BADGE_STYLE_TYPE_CAUTION"
+            }
+            None => e.values().any(is_explicit),
+        },
+        _ => false,
+    }
+}
+
+/// Tries to find a release year in the subtitle runs of an item, the title is skipped
+pub(crate) fn get_year(value: &Value) -> Option<u16> {
+    value
+        .get("flexColumns")?
+        .as_array()?
+        .iter()
+        .skip(1)
+        .flat_map(|x| x.pointer("/musicResponsiveListItemFlexColumnRenderer/text/runs"))
+        .flat_map(Value::as_array)
+        .flatten()
+        .flat_map(|x| x.get("text").and_then(Value::as_str))
+        .find_map(|x| {
+            let x = x.trim();
+            (x.len() == 4)
+                .then(|| x.parse().ok())
+                .flatten()
+                .filter(|year| (1900..2100).contains(year))
+        })
+}
+
+/// Tries to find the rating of the video, given by the like button of its menu
+pub(crate) fn get_like_status(value: &Value) -> Option<LikeStatus> {
+    match value {
+        Value::Array(e) => e.iter().find_map(get_like_status),
+        Value::Object(e) => match e.get("likeStatus").and_then(Value::as_str) {
+            Some("LIKE") => Some(LikeStatus::Like),
+            Some("INDIFFERENT") => Some(LikeStatus::Indifferent),
+            Some("DISLIKE") => Some(LikeStatus::Dislike),
+            _ => e.values().find_map(get_like_status),
+        },
+        _ => None,
+    }
+}
+
+/// Tries to find the url of the largest thumbnail in the json
+pub(crate) fn get_thumbnail_url(value: &Value) -> Option<String> {
+    match value {
+        Value::Array(e) => e.iter().find_map(get_thumbnail_url),
+        Value::Object(e) => {
+            if let Some(thumbnails) = e.get("thumbnails").and_then(Value::as_array) {
+                let size = |x: &Value| {
+                    x.get("width").and_then(Value::as_u64).unwrap_or(0)
+                        * x.get("height").and_then(Value::as_u64).unwrap_or(0)
+                };
+                if let Some(url) = thumbnails
+                    .iter()
+                    .max_by_key(|x| size(x))
+                    .and_then(|x| x.get("url"))
+                    .and_then(Value::as_str)
+                {
+                    return Some(url.to_string());
+                }
+            }
+            e.values().find_map(get_thumbnail_url)
+        }
+        _ => None,
+    }
+}
+
+/// Tries to find the duration of a track in its fixed columns or in the runs of its subtitle.
+fn get_duration_seconds(value: &Value) -> Option<u32> {
+    let fixed_columns = value.get("fixedColumns").and_then(Value::as_array);
+    // The first flex column is the title
+    let flex_columns = value.get("flexColumns").and_then(Value::as_array);
+    fixed_columns
+        .into_iter()
+        .flatten()
+        .chain(flex_columns.into_iter().flatten().skip(1))
+        .filter_map(|x| {
+            x.as_object()?
+                .values()
+                .next()?
+                .get("text")?
+                .get("runs")?
+                .as_array()
+        })
+        .flatten()
+        .filter_map(|x| x.get("text").and_then(Value::as_str))
+        .find_map(parse_duration_text)
+}
+
+/// Parses a duration text such as `3:45` or `1:02:03` into seconds
+pub(crate) fn parse_duration_text(s: &str) -> Option<u32> {
+    let parts = s
+        .trim()
+        .split(':')
+        .map(|x| {
+            if x.is_empty() || !x.bytes().all(|x| x.is_ascii_digit()) {
+                None
+            } else {
+                x.parse::<u32>().ok()
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    match parts.as_slice() {
+        [minutes, seconds] if *seconds < 60 => Some(minutes * 60 + seconds),
+        [hours, minutes, seconds] if *minutes < 60 && *seconds < 60 => {
+            Some(hours * 3600 + minutes * 60 + seconds)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,163 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::{debug, trace};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{client_builder, ClientOptions, Result, YoutubeMusicError};
+
+const DEVICE_CODE_URL: &str = "https://oauth2.googleapis.com/device/code";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const SCOPE: &str = "https://www.googleapis.com/auth/youtube";
+/// The access token is refreshed this long before it expires
+const EXPIRY_MARGIN: u64 = 60;
+
+/// A Google OAuth client of the "TVs and Limited Input devices" type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+/// The code the user enters on the verification page to authorize the device
+#[derive(Debug, Clone)]
+pub struct DeviceCode {
+    pub verification_url: String,
+    pub user_code: String,
+    device_code: String,
+    /// Seconds to wait between two polls of the token endpoint
+    interval: u64,
+}
+
+/// The tokens of an authorized device, with the client needed to refresh them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OAuthToken {
+    pub client: OAuthClient,
+    pub access_token: String,
+    pub refresh_token: String,
+    /// When the access token expires, in seconds since the epoch
+    pub expires_at: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Builds a client with the given options for the requests to Google OAuth
+fn http_client(options: &ClientOptions) -> Result<reqwest::Client> {
+    client_builder(options)?
+        .build()
+        .map_err(YoutubeMusicError::RequestError)
+}
+
+/// Posts a form to an endpoint of Google OAuth, the errors are returned as `Ok` to be matched
+async fn post_form(client: &reqwest::Client, url: &str, form: &[(&str, &str)]) -> Result<Value> {
+    let response = client
+        .post(url)
+        .form(form)
+        .send()
+        .await
+        .map_err(YoutubeMusicError::RequestError)?
+        .text()
+        .await
+        .map_err(YoutubeMusicError::RequestError)?;
+    serde_json::from_str(&response).map_err(YoutubeMusicError::SerdeJson)
+}
+
+fn get_str(value: &Value, key: &str) -> Result<String> {
+    value[key]
+        .as_str()
+        .map(str::to_owned)
+        .ok_or_else(|| YoutubeMusicError::OAuthError(format!("No `{key}` in {value}")))
+}
+
+impl OAuthClient {
+    /// Starts the device authorization flow
+    pub async fn request_device_code(&self, options: &ClientOptions) -> Result<DeviceCode> {
+        let response = post_form(
+            &http_client(options)?,
+            DEVICE_CODE_URL,
+            &[("client_id", &self.client_id), ("scope", SCOPE)],
+        )
+        .await?;
+        Ok(DeviceCode {
+            verification_url: get_str(&response, "verification_url")?,
+            user_code: get_str(&response, "user_code")?,
+            device_code: get_str(&response, "device_code")?,
+            interval: response["interval"].as_u64().unwrap_or(5),
+        })
+    }
+
+    /// Polls the token endpoint until the user authorized the device, refused or the code expired
+    pub async fn wait_for_token(
+        &self,
+        code: &DeviceCode,
+        options: &ClientOptions,
+    ) -> Result<OAuthToken> {
+        let client = http_client(options)?;
+        let mut interval = code.interval;
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            let response = post_form(
+                &client,
+                TOKEN_URL,
+                &[
+                    ("client_id", &self.client_id),
+                    ("client_secret", &self.client_secret),
+                    ("device_code", &code.device_code),
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ],
+            )
+            .await?;
+            match response["error"].as_str() {
+                Some("authorization_pending") => trace!("Waiting for the authorization"),
+                Some("slow_down") => interval += 5,
+                Some(error) => return Err(YoutubeMusicError::OAuthError(error.to_owned())),
+                None => {
+                    return Ok(OAuthToken {
+                        client: self.clone(),
+                        access_token: get_str(&response, "access_token")?,
+                        refresh_token: get_str(&response, "refresh_token")?,
+                        expires_at: now() + response["expires_in"].as_u64().unwrap_or_default(),
+                    })
+                }
+            }
+        }
+    }
+}
+
+impl OAuthToken {
+    pub fn is_expired(&self) -> bool {
+        now() + EXPIRY_MARGIN >= self.expires_at
+    }
+
+    /// Gets a new access token with the refresh token
+    pub async fn refresh(&mut self, options: &ClientOptions) -> Result<()> {
+        self.refresh_with(&http_client(options)?).await
+    }
+
+    /// Same as `refresh`, reusing the client of an instance
+    pub(crate) async fn refresh_with(&mut self, client: &reqwest::Client) -> Result<()> {
+        debug!("Refreshing the OAuth access token");
+        let response = post_form(
+            client,
+            TOKEN_URL,
+            &[
+                ("client_id", &self.client.client_id),
+                ("client_secret", &self.client.client_secret),
+                ("refresh_token", &self.refresh_token),
+                ("grant_type", "refresh_token"),
+            ],
+        )
+        .await?;
+        if let Some(error) = response["error"].as_str() {
+            return Err(YoutubeMusicError::OAuthError(error.to_owned()));
+        }
+        self.access_token = get_str(&response, "access_token")?;
+        self.expires_at = now() + response["expires_in"].as_u64().unwrap_or_default();
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Options to throttle and retry the requests sent to YouTube Music
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitOptions {
+    /// Maximum number of requests sent per second.
+    /// A value of `0.0` disables the limiter.
+    pub requests_per_second: f64,
+    /// Number of times a request is retried after a 429 or 5xx response.
+    pub max_retries: u8,
+    /// Time to wait before the first retry, doubled on each following attempt.
+    pub initial_backoff: Duration,
+}
+
+impl Default for RateLimitOptions {
+    fn default() -> Self {
+        Self {
+            requests_per_second: 0.0,
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A token bucket shared by all the requests of a `YoutubeMusicInstance`
+pub(crate) struct RateLimiter {
+    pub(crate) options: RateLimitOptions,
+    bucket: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(options: RateLimitOptions) -> Self {
+        Self {
+            bucket: Mutex::new((options.requests_per_second.max(1.0), Instant::now())),
+            options,
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.options.requests_per_second.is_finite() && self.options.requests_per_second > 0.0
+    }
+
+    /// Waits until a token is available and consumes it
+    pub(crate) async fn acquire(&self) {
+        if !self.is_enabled() {
+            return;
+        }
+        let rate = self.options.requests_per_second;
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let (tokens, last_refill) = &mut *bucket;
+                let now = Instant::now();
+                *tokens = (*tokens + now.duration_since(*last_refill).as_secs_f64() * rate)
+                    .min(rate.max(1.0));
+                *last_refill = now;
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - *tokens) / rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Time to wait before the retry number `attempt` (starting at 0)
+    pub(crate) fn backoff(&self, attempt: u8) -> Duration {
+        self.options
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt as u32))
+    }
+}